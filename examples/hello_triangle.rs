@@ -1,11 +1,8 @@
-#[macro_use]
-extern crate softrender_derive;
-
-use glam::Vec3;
+use glam::{Mat4, Vec3};
 use softbuffer::GraphicsContext;
 use softrender::{
     renderer::Renderer,
-    shader::{Barycentric, Shader},
+    shaders::{ColorVertex, VertexColorShader},
 };
 use winit::{
     dpi::LogicalSize,
@@ -15,42 +12,6 @@ use winit::{
     window::WindowBuilder,
 };
 
-// This struct defines the results of our vertex shader that will then be passed in as inputs to the
-// fragment shader. Vertex outputs will be interpolated on a per-fragment basis by the rendering pipeline,
-// using barycentric coordinates.
-// These structs MUST derive the Barycentric trait.
-#[derive(Clone, Barycentric)]
-struct VertexOut {
-    color: glam::Vec3,
-}
-
-// This struct defined our input vertices, also known as vertex attributes.
-struct Vertex {
-    pos: glam::Vec3,
-    color: glam::Vec3,
-}
-
-// We define and implement a shader program here.
-// It requires two functions, one for each type of mandatory shader.
-// The vertex shader takes in one set of vertex attributes, and outputs at least
-// one vec4, and optionally any number of output parameters.
-// The fragment shader takes in the interpolated result from the vertex shader, and outputs a single color.
-struct MyShader;
-impl Shader<Vertex, VertexOut> for MyShader {
-    fn vertex(&self, vertex: &Vertex) -> (glam::Vec4, VertexOut) {
-        (
-            vertex.pos.extend(1.0),
-            VertexOut {
-                color: vertex.color,
-            },
-        )
-    }
-
-    fn fragment(&self, inputs: VertexOut) -> glam::UVec3 {
-        inputs.color.as_uvec3()
-    }
-}
-
 fn main() {
     // Construct winit window
     let mut event_loop = EventLoop::new();
@@ -61,24 +22,29 @@ fn main() {
         .expect("Failed to initialize window");
     let mut gc = unsafe { GraphicsContext::new(&window, &window).expect("Failed to create GC") };
 
-    // Create our renderer, as well as an example shader.
+    // Create our renderer, as well as a shader from the built-in library. It interpolates the color of
+    // each vertex across the triangle. The `Shader` trait can be implemented to write your own.
+    // Positions are already in clip space, so they need no transformation.
     let mut renderer = Renderer::new(800, 800);
-    let mut shader = MyShader {};
+    let mut shader = VertexColorShader {
+        mvp: Mat4::IDENTITY,
+    };
 
-    // Build the buffer data for our triangle
-    let mut vertices = Vec::new();
-    vertices.push(Vertex {
-        pos: Vec3::new(0.5, -0.5, 0.0),
-        color: Vec3::new(255.0, 0.0, 0.0),
-    });
-    vertices.push(Vertex {
-        pos: Vec3::new(0.0, 0.5, 0.0),
-        color: Vec3::new(0.0, 255.0, 0.0),
-    });
-    vertices.push(Vertex {
-        pos: Vec3::new(-0.5, -0.5, 0.0),
-        color: Vec3::new(0.0, 0.0, 255.0),
-    });
+    // Build the buffer data for our triangle. Colors range from 0 to 1.
+    let vertices = vec![
+        ColorVertex {
+            position: Vec3::new(0.5, -0.5, 0.0),
+            color: Vec3::new(1.0, 0.0, 0.0),
+        },
+        ColorVertex {
+            position: Vec3::new(0.0, 0.5, 0.0),
+            color: Vec3::new(0.0, 1.0, 0.0),
+        },
+        ColorVertex {
+            position: Vec3::new(-0.5, -0.5, 0.0),
+            color: Vec3::new(0.0, 0.0, 1.0),
+        },
+    ];
     let indices = [0, 1, 2];
 
     // Winit loop
@@ -87,12 +53,9 @@ fn main() {
 
         match event {
             Event::WindowEvent {
-                event: window_event,
+                event: WindowEvent::CloseRequested,
                 ..
-            } => match window_event {
-                WindowEvent::CloseRequested => cf.set_exit(),
-                _ => (),
-            },
+            } => cf.set_exit(),
 
             Event::MainEventsCleared => {
                 // Render a frame.
@@ -102,8 +65,8 @@ fn main() {
                 let color_buf = renderer.draw(&mut shader, &vertices, &indices);
                 gc.set_buffer(
                     color_buf.get_raw(),
-                    color_buf.get_width(),
-                    color_buf.get_height(),
+                    color_buf.get_width() as u16,
+                    color_buf.get_height() as u16,
                 );
             }
             _ => (),
@@ -65,23 +65,24 @@ fn main() {
             .into_rgb8(),
     };
 
-    let mut vertices = Vec::new();
-    vertices.push(Vertex {
-        pos: Vec3::new(-0.5, -0.5, 0.0),
-        uv: Vec2::new(0.0, 0.0),
-    });
-    vertices.push(Vertex {
-        pos: Vec3::new(0.5, -0.5, 0.0),
-        uv: Vec2::new(1.0, 0.0),
-    });
-    vertices.push(Vertex {
-        pos: Vec3::new(-0.5, 0.5, 0.0),
-        uv: Vec2::new(0.0, 1.0),
-    });
-    vertices.push(Vertex {
-        pos: Vec3::new(0.5, 0.5, 0.0),
-        uv: Vec2::new(1.0, 1.0),
-    });
+    let vertices = vec![
+        Vertex {
+            pos: Vec3::new(-0.5, -0.5, 0.0),
+            uv: Vec2::new(0.0, 0.0),
+        },
+        Vertex {
+            pos: Vec3::new(0.5, -0.5, 0.0),
+            uv: Vec2::new(1.0, 0.0),
+        },
+        Vertex {
+            pos: Vec3::new(-0.5, 0.5, 0.0),
+            uv: Vec2::new(0.0, 1.0),
+        },
+        Vertex {
+            pos: Vec3::new(0.5, 0.5, 0.0),
+            uv: Vec2::new(1.0, 1.0),
+        },
+    ];
     let indices = [0, 1, 2, 2, 1, 3];
 
     event_loop.run_return(|event, _, cf| {
@@ -89,20 +90,17 @@ fn main() {
 
         match event {
             Event::WindowEvent {
-                event: window_event,
+                event: WindowEvent::CloseRequested,
                 ..
-            } => match window_event {
-                WindowEvent::CloseRequested => cf.set_exit(),
-                _ => (),
-            },
+            } => cf.set_exit(),
 
             Event::MainEventsCleared => {
                 renderer.clear_framebuffer(95 | 95 << 8 | 95 << 16);
                 let color_buf = renderer.draw(&mut shader, &vertices, &indices);
                 gc.set_buffer(
                     color_buf.get_raw(),
-                    color_buf.get_width(),
-                    color_buf.get_height(),
+                    color_buf.get_width() as u16,
+                    color_buf.get_height() as u16,
                 );
             }
             _ => (),
@@ -3,45 +3,209 @@ extern crate quote;
 extern crate syn;
 
 use proc_macro::TokenStream;
-use syn::{Data, DeriveInput};
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{parse_quote, Data, DeriveInput, Field, Fields, Index, Member};
 
-#[proc_macro_derive(Barycentric)]
+/// How a single field of a struct deriving Barycentric is interpolated
+#[derive(PartialEq)]
+enum Qualifier {
+    /// Perspective-correct interpolation, the default
+    Smooth,
+    /// Screen-space interpolation, requested with `#[noperspective]`
+    NoPerspective,
+    /// No interpolation at all, the value is taken from the provoking vertex. Requested with `#[flat]`
+    Flat,
+    /// Like flat, but for fields that cannot be interpolated at all. Requested with
+    /// `#[barycentric(skip)]`
+    Skip,
+}
+
+fn field_qualifier(field: &Field) -> syn::Result<Qualifier> {
+    let mut qualifier = Qualifier::Smooth;
+    let mut set_qualifier = |new: Qualifier, span: &dyn quote::ToTokens| {
+        if qualifier != Qualifier::Smooth {
+            return Err(syn::Error::new_spanned(
+                span,
+                "a field may only have one interpolation qualifier",
+            ));
+        }
+        qualifier = new;
+        Ok(())
+    };
+
+    for attr in &field.attrs {
+        if attr.path().is_ident("flat") {
+            attr.meta.require_path_only()?;
+            set_qualifier(Qualifier::Flat, attr)?;
+        } else if attr.path().is_ident("noperspective") {
+            attr.meta.require_path_only()?;
+            set_qualifier(Qualifier::NoPerspective, attr)?;
+        } else if attr.path().is_ident("barycentric") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    set_qualifier(Qualifier::Skip, attr)
+                } else if meta.path.is_ident("flat") {
+                    set_qualifier(Qualifier::Flat, attr)
+                } else if meta.path.is_ident("noperspective") {
+                    set_qualifier(Qualifier::NoPerspective, attr)
+                } else {
+                    Err(meta.error(
+                        "unknown barycentric option, expected `skip`, `flat` or `noperspective`",
+                    ))
+                }
+            })?;
+        }
+    }
+
+    Ok(qualifier)
+}
+
+/// Builds an instance of the struct out of one expression per field, in declaration order
+fn construct(struct_name: &syn::Ident, fields: &Fields, values: &[TokenStream2]) -> TokenStream2 {
+    match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote::quote!(#struct_name { #(#names: #values,)* })
+        }
+        Fields::Unnamed(_) => quote::quote!(#struct_name ( #(#values,)* )),
+        Fields::Unit => quote::quote!(#struct_name),
+    }
+}
+
+#[proc_macro_derive(Barycentric, attributes(flat, noperspective, barycentric))]
 pub fn barycentric_impl(input: TokenStream) -> TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
-    let Data::Struct(data) = ast.data else { unimplemented!() };
+    match expand(ast) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(err) => TokenStream::from(err.to_compile_error()),
+    }
+}
+
+fn expand(ast: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &ast.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(_) | Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "Barycentric can only be derived for structs",
+            ))
+        }
+    };
+
+    let struct_name = &ast.ident;
+    let qualifiers = fields
+        .iter()
+        .map(field_qualifier)
+        .collect::<syn::Result<Vec<_>>>()?;
+    // Tuple struct fields have no ident, and are instead accessed by their index
+    let members: Vec<Member> = fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(idx)),
+        })
+        .collect();
+
+    // Every field that is interpolated must itself be Barycentric, and every field that is copied
+    // from a single vertex must be Clone. Bounding the field types, rather than the generic
+    // parameters, means generic structs only require what they actually use.
+    let mut generics = ast.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for (field, qualifier) in fields.iter().zip(&qualifiers) {
+        let ty = &field.ty;
+        match qualifier {
+            Qualifier::Smooth | Qualifier::NoPerspective => {
+                where_clause.predicates.push(parse_quote!(#ty: Barycentric))
+            }
+            Qualifier::Flat | Qualifier::Skip => {
+                where_clause.predicates.push(parse_quote!(#ty: Clone))
+            }
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let struct_name = ast.ident;
     // First we construct the body of the impl, line-by-line, to simply recursively call
     // interpolated on every field it contains, using that same field in the other two
-    // scalars provided (second and third)
-    let fields_tri = data.fields.iter().map(|field| {
-        let field_name = &field.ident;
-        quote::quote!(
-         #field_name: self.#field_name.interpolated(lambda, &second.#field_name, &third.#field_name))
-    });
-    let fields_line = data.fields.iter().map(|field| {
-        let field_name = &field.ident;
-        quote::quote!(
-         #field_name: self.#field_name.line_interpolated(lambda, &second.#field_name))
-    });
+    // scalars provided (second and third). Flat and skipped fields are never interpolated,
+    // so they are simply copied from the first vertex.
+    let mut fields_tri = Vec::new();
+    let mut fields_line = Vec::new();
+    // The qualified variants pick which set of barycentric coordinates each field is interpolated
+    // with, and take flat and skipped fields from the provoking vertex.
+    let mut fields_tri_qualified = Vec::new();
+    let mut fields_line_qualified = Vec::new();
+    for (member, qualifier) in members.iter().zip(&qualifiers) {
+        match qualifier {
+            Qualifier::Smooth | Qualifier::NoPerspective => {
+                fields_tri.push(quote::quote!(
+                    self.#member.interpolated(lambda, &second.#member, &third.#member)));
+                fields_line.push(quote::quote!(
+                    self.#member.line_interpolated(lambda, &second.#member)));
+            }
+            Qualifier::Flat | Qualifier::Skip => {
+                fields_tri.push(quote::quote!(self.#member.clone()));
+                fields_line.push(quote::quote!(self.#member.clone()));
+            }
+        }
+        match qualifier {
+            Qualifier::Smooth => {
+                fields_tri_qualified.push(quote::quote!(
+                    self.#member.interpolated_qualified(
+                        perspective, linear, &second.#member, &third.#member, &provoking.#member)));
+                fields_line_qualified.push(quote::quote!(
+                    self.#member.line_interpolated_qualified(
+                        perspective, linear, &second.#member, &provoking.#member)));
+            }
+            Qualifier::NoPerspective => {
+                fields_tri_qualified.push(quote::quote!(
+                    self.#member.interpolated(linear, &second.#member, &third.#member)));
+                fields_line_qualified.push(quote::quote!(
+                    self.#member.line_interpolated(linear, &second.#member)));
+            }
+            Qualifier::Flat | Qualifier::Skip => {
+                fields_tri_qualified.push(quote::quote!(provoking.#member.clone()));
+                fields_line_qualified.push(quote::quote!(provoking.#member.clone()));
+            }
+        }
+    }
+
+    let tri = construct(struct_name, fields, &fields_tri);
+    let line = construct(struct_name, fields, &fields_line);
+    let tri_qualified = construct(struct_name, fields, &fields_tri_qualified);
+    let line_qualified = construct(struct_name, fields, &fields_line_qualified);
 
     // Once all the individual recursive calls are constructed, we construct the impl itself,
     // returning a new instance of struct_name with the result of the recursive calls to interpolated
-    // as its fields
-    let expanded = quote::quote!(
-      impl Barycentric for #struct_name {
+    // as its fields. Unit structs never read their arguments.
+    Ok(quote::quote!(
+      #[allow(unused_variables)]
+      impl #impl_generics Barycentric for #struct_name #ty_generics #where_clause {
          fn interpolated(&self, lambda: glam::Vec3, second: &Self, third: &Self) -> Self {
-            #struct_name {
-               #(#fields_tri,)*
-            }
+            #tri
          }
          fn line_interpolated(&self, lambda: glam::Vec2, second: &Self) -> Self {
-            #struct_name {
-               #(#fields_line,)*
-            }
+            #line
+         }
+         fn interpolated_qualified(
+            &self,
+            perspective: glam::Vec3,
+            linear: glam::Vec3,
+            second: &Self,
+            third: &Self,
+            provoking: &Self,
+         ) -> Self {
+            #tri_qualified
+         }
+         fn line_interpolated_qualified(
+            &self,
+            perspective: glam::Vec2,
+            linear: glam::Vec2,
+            second: &Self,
+            provoking: &Self,
+         ) -> Self {
+            #line_qualified
          }
       }
-    );
-
-    TokenStream::from(expanded)
+    ))
 }
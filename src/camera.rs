@@ -0,0 +1,307 @@
+//! Cameras, and controllers that move them in response to user input.
+//!
+//! A `Camera` looks down its local -Z axis, with +Y up and +X to the right, matching the right-handed
+//! conventions of glam. Controllers own the state that input manipulates, such as yaw and pitch, and
+//! write the resulting position and orientation to a camera with `update`.
+
+use glam::{EulerRot, Mat4, Quat, Vec2, Vec3};
+
+use crate::math::Frustum;
+
+/// Largest pitch that controllers allow, just short of straight up or down, where yaw would become
+/// ambiguous
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// A perspective projection with a vertical field of view, in radians
+    PERSPECTIVE { fov_y: f32, near: f32, far: f32 },
+    /// An orthographic projection showing `height` world units vertically
+    ORTHOGRAPHIC { height: f32, near: f32, far: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    position: Vec3,
+    orientation: Quat,
+    projection: Projection,
+    aspect_ratio: f32,
+}
+
+impl Camera {
+    /// Creates a perspective camera at `position`, looking down -Z
+    pub fn perspective(fov_y: f32, aspect_ratio: f32, near: f32, far: f32, position: Vec3) -> Self {
+        Camera {
+            position,
+            orientation: Quat::IDENTITY,
+            projection: Projection::PERSPECTIVE { fov_y, near, far },
+            aspect_ratio,
+        }
+    }
+
+    /// Creates an orthographic camera at `position`, looking down -Z
+    pub fn orthographic(
+        height: f32,
+        aspect_ratio: f32,
+        near: f32,
+        far: f32,
+        position: Vec3,
+    ) -> Self {
+        Camera {
+            position,
+            orientation: Quat::IDENTITY,
+            projection: Projection::ORTHOGRAPHIC { height, near, far },
+            aspect_ratio,
+        }
+    }
+
+    /// Turns the camera to face `target`, keeping its up direction as close to `up` as possible
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let view = Mat4::look_at_rh(self.position, target, up);
+        // The view matrix is the inverse of the camera's rotation, if its position is removed
+        let (_, rotation, _) = view.inverse().to_scale_rotation_translation();
+        if rotation.is_finite() {
+            self.orientation = rotation.normalize();
+        }
+    }
+
+    pub fn get_position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn get_orientation(&self) -> Quat {
+        self.orientation
+    }
+
+    pub fn set_orientation(&mut self, orientation: Quat) {
+        self.orientation = orientation.normalize();
+    }
+
+    pub fn get_projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.aspect_ratio = aspect_ratio;
+    }
+
+    /// Updates the aspect ratio to match a resized framebuffer or window. Empty sizes, such as those
+    /// of a minimized window, are ignored.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect_ratio = width as f32 / height as f32;
+        }
+    }
+
+    /// The direction the camera is looking in
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::NEG_Z
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.orientation * Vec3::X
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::Y
+    }
+
+    /// Transforms world space into the camera's view space
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.orientation, self.position).inverse()
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        match self.projection {
+            Projection::PERSPECTIVE { fov_y, near, far } => {
+                Mat4::perspective_rh(fov_y, self.aspect_ratio, near, far)
+            }
+            Projection::ORTHOGRAPHIC { height, near, far } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect_ratio;
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
+
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// The volume of world space that the camera can see, for culling objects outside of it
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_projection_matrix())
+    }
+}
+
+/// A first-person controller, which turns the camera with yaw, pitch and roll, and moves it relative
+/// to the direction it faces
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlyController {
+    pub position: Vec3,
+    /// Rotation about the world Y axis, in radians. Zero looks down -Z.
+    pub yaw: f32,
+    /// Rotation above or below the horizon, in radians
+    pub pitch: f32,
+    /// Rotation about the view direction, in radians
+    pub roll: f32,
+}
+
+impl FlyController {
+    pub fn new(position: Vec3) -> Self {
+        FlyController {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+        }
+    }
+
+    /// Turns the view, clamping the pitch short of straight up or down
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Tilts the view about the direction it faces
+    pub fn roll(&mut self, angle: f32) {
+        self.roll += angle;
+    }
+
+    pub fn orientation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, self.roll)
+    }
+
+    /// Moves relative to the current view, where x is to the right, y is up and z is forward
+    pub fn translate(&mut self, delta: Vec3) {
+        let orientation = self.orientation();
+        self.position += orientation * Vec3::new(delta.x, delta.y, -delta.z);
+    }
+
+    /// Writes the controller's position and orientation to a camera
+    pub fn update(&self, camera: &mut Camera) {
+        camera.set_position(self.position);
+        camera.set_orientation(self.orientation());
+    }
+}
+
+/// A controller that circles the camera around a target, always facing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitController {
+    pub target: Vec3,
+    pub distance: f32,
+    /// Rotation about the world Y axis, in radians. Zero places the camera on the +Z side of the
+    /// target.
+    pub yaw: f32,
+    /// Elevation above the target, in radians
+    pub pitch: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    /// Circles around the target, clamping the pitch short of directly above or below it
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Scales the distance to the target, where factors below one move closer
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).max(f32::EPSILON);
+    }
+
+    /// Moves the target within the view plane, by a distance proportional to how far away it is so
+    /// that panning feels the same at any zoom
+    pub fn pan(&mut self, delta: Vec2) {
+        let orientation = self.orientation();
+        self.target += orientation * delta.extend(0.0) * self.distance;
+    }
+
+    pub fn orientation(&self) -> Quat {
+        // Pitching up to look down at the target is a negative rotation about X
+        Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.0)
+    }
+
+    pub fn update(&self, camera: &mut Camera) {
+        let orientation = self.orientation();
+        camera.set_position(self.target + orientation * Vec3::Z * self.distance);
+        camera.set_orientation(orientation);
+    }
+}
+
+/// A controller that rotates the camera around a target as if dragging a ball, allowing any
+/// orientation including upside down
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArcballController {
+    pub target: Vec3,
+    pub distance: f32,
+    pub orientation: Quat,
+}
+
+impl ArcballController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        ArcballController {
+            target,
+            distance,
+            orientation: Quat::IDENTITY,
+        }
+    }
+
+    /// Maps a point in normalized device coordinates onto a unit ball facing the viewer
+    fn project_to_ball(point: Vec2) -> Vec3 {
+        let length_squared = point.length_squared();
+        if length_squared <= 1.0 {
+            point.extend((1.0 - length_squared).sqrt())
+        } else {
+            // Points outside the ball lie on its rim
+            (point / length_squared.sqrt()).extend(0.0)
+        }
+    }
+
+    /// Rotates the view as if the ball was dragged from one point to another, both in normalized
+    /// device coordinates
+    pub fn drag(&mut self, from: Vec2, to: Vec2) {
+        let rotation =
+            Quat::from_rotation_arc(Self::project_to_ball(from), Self::project_to_ball(to));
+        // Turning the scene one way is the same as turning the camera the other way
+        self.orientation = (self.orientation * rotation.inverse()).normalize();
+    }
+
+    /// Scales the distance to the target, where factors below one move closer
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).max(f32::EPSILON);
+    }
+
+    pub fn update(&self, camera: &mut Camera) {
+        camera.set_position(self.target + self.orientation * Vec3::Z * self.distance);
+        camera.set_orientation(self.orientation);
+    }
+}
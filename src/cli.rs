@@ -0,0 +1,188 @@
+//! Argument parsing and camera framing for the `softrender` command-line renderer. Requires the `cli`
+//! feature.
+
+use std::path::PathBuf;
+
+use glam::Vec3;
+
+use crate::{camera::Camera, math::Aabb};
+
+pub const USAGE: &str = "\
+Usage: softrender <MODEL.obj> [OPTIONS]
+
+Renders an OBJ model without a window, and writes the result to an image file.
+
+Options:
+  -o, --output <PATH>      Image to write, as .png, .ppm, .pam, .bmp or .tga [default: out.png]
+  -m, --mode <MODE>        Shading mode: flat, normals, textured or wireframe [default: flat]
+  -t, --texture <PATH>     Texture for the textured mode, as .png or .ppm [default: the
+                           diffuse textures of the model's materials]
+      --width <PIXELS>     Width of the image [default: 800]
+      --height <PIXELS>    Height of the image [default: 800]
+      --eye <X,Y,Z>        Camera position [default: framed to fit the model]
+      --target <X,Y,Z>     Point the camera looks at [default: center of the model]
+      --up <X,Y,Z>         Up direction of the camera [default: 0,1,0]
+      --fov <DEGREES>      Vertical field of view [default: 50]
+      --background <HEX>   Background color as RRGGBB [default: 202020]
+  -h, --help               Print this message";
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingMode {
+    /// Lit by a light at the camera, with the normal of each face
+    FLAT,
+    /// The interpolated normal as a color
+    NORMALS,
+    /// The diffuse texture, unlit
+    TEXTURED,
+    /// White edges only
+    WIREFRAME,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub model: PathBuf,
+    pub output: PathBuf,
+    pub mode: ShadingMode,
+    /// Replaces the diffuse textures of the model's materials in the textured mode
+    pub texture: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
+    pub eye: Option<Vec3>,
+    pub target: Option<Vec3>,
+    pub up: Vec3,
+    /// Vertical field of view, in degrees
+    pub fov: f32,
+    pub background: u32,
+}
+
+fn parse_vec3(value: &str) -> Result<Vec3, String> {
+    let components = value
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid vector \"{value}\""))?;
+    match components[..] {
+        [x, y, z] if x.is_finite() && y.is_finite() && z.is_finite() => Ok(Vec3::new(x, y, z)),
+        [_, _, _] => Err(format!("invalid vector \"{value}\"")),
+        _ => Err(format!("expected three components in \"{value}\"")),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value \"{value}\" for {flag}"))
+}
+
+/// Parses the command-line arguments, not including the program name
+///
+/// Returns `None` if help was asked for, or an error message for invalid arguments.
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        model: PathBuf::new(),
+        output: PathBuf::from("out.png"),
+        mode: ShadingMode::FLAT,
+        texture: None,
+        width: 800,
+        height: 800,
+        eye: None,
+        target: None,
+        up: Vec3::Y,
+        fov: 50.0,
+        background: 0x202020,
+    };
+    let mut model = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        if !arg.starts_with('-') {
+            if model.replace(PathBuf::from(&arg)).is_some() {
+                return Err(format!("unexpected argument \"{arg}\""));
+            }
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {arg}"))?;
+        match arg.as_str() {
+            "-o" | "--output" => options.output = PathBuf::from(value),
+            "-m" | "--mode" => {
+                options.mode = match value.as_str() {
+                    "flat" => ShadingMode::FLAT,
+                    "normals" => ShadingMode::NORMALS,
+                    "textured" => ShadingMode::TEXTURED,
+                    "wireframe" => ShadingMode::WIREFRAME,
+                    _ => return Err(format!("unknown mode \"{value}\"")),
+                }
+            }
+            "-t" | "--texture" => options.texture = Some(PathBuf::from(value)),
+            "--width" => options.width = parse_number(&arg, &value)?,
+            "--height" => options.height = parse_number(&arg, &value)?,
+            "--eye" => options.eye = Some(parse_vec3(&value)?),
+            "--target" => options.target = Some(parse_vec3(&value)?),
+            "--up" => options.up = parse_vec3(&value)?,
+            "--fov" => options.fov = parse_number(&arg, &value)?,
+            "--background" => {
+                options.background = u32::from_str_radix(value.trim_start_matches('#'), 16)
+                    .ok()
+                    .filter(|color| *color <= 0xFFFFFF)
+                    .ok_or_else(|| format!("invalid color \"{value}\""))?
+            }
+            _ => return Err(format!("unknown option \"{arg}\"")),
+        }
+    }
+
+    options.model = model.ok_or("no model given")?;
+    if options.width == 0 || options.height == 0 {
+        return Err("the image must be at least one pixel in size".to_string());
+    }
+    if !(options.fov > 0.0 && options.fov < 180.0) {
+        return Err("the field of view must lie between 0 and 180 degrees".to_string());
+    }
+    Ok(Some(options))
+}
+
+/// Places the camera described by `options`, looking at a model with the given bounds
+///
+/// Without an eye position, the camera backs away from the target until the model's bounding sphere
+/// fits the field of view. A model without any size, such as a single point, is framed as if it were
+/// a unit sphere. The near and far planes are fitted around the bounding sphere.
+pub fn frame_camera(options: &Options, bounds: &Aabb) -> Result<Camera, String> {
+    let center = (bounds.min + bounds.max) / 2.0;
+    // Far from the origin, a model too small for f32 to place the camera apart from it is enlarged
+    let radius = ((bounds.max - bounds.min).length() / 2.0).max(center.abs().max_element() * 1e-4);
+    let radius = if radius > 0.0 { radius } else { 1.0 };
+
+    let fov = options.fov.to_radians();
+    let aspect = options.width as f32 / options.height as f32;
+    let fit_fov = if aspect < 1.0 {
+        // Narrow images are limited by their horizontal field of view
+        2.0 * ((fov / 2.0).tan() * aspect).atan()
+    } else {
+        fov
+    };
+    let target = options.target.unwrap_or(center);
+    let eye = options.eye.unwrap_or_else(|| {
+        let distance = radius / (fit_fov / 2.0).sin();
+        target + Vec3::new(0.0, 0.3, 1.0).normalize() * distance
+    });
+    let distance = eye.distance(target);
+    if distance == 0.0 {
+        return Err("the camera's eye and target must not be the same point".to_string());
+    }
+    if (target - eye).cross(options.up).length_squared() == 0.0 {
+        return Err("the up direction must not be parallel to the view direction".to_string());
+    }
+    // Fit the depth range around the bounding sphere, wherever the camera is looking
+    let depth = eye.distance(center);
+    let near = (depth - radius).max(depth.max(distance) * 0.001);
+    let far = (depth + radius * 2.0).max(near * 2.0);
+
+    let mut camera = Camera::perspective(fov, aspect, near, far, eye);
+    camera.look_at(target, options.up);
+    Ok(camera)
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+
+/// Errors that can occur while drawing into or accessing a framebuffer, or while loading and saving
+/// resources
+#[derive(Debug)]
+pub enum Error {
+    /// A pixel coordinate lies outside the bounds of a framebuffer
+    PixelOutOfBounds {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// A rectangle does not lie entirely within a framebuffer
+    RegionOutOfBounds {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        fb_width: u32,
+        fb_height: u32,
+    },
+    /// The row stride of a view is smaller than its width
+    InvalidStride { stride: usize, width: u32 },
+    /// A buffer is too small to hold an image of the requested size and stride
+    BufferTooSmall { required: usize, len: usize },
+    /// Two images that must be the same size are not
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// An image could not be read or written
+    Io(std::io::Error),
+    /// A file extension does not name a supported image format
+    UnsupportedFormat { extension: String },
+    /// An image file could not be decoded
+    MalformedImage { reason: String },
+    /// A model file could not be parsed
+    MalformedModel { line: usize, reason: String },
+    /// A scene file could not be loaded, for a reason not tied to any one line
+    MalformedScene { reason: String },
+    /// An index buffer references a vertex past the end of the vertex buffer
+    IndexOutOfBounds {
+        /// The position of the offending index within the index buffer
+        position: usize,
+        index: u32,
+        vertex_count: usize,
+    },
+    /// The length of an index buffer is not a multiple of the number of vertices per primitive
+    IncompletePrimitive {
+        index_count: usize,
+        vertices_per_primitive: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::PixelOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "pixel ({x}, {y}) is out of bounds of a {width}x{height} framebuffer"
+            ),
+            Error::RegionOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+                fb_width,
+                fb_height,
+            } => write!(
+                f,
+                "{width}x{height} region at ({x}, {y}) is out of bounds of a \
+                 {fb_width}x{fb_height} framebuffer"
+            ),
+            Error::InvalidStride { stride, width } => {
+                write!(f, "stride {stride} is smaller than the width {width}")
+            }
+            Error::BufferTooSmall { required, len } => write!(
+                f,
+                "buffer of {len} elements is too small, {required} are required"
+            ),
+            Error::SizeMismatch { expected, actual } => write!(
+                f,
+                "expected an image of size {}x{}, found {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            Error::Io(err) => write!(f, "i/o error: {err}"),
+            Error::UnsupportedFormat { extension } => {
+                write!(f, "unsupported image format \"{extension}\"")
+            }
+            Error::MalformedImage { reason } => write!(f, "malformed image: {reason}"),
+            Error::MalformedModel { line, reason } => {
+                write!(f, "malformed model on line {line}: {reason}")
+            }
+            Error::MalformedScene { reason } => write!(f, "malformed scene: {reason}"),
+            Error::IndexOutOfBounds {
+                position,
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "index {index} at position {position} of the index buffer is out of bounds of a \
+                 vertex buffer with {vertex_count} vertices"
+            ),
+            Error::IncompletePrimitive {
+                index_count,
+                vertices_per_primitive,
+            } => write!(
+                f,
+                "index buffer with {index_count} indices does not describe a whole number of \
+                 primitives with {vertices_per_primitive} vertices each"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,341 @@
+//! Saving framebuffers as image files.
+//!
+//! Color buffers can be written as PPM, PAM, BMP and TGA without any dependencies, and as PNG when the
+//! `png` feature is enabled. Depth buffers can be written as normalized grayscale or as raw floats in
+//! the PFM format. Every format is written so that pixel (0, 0) appears at the bottom left of the
+//! image, matching `Framebuffer::plot_pixel`.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{Error, Result},
+    fb::{Framebuffer, FramebufferView},
+    math::unpack_color,
+};
+
+/// File formats that a color buffer can be saved as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Binary portable pixmap (P6)
+    PPM,
+    /// Portable arbitrary map (P7), with an RGB tuple type
+    PAM,
+    /// Uncompressed 24-bit Windows bitmap
+    BMP,
+    /// Uncompressed 24-bit Truevision TGA
+    TGA,
+    /// 8-bit RGB PNG
+    #[cfg(feature = "png")]
+    PNG,
+}
+
+/// File formats that a depth buffer can be saved as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthFormat {
+    /// 16-bit portable graymap (P5), with depths normalized so the nearest is black and the farthest
+    /// is white
+    PGM,
+    /// Portable float map, holding the raw depth values
+    PFM,
+    /// 16-bit grayscale PNG, normalized in the same way as `PGM`
+    #[cfg(feature = "png")]
+    PNG,
+}
+
+/// Picks a format from the extension of a path, ignoring case
+fn extension_of(path: &Path) -> Result<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .ok_or_else(|| Error::UnsupportedFormat {
+            extension: String::new(),
+        })
+}
+
+impl ImageFormat {
+    /// Infers the format of an image from the extension of its path
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let extension = extension_of(path.as_ref())?;
+        match extension.as_str() {
+            "ppm" => Ok(ImageFormat::PPM),
+            "pam" => Ok(ImageFormat::PAM),
+            "bmp" => Ok(ImageFormat::BMP),
+            "tga" => Ok(ImageFormat::TGA),
+            #[cfg(feature = "png")]
+            "png" => Ok(ImageFormat::PNG),
+            _ => Err(Error::UnsupportedFormat { extension }),
+        }
+    }
+}
+
+impl DepthFormat {
+    /// Infers the format of a depth image from the extension of its path
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let extension = extension_of(path.as_ref())?;
+        match extension.as_str() {
+            "pgm" => Ok(DepthFormat::PGM),
+            "pfm" => Ok(DepthFormat::PFM),
+            #[cfg(feature = "png")]
+            "png" => Ok(DepthFormat::PNG),
+            _ => Err(Error::UnsupportedFormat { extension }),
+        }
+    }
+}
+
+/// Opens a buffered file for writing, and flushes it once `write` has finished
+fn save_with(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Calls `f` with every row of the view, from the top of the image down
+fn for_each_row_top_down<T: Copy>(
+    view: &FramebufferView<'_, T>,
+    mut f: impl FnMut(&[T]) -> Result<()>,
+) -> Result<()> {
+    for y in (0..view.get_height()).rev() {
+        f(view.row(y))?;
+    }
+    Ok(())
+}
+
+/// Calls `f` with every row of the view, from the bottom of the image up
+fn for_each_row_bottom_up<T: Copy>(
+    view: &FramebufferView<'_, T>,
+    mut f: impl FnMut(&[T]) -> Result<()>,
+) -> Result<()> {
+    for y in 0..view.get_height() {
+        f(view.row(y))?;
+    }
+    Ok(())
+}
+
+/// Converts a row of packed colors into RGB, or BGR if `bgr` is set, byte triples
+fn row_bytes(row: &[u32], bgr: bool, out: &mut Vec<u8>) {
+    out.clear();
+    for &color in row {
+        let rgb = unpack_color(color);
+        let (r, g, b) = (rgb.x as u8, rgb.y as u8, rgb.z as u8);
+        if bgr {
+            out.extend_from_slice(&[b, g, r]);
+        } else {
+            out.extend_from_slice(&[r, g, b]);
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+fn png_error(err: png::EncodingError) -> Error {
+    match err {
+        png::EncodingError::IoError(err) => Error::Io(err),
+        err => Error::Io(std::io::Error::other(err)),
+    }
+}
+
+impl FramebufferView<'_, u32> {
+    /// Writes the colors of the view as an image in the given format
+    pub fn write_image<W: Write>(&self, writer: &mut W, format: ImageFormat) -> Result<()> {
+        let (width, height) = (self.get_width(), self.get_height());
+        let mut bytes = Vec::with_capacity(width as usize * 3);
+        match format {
+            ImageFormat::PPM => {
+                write!(writer, "P6\n{width} {height}\n255\n")?;
+                for_each_row_top_down(self, |row| {
+                    row_bytes(row, false, &mut bytes);
+                    Ok(writer.write_all(&bytes)?)
+                })
+            }
+            ImageFormat::PAM => {
+                write!(
+                    writer,
+                    "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH 3\nMAXVAL 255\nTUPLTYPE RGB\nENDHDR\n"
+                )?;
+                for_each_row_top_down(self, |row| {
+                    row_bytes(row, false, &mut bytes);
+                    Ok(writer.write_all(&bytes)?)
+                })
+            }
+            ImageFormat::BMP => {
+                // Rows are padded to a multiple of four bytes
+                let row_size = (width as usize * 3).next_multiple_of(4);
+                let image_size = row_size * height as usize;
+                let file_size = u32::try_from(54 + image_size).map_err(|_| {
+                    Error::Io(std::io::Error::other("image is too large for a bitmap"))
+                })?;
+
+                // File header
+                writer.write_all(b"BM")?;
+                writer.write_all(&file_size.to_le_bytes())?;
+                writer.write_all(&[0; 4])?;
+                writer.write_all(&54u32.to_le_bytes())?;
+                // Info header. A positive height stores the rows from the bottom up.
+                writer.write_all(&40u32.to_le_bytes())?;
+                writer.write_all(&(width as i32).to_le_bytes())?;
+                writer.write_all(&(height as i32).to_le_bytes())?;
+                writer.write_all(&1u16.to_le_bytes())?;
+                writer.write_all(&24u16.to_le_bytes())?;
+                writer.write_all(&0u32.to_le_bytes())?;
+                writer.write_all(&(image_size as u32).to_le_bytes())?;
+                // 72 DPI, in pixels per metre
+                writer.write_all(&2835u32.to_le_bytes())?;
+                writer.write_all(&2835u32.to_le_bytes())?;
+                writer.write_all(&[0; 8])?;
+
+                for_each_row_bottom_up(self, |row| {
+                    row_bytes(row, true, &mut bytes);
+                    bytes.resize(row_size, 0);
+                    Ok(writer.write_all(&bytes)?)
+                })
+            }
+            ImageFormat::TGA => {
+                let (Ok(tga_width), Ok(tga_height)) = (u16::try_from(width), u16::try_from(height))
+                else {
+                    return Err(Error::Io(std::io::Error::other(
+                        "image is too large for a TGA file",
+                    )));
+                };
+                // No image ID or color map, uncompressed true color
+                writer.write_all(&[0, 0, 2])?;
+                writer.write_all(&[0; 5])?;
+                // Origin, then dimensions
+                writer.write_all(&[0; 4])?;
+                writer.write_all(&tga_width.to_le_bytes())?;
+                writer.write_all(&tga_height.to_le_bytes())?;
+                // 24 bits per pixel, and a descriptor of zero places the first row at the bottom
+                writer.write_all(&[24, 0])?;
+
+                for_each_row_bottom_up(self, |row| {
+                    row_bytes(row, true, &mut bytes);
+                    Ok(writer.write_all(&bytes)?)
+                })
+            }
+            #[cfg(feature = "png")]
+            ImageFormat::PNG => {
+                let mut encoder = png::Encoder::new(writer, width, height);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                let mut png_writer = encoder.write_header().map_err(png_error)?;
+                let mut stream = png_writer.stream_writer().map_err(png_error)?;
+                for_each_row_top_down(self, |row| {
+                    row_bytes(row, false, &mut bytes);
+                    Ok(stream.write_all(&bytes)?)
+                })?;
+                stream.finish().map_err(png_error)
+            }
+        }
+    }
+
+    /// Saves the colors of the view to a file, picking the format from its extension
+    pub fn save_image(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path)?;
+        save_with(path, |writer| self.write_image(writer, format))
+    }
+}
+
+impl FramebufferView<'_, f32> {
+    /// Finds the range of finite depths in the view, to normalize them over
+    fn depth_range(&self) -> (f32, f32) {
+        let mut range = (f32::INFINITY, f32::NEG_INFINITY);
+        for y in 0..self.get_height() {
+            for &depth in self.row(y).iter().filter(|depth| depth.is_finite()) {
+                range = (range.0.min(depth), range.1.max(depth));
+            }
+        }
+        range
+    }
+
+    /// Converts a row of depths to 16-bit big-endian grayscale, mapping `min` to black and `max` to
+    /// white
+    fn normalized_row_bytes(row: &[f32], (min, max): (f32, f32), out: &mut Vec<u8>) {
+        out.clear();
+        let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+        for &depth in row {
+            let value = if depth.is_nan() {
+                0.0
+            } else {
+                ((depth - min) * scale).clamp(0.0, 1.0)
+            };
+            out.extend_from_slice(&((value * 65535.0).round() as u16).to_be_bytes());
+        }
+    }
+
+    /// Writes the depths of the view as an image in the given format
+    pub fn write_depth_image<W: Write>(&self, writer: &mut W, format: DepthFormat) -> Result<()> {
+        let (width, height) = (self.get_width(), self.get_height());
+        let mut bytes = Vec::with_capacity(width as usize * 4);
+        match format {
+            DepthFormat::PGM => {
+                let range = self.depth_range();
+                write!(writer, "P5\n{width} {height}\n65535\n")?;
+                for_each_row_top_down(self, |row| {
+                    Self::normalized_row_bytes(row, range, &mut bytes);
+                    Ok(writer.write_all(&bytes)?)
+                })
+            }
+            DepthFormat::PFM => {
+                // A negative scale marks the data as little-endian. Rows are stored from the bottom
+                // up, just like the framebuffer.
+                write!(writer, "Pf\n{width} {height}\n-1.0\n")?;
+                for_each_row_bottom_up(self, |row| {
+                    bytes.clear();
+                    for depth in row {
+                        bytes.extend_from_slice(&depth.to_le_bytes());
+                    }
+                    Ok(writer.write_all(&bytes)?)
+                })
+            }
+            #[cfg(feature = "png")]
+            DepthFormat::PNG => {
+                let range = self.depth_range();
+                let mut encoder = png::Encoder::new(writer, width, height);
+                encoder.set_color(png::ColorType::Grayscale);
+                encoder.set_depth(png::BitDepth::Sixteen);
+                let mut png_writer = encoder.write_header().map_err(png_error)?;
+                let mut stream = png_writer.stream_writer().map_err(png_error)?;
+                for_each_row_top_down(self, |row| {
+                    Self::normalized_row_bytes(row, range, &mut bytes);
+                    Ok(stream.write_all(&bytes)?)
+                })?;
+                stream.finish().map_err(png_error)
+            }
+        }
+    }
+
+    /// Saves the depths of the view to a file, picking the format from its extension
+    pub fn save_depth_image(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let format = DepthFormat::from_path(path)?;
+        save_with(path, |writer| self.write_depth_image(writer, format))
+    }
+}
+
+impl Framebuffer<u32> {
+    /// Writes the color buffer as an image in the given format
+    pub fn write_image<W: Write>(&self, writer: &mut W, format: ImageFormat) -> Result<()> {
+        self.as_view().write_image(writer, format)
+    }
+
+    /// Saves the color buffer to a file, picking the format from its extension
+    pub fn save_image(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.as_view().save_image(path)
+    }
+}
+
+impl Framebuffer<f32> {
+    /// Writes the depth buffer as an image in the given format
+    pub fn write_depth_image<W: Write>(&self, writer: &mut W, format: DepthFormat) -> Result<()> {
+        self.as_view().write_depth_image(writer, format)
+    }
+
+    /// Saves the depth buffer to a file, picking the format from its extension
+    pub fn save_depth_image(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.as_view().save_depth_image(path)
+    }
+}
@@ -1,11 +1,55 @@
+use crate::error::{Error, Result};
+
+#[derive(Clone)]
 pub struct Framebuffer<T> {
-    width: u16,
-    height: u16,
+    width: u32,
+    height: u32,
     buf: Vec<T>,
 }
 
+/// Computes the index of a pixel within a buffer whose rows are `stride` elements apart, stored from
+/// the top row down. The caller must ensure the pixel lies within `width` and `height`.
+fn pixel_index(x: u32, y: u32, height: u32, stride: usize) -> usize {
+    // Invert y so that the start coordinate of the buffer is bottom left.
+    let y = (height - 1) - y;
+    y as usize * stride + x as usize
+}
+
+fn check_pixel(x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+    if x >= width || y >= height {
+        return Err(Error::PixelOutOfBounds {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+    Ok(())
+}
+
+/// Computes the range of a buffer of `len` elements covered by an image of the given size and stride,
+/// or an error if the buffer is too small to hold it
+fn region_len(width: u32, height: u32, stride: usize, len: usize) -> Result<usize> {
+    if stride < width as usize {
+        return Err(Error::InvalidStride { stride, width });
+    }
+    let required = if width == 0 || height == 0 {
+        0
+    } else {
+        // A region too large to address can never fit, so report it as needing every element
+        (height as usize - 1)
+            .checked_mul(stride)
+            .and_then(|rows| rows.checked_add(width as usize))
+            .unwrap_or(usize::MAX)
+    };
+    if len < required {
+        return Err(Error::BufferTooSmall { required, len });
+    }
+    Ok(required)
+}
+
 impl<T: Default + Copy> Framebuffer<T> {
-    pub fn new(width: u16, height: u16) -> Self {
+    pub fn new(width: u32, height: u32) -> Self {
         let buf = vec![T::default(); width as usize * height as usize];
         Framebuffer { width, height, buf }
     }
@@ -16,35 +60,342 @@ impl<T: Default + Copy> Framebuffer<T> {
         }
     }
 
-    pub fn plot_pixel(&mut self, x: u16, mut y: u16, value: T) {
-        // Invert y so that the start coordinate of the buffer is bottom left.
-        y = (self.height - 1) - y;
-        let idx = y as usize * self.width as usize + x as usize;
-        self.buf[idx] = value;
+    /// Writes a single pixel, where (0, 0) is the bottom left of the framebuffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside the framebuffer. See `try_plot_pixel` for a non-panicking
+    /// alternative.
+    pub fn plot_pixel(&mut self, x: u32, y: u32, value: T) {
+        if let Err(err) = self.try_plot_pixel(x, y, value) {
+            panic!("{err}");
+        }
     }
 
-    pub fn get_pixel(&self, x: u16, mut y: u16) -> T {
-        y = (self.height - 1) - y;
-        let idx = y as usize * self.width as usize + x as usize;
-        self.buf[idx]
+    /// Reads a single pixel, where (0, 0) is the bottom left of the framebuffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside the framebuffer. See `try_get_pixel` for a non-panicking
+    /// alternative.
+    pub fn get_pixel(&self, x: u32, y: u32) -> T {
+        self.try_get_pixel(x, y)
+            .unwrap_or_else(|err| panic!("{err}"))
     }
 
-    pub fn resize(&mut self, new_width: u16, new_height: u16, default: T) {
+    /// Writes a single pixel, or returns an error if it lies outside the framebuffer
+    pub fn try_plot_pixel(&mut self, x: u32, y: u32, value: T) -> Result<()> {
+        check_pixel(x, y, self.width, self.height)?;
+        self.buf[pixel_index(x, y, self.height, self.width as usize)] = value;
+        Ok(())
+    }
+
+    /// Reads a single pixel, or returns an error if it lies outside the framebuffer
+    pub fn try_get_pixel(&self, x: u32, y: u32) -> Result<T> {
+        check_pixel(x, y, self.width, self.height)?;
+        Ok(self.buf[pixel_index(x, y, self.height, self.width as usize)])
+    }
+
+    /// Writes a single pixel without checking that it lies inside the framebuffer
+    ///
+    /// # Safety
+    ///
+    /// `x` must be less than the width, and `y` less than the height, of the framebuffer.
+    pub unsafe fn plot_pixel_unchecked(&mut self, x: u32, y: u32, value: T) {
+        let idx = pixel_index(x, y, self.height, self.width as usize);
+        *self.buf.get_unchecked_mut(idx) = value;
+    }
+
+    /// Reads a single pixel without checking that it lies inside the framebuffer
+    ///
+    /// # Safety
+    ///
+    /// `x` must be less than the width, and `y` less than the height, of the framebuffer.
+    pub unsafe fn get_pixel_unchecked(&self, x: u32, y: u32) -> T {
+        *self
+            .buf
+            .get_unchecked(pixel_index(x, y, self.height, self.width as usize))
+    }
+
+    pub fn resize(&mut self, new_width: u32, new_height: u32, default: T) {
         self.width = new_width;
         self.height = new_height;
         let new_size = self.width as usize * self.height as usize;
         self.buf.resize(new_size, default);
     }
 
-    pub fn get_width(&self) -> u16 {
+    pub fn get_width(&self) -> u32 {
         self.width
     }
 
-    pub fn get_height(&self) -> u16 {
+    pub fn get_height(&self) -> u32 {
         self.height
     }
 
     pub fn get_raw(&self) -> &[T] {
         self.buf.as_slice()
     }
+
+    /// Mutably borrows the pixels, stored row by row from the top row down
+    pub fn get_raw_mut(&mut self) -> &mut [T] {
+        self.buf.as_mut_slice()
+    }
+
+    /// Checks that a rectangle lies within the framebuffer, and returns the range of the underlying
+    /// buffer it covers
+    fn region_range(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<std::ops::Range<usize>> {
+        let fits =
+            |start: u32, size: u32, max: u32| start.checked_add(size).is_some_and(|end| end <= max);
+        if !fits(x, width, self.width) || !fits(y, height, self.height) {
+            return Err(Error::RegionOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+                fb_width: self.width,
+                fb_height: self.height,
+            });
+        }
+        if width == 0 || height == 0 {
+            return Ok(0..0);
+        }
+
+        // Rows are stored from the top down, so the region starts at its top left pixel
+        let start = pixel_index(x, y + height - 1, self.height, self.width as usize);
+        let len = region_len(width, height, self.width as usize, self.buf.len() - start)?;
+        Ok(start..start + len)
+    }
+
+    /// Borrows a rectangular region of the framebuffer, whose bottom left corner lies at (x, y)
+    pub fn view(&self, x: u32, y: u32, width: u32, height: u32) -> Result<FramebufferView<'_, T>> {
+        let range = self.region_range(x, y, width, height)?;
+        Ok(FramebufferView {
+            width,
+            height,
+            stride: self.width as usize,
+            buf: &self.buf[range],
+        })
+    }
+
+    /// Mutably borrows a rectangular region of the framebuffer, whose bottom left corner lies at (x, y)
+    pub fn view_mut(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<FramebufferViewMut<'_, T>> {
+        let range = self.region_range(x, y, width, height)?;
+        Ok(FramebufferViewMut {
+            width,
+            height,
+            stride: self.width as usize,
+            buf: &mut self.buf[range],
+        })
+    }
+
+    /// Borrows the entire framebuffer as a view
+    pub fn as_view(&self) -> FramebufferView<'_, T> {
+        FramebufferView {
+            width: self.width,
+            height: self.height,
+            stride: self.width as usize,
+            buf: &self.buf,
+        }
+    }
+
+    /// Mutably borrows the entire framebuffer as a view
+    pub fn as_view_mut(&mut self) -> FramebufferViewMut<'_, T> {
+        FramebufferViewMut {
+            width: self.width,
+            height: self.height,
+            stride: self.width as usize,
+            buf: &mut self.buf,
+        }
+    }
+}
+
+/// A borrowed rectangle of pixels, whose rows are `stride` elements apart in memory
+///
+/// Like a `Framebuffer`, (0, 0) is the bottom left pixel of the view, while rows are stored in memory
+/// from the top down.
+pub struct FramebufferView<'a, T> {
+    width: u32,
+    height: u32,
+    stride: usize,
+    buf: &'a [T],
+}
+
+/// A mutably borrowed rectangle of pixels, whose rows are `stride` elements apart in memory
+///
+/// Like a `Framebuffer`, (0, 0) is the bottom left pixel of the view, while rows are stored in memory
+/// from the top down.
+pub struct FramebufferViewMut<'a, T> {
+    width: u32,
+    height: u32,
+    stride: usize,
+    buf: &'a mut [T],
+}
+
+impl<'a, T: Copy> FramebufferView<'a, T> {
+    /// Wraps an existing buffer of pixels, stored from the top row down with rows `stride` elements
+    /// apart
+    pub fn from_raw(buf: &'a [T], width: u32, height: u32, stride: usize) -> Result<Self> {
+        let len = region_len(width, height, stride, buf.len())?;
+        Ok(FramebufferView {
+            width,
+            height,
+            stride,
+            buf: &buf[..len],
+        })
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Reads a single pixel, or returns an error if it lies outside the view
+    pub fn try_get_pixel(&self, x: u32, y: u32) -> Result<T> {
+        check_pixel(x, y, self.width, self.height)?;
+        Ok(self.buf[pixel_index(x, y, self.height, self.stride)])
+    }
+
+    /// Reads a single pixel, where (0, 0) is the bottom left of the view
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside the view.
+    pub fn get_pixel(&self, x: u32, y: u32) -> T {
+        self.try_get_pixel(x, y)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Returns a single row of pixels, where row 0 is the bottom of the view
+    ///
+    /// # Panics
+    ///
+    /// Panics if the row lies outside the view.
+    pub fn row(&self, y: u32) -> &[T] {
+        if let Err(err) = check_pixel(0, y, self.width.max(1), self.height) {
+            panic!("{err}");
+        }
+        let start = pixel_index(0, y, self.height, self.stride);
+        &self.buf[start..start + self.width as usize]
+    }
+}
+
+impl<'a, T: Copy> FramebufferViewMut<'a, T> {
+    /// Wraps an existing buffer of pixels, stored from the top row down with rows `stride` elements
+    /// apart
+    pub fn from_raw(buf: &'a mut [T], width: u32, height: u32, stride: usize) -> Result<Self> {
+        let len = region_len(width, height, stride, buf.len())?;
+        Ok(FramebufferViewMut {
+            width,
+            height,
+            stride,
+            buf: &mut buf[..len],
+        })
+    }
+
+    /// Reborrows this view as an immutable one
+    pub fn as_view(&self) -> FramebufferView<'_, T> {
+        FramebufferView {
+            width: self.width,
+            height: self.height,
+            stride: self.stride,
+            buf: self.buf,
+        }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Reads a single pixel, or returns an error if it lies outside the view
+    pub fn try_get_pixel(&self, x: u32, y: u32) -> Result<T> {
+        self.as_view().try_get_pixel(x, y)
+    }
+
+    /// Reads a single pixel, where (0, 0) is the bottom left of the view
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside the view.
+    pub fn get_pixel(&self, x: u32, y: u32) -> T {
+        self.as_view().get_pixel(x, y)
+    }
+
+    /// Writes a single pixel, or returns an error if it lies outside the view
+    pub fn try_plot_pixel(&mut self, x: u32, y: u32, value: T) -> Result<()> {
+        check_pixel(x, y, self.width, self.height)?;
+        self.buf[pixel_index(x, y, self.height, self.stride)] = value;
+        Ok(())
+    }
+
+    /// Writes a single pixel, where (0, 0) is the bottom left of the view
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside the view.
+    pub fn plot_pixel(&mut self, x: u32, y: u32, value: T) {
+        if let Err(err) = self.try_plot_pixel(x, y, value) {
+            panic!("{err}");
+        }
+    }
+
+    /// Returns a single mutable row of pixels, where row 0 is the bottom of the view
+    ///
+    /// # Panics
+    ///
+    /// Panics if the row lies outside the view.
+    pub fn row_mut(&mut self, y: u32) -> &mut [T] {
+        if let Err(err) = check_pixel(0, y, self.width.max(1), self.height) {
+            panic!("{err}");
+        }
+        let start = pixel_index(0, y, self.height, self.stride);
+        &mut self.buf[start..start + self.width as usize]
+    }
+
+    pub fn fill(&mut self, value: T) {
+        for y in 0..self.height {
+            self.row_mut(y).fill(value);
+        }
+    }
+
+    /// Copies every pixel of another view of the same size into this one, for example to place a
+    /// rendered frame into a region of a larger window buffer
+    pub fn copy_from(&mut self, src: &FramebufferView<'_, T>) -> Result<()> {
+        if src.width != self.width || src.height != self.height {
+            return Err(Error::SizeMismatch {
+                expected: (self.width, self.height),
+                actual: (src.width, src.height),
+            });
+        }
+        for y in 0..self.height {
+            self.row_mut(y).copy_from_slice(src.row(y));
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,91 @@
+//! Fog, fading distant or low-lying geometry into a flat color.
+//!
+//! Fog is applied by the renderer after the fragment shader, so any shader can be drawn through it
+//! without changes. See `Renderer::set_fog`. The view depth of every fragment is its interpolated
+//! clip space w, and height fog rebuilds its world position from the view and projection matrices
+//! the fog was set up with.
+
+use glam::Vec3;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    /// Fog thickens evenly from none at the view depth `start` to solid at `end`
+    LINEAR { start: f32, end: f32 },
+    /// `1 - e^(-density * depth)`
+    EXPONENTIAL { density: f32 },
+    /// `1 - e^(-(density * depth)^2)`, which keeps nearby geometry clearer before thickening quickly
+    EXPONENTIAL_SQUARED { density: f32 },
+    /// Fog with the given density at `height`, thinning by a factor of e for every `1 / falloff`
+    /// world units above it and thickening below. The density is gathered along the whole ray from
+    /// the camera to the surface, so looking down into a valley passes through more fog than looking
+    /// across it.
+    HEIGHT {
+        density: f32,
+        height: f32,
+        falloff: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    /// The color fogged surfaces blend towards, with channels in the 0-1 range
+    pub color: Vec3,
+}
+
+impl Fog {
+    pub fn new(mode: FogMode, color: Vec3) -> Self {
+        Fog { mode, color }
+    }
+
+    /// How much of a surface is hidden by fog, from zero for none to one when the fog is solid
+    ///
+    /// `view_depth` is the distance of the surface in front of the camera, along its view direction.
+    /// The positions are only used by `FogMode::HEIGHT`, and are in world space.
+    pub fn amount(&self, view_depth: f32, camera_position: Vec3, world_position: Vec3) -> f32 {
+        let view_depth = view_depth.max(0.0);
+        let amount = match self.mode {
+            FogMode::LINEAR { start, end } => {
+                if end > start {
+                    (view_depth - start) / (end - start)
+                } else if view_depth >= start {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            FogMode::EXPONENTIAL { density } => 1.0 - (-density * view_depth).exp(),
+            FogMode::EXPONENTIAL_SQUARED { density } => {
+                1.0 - (-(density * view_depth).powi(2)).exp()
+            }
+            FogMode::HEIGHT {
+                density,
+                height,
+                falloff,
+            } => {
+                let distance = camera_position.distance(world_position);
+                // The density at the camera, integrated along a ray whose height changes linearly
+                let camera_density = density * (-falloff * (camera_position.y - height)).exp();
+                let rise = falloff * (world_position.y - camera_position.y);
+                let integral = if rise.abs() > 1e-4 {
+                    (1.0 - (-rise).exp()) / rise
+                } else {
+                    1.0
+                };
+                1.0 - (-camera_density * distance * integral).exp()
+            }
+        };
+        if amount.is_nan() {
+            // Infinite density reaching the surface, far below the fog's height
+            1.0
+        } else {
+            amount.clamp(0.0, 1.0)
+        }
+    }
+
+    /// Blends a 0-1 color towards the fog color by `amount`
+    pub fn apply(&self, color: Vec3, amount: f32) -> Vec3 {
+        color.lerp(self.color, amount)
+    }
+}
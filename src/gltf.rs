@@ -0,0 +1,571 @@
+//! Loading glTF 2.0 scenes, from `.gltf` files with separate or embedded buffers, or from binary
+//! `.glb` files. Requires the `gltf` feature.
+//!
+//! Each glTF mesh becomes a `Mesh`, with one material group per triangle primitive. Nodes keep their
+//! hierarchy, and their world transforms are computed on load, which fails if a node has more than
+//! one parent or is its own ancestor. Texture coordinates are flipped on load so that, as with every
+//! `Texture`, v runs upwards from the bottom of the image.
+
+use std::path::Path;
+
+use glam::{Affine3A, Mat3, Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+use softrender_derive::Barycentric;
+
+use crate::{
+    error::{Error, Result},
+    mesh::{MaterialGroup, Mesh, MeshVertex},
+    pbr::PbrMaterial,
+    renderer::Renderer,
+    shader::{Barycentric, Shader},
+    shaders::{decode_srgb, encode_output},
+    texture::Texture,
+};
+
+/// How the alpha of a material's base color is interpreted
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    OPAQUE,
+    /// Fragments with an alpha below the cutoff are discarded
+    MASK(f32),
+    BLEND,
+}
+
+/// A metallic-roughness material. Texture indices refer to `GltfScene::textures`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color_factor: Vec4,
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Roughness is read from the green channel, and metalness from the blue channel
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+    /// Ambient occlusion is read from the red channel
+    pub occlusion_texture: Option<usize>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: Vec3,
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl GltfMaterial {
+    /// Resolves the material's textures in `textures`, usually `GltfScene::textures`, for `PbrShader`
+    pub fn to_pbr<'a>(&self, textures: &'a [Texture]) -> PbrMaterial<'a> {
+        let texture = |index: Option<usize>| index.and_then(|index| textures.get(index));
+        PbrMaterial {
+            base_color: self.base_color_factor,
+            base_color_texture: texture(self.base_color_texture),
+            metallic: self.metallic_factor,
+            roughness: self.roughness_factor,
+            metallic_roughness_texture: texture(self.metallic_roughness_texture),
+            normal_texture: texture(self.normal_texture),
+            normal_scale: self.normal_scale,
+            occlusion_texture: texture(self.occlusion_texture),
+            occlusion_strength: self.occlusion_strength,
+            emissive: self.emissive_factor,
+            emissive_texture: texture(self.emissive_texture),
+        }
+    }
+}
+
+/// A node of the scene hierarchy. Indices refer to `GltfScene::nodes` and `GltfScene::meshes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// Transform relative to the parent node
+    pub local_transform: Affine3A,
+    /// Transform relative to the scene, combining the transforms of every ancestor
+    pub world_transform: Affine3A,
+    pub mesh: Option<usize>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// The contents of a glTF file
+///
+/// The material groups of each mesh index into `materials`, rather than into `Mesh::materials`, which
+/// is left empty.
+#[derive(Clone, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<GltfMaterial>,
+    /// One texture per image in the file, whether embedded or external
+    pub textures: Vec<Texture>,
+    pub nodes: Vec<GltfNode>,
+    /// The top level nodes of the default scene, or of the first scene if there is no default
+    pub roots: Vec<usize>,
+}
+
+fn malformed(reason: String) -> Error {
+    Error::MalformedScene { reason }
+}
+
+impl From<::gltf::Error> for Error {
+    fn from(err: ::gltf::Error) -> Self {
+        match err {
+            ::gltf::Error::Io(err) => Error::Io(err),
+            err => malformed(err.to_string()),
+        }
+    }
+}
+
+/// Converts a decoded image into a texture, flipping its rows so the first texel is at the bottom
+fn convert_image(image: &::gltf::image::Data) -> Result<Texture> {
+    use ::gltf::image::Format;
+
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let channel = |bytes: &[u8]| match bytes_per_channel {
+        1 => bytes[0] as f32 / 255.0,
+        2 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+        _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    };
+
+    let (width, height) = (image.width as usize, image.height as usize);
+    let texel_size = channels * bytes_per_channel;
+    if width == 0 || height == 0 || image.pixels.len() < width * height * texel_size {
+        return Err(Error::MalformedImage {
+            reason: "image data does not match its dimensions".to_string(),
+        });
+    }
+    let mut texels = Vec::with_capacity(width * height);
+    for row in image
+        .pixels
+        .chunks_exact(width * texel_size)
+        .take(height)
+        .rev()
+    {
+        for texel in row.chunks_exact(texel_size) {
+            let mut values = texel.chunks_exact(bytes_per_channel).map(channel);
+            let red = values.next().unwrap_or_default();
+            texels.push(match channels {
+                // Single and dual channel images are treated as grayscale
+                1 | 2 => Vec3::splat(red),
+                _ => Vec3::new(
+                    red,
+                    values.next().unwrap_or_default(),
+                    values.next().unwrap_or_default(),
+                ),
+            });
+        }
+    }
+    Ok(Texture::new(image.width, image.height, texels))
+}
+
+fn convert_material(material: &::gltf::Material) -> GltfMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    let image_of = |texture: ::gltf::Texture| texture.source().index();
+    GltfMaterial {
+        name: material.name().map(str::to_string),
+        base_color_factor: pbr.base_color_factor().into(),
+        base_color_texture: pbr
+            .base_color_texture()
+            .map(|info| image_of(info.texture())),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|info| image_of(info.texture())),
+        normal_texture: material
+            .normal_texture()
+            .map(|info| image_of(info.texture())),
+        normal_scale: material.normal_texture().map_or(1.0, |info| info.scale()),
+        occlusion_texture: material
+            .occlusion_texture()
+            .map(|info| image_of(info.texture())),
+        occlusion_strength: material
+            .occlusion_texture()
+            .map_or(1.0, |info| info.strength()),
+        emissive_factor: material.emissive_factor().into(),
+        emissive_texture: material
+            .emissive_texture()
+            .map(|info| image_of(info.texture())),
+        alpha_mode: match material.alpha_mode() {
+            ::gltf::material::AlphaMode::Opaque => AlphaMode::OPAQUE,
+            ::gltf::material::AlphaMode::Mask => {
+                AlphaMode::MASK(material.alpha_cutoff().unwrap_or(0.5))
+            }
+            ::gltf::material::AlphaMode::Blend => AlphaMode::BLEND,
+        },
+        double_sided: material.double_sided(),
+    }
+}
+
+/// Converts the indices of a primitive into a triangle list, given the number of vertices it has
+fn triangle_indices(mode: ::gltf::mesh::Mode, indices: Vec<u32>) -> Option<Vec<u32>> {
+    use ::gltf::mesh::Mode;
+
+    match mode {
+        Mode::Triangles => Some(indices),
+        // Every other strip triangle is flipped to keep a consistent winding
+        Mode::TriangleStrip => Some(
+            (2..indices.len())
+                .flat_map(|i| {
+                    if i % 2 == 0 {
+                        [indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        [indices[i - 1], indices[i - 2], indices[i]]
+                    }
+                })
+                .collect(),
+        ),
+        Mode::TriangleFan => Some(
+            (2..indices.len())
+                .flat_map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+        ),
+        // Points and lines are not part of a triangle mesh
+        _ => None,
+    }
+}
+
+fn convert_mesh(mesh: &::gltf::Mesh, buffers: &[::gltf::buffer::Data]) -> Result<Mesh> {
+    let mut result = Mesh::default();
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let Some(positions) = reader.read_positions() else {
+            continue;
+        };
+        let positions: Vec<Vec3> = positions.map(Vec3::from).collect();
+        let count = positions.len() as u32;
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..count).collect(),
+        };
+        let Some(indices) = triangle_indices(primitive.mode(), indices) else {
+            continue;
+        };
+        if let Some(&index) = indices.iter().find(|&&index| index >= count) {
+            return Err(malformed(format!(
+                "index {index} is out of bounds of a primitive with {count} vertices"
+            )));
+        }
+
+        let mut normals = reader.read_normals().map(|normals| normals.map(Vec3::from));
+        let mut uvs = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(|[u, v]| Vec2::new(u, 1.0 - v)));
+        let mut tangents = reader
+            .read_tangents()
+            .map(|tangents| tangents.map(Vec4::from));
+        let has_tangents = tangents.is_some();
+        let mut part = Mesh {
+            vertices: positions
+                .into_iter()
+                .map(|position| MeshVertex {
+                    position,
+                    normal: normals
+                        .as_mut()
+                        .and_then(Iterator::next)
+                        .unwrap_or(Vec3::ZERO),
+                    uv: uvs.as_mut().and_then(Iterator::next).unwrap_or(Vec2::ZERO),
+                    tangent: tangents.as_mut().and_then(Iterator::next).map_or(
+                        Vec4::ZERO,
+                        |tangent| {
+                            // Flipping v mirrors the texture space, which flips the bitangent
+                            tangent.xyz().extend(-tangent.w)
+                        },
+                    ),
+                })
+                .collect(),
+            indices,
+            ..Mesh::default()
+        };
+
+        // Flat shaded primitives without normals get the normal of each face, so their triangles
+        // can't share vertices
+        if normals.is_none() {
+            part.vertices = part
+                .indices
+                .iter()
+                .map(|&index| part.vertices[index as usize])
+                .collect();
+            part.indices = (0..part.vertices.len() as u32).collect();
+            for triangle in part.vertices.chunks_exact_mut(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i].position);
+                let normal = (b - a).cross(c - a).normalize_or_zero();
+                for vertex in triangle {
+                    vertex.normal = normal;
+                }
+            }
+        }
+        // Tangents are only generated for primitives that don't supply their own
+        if !has_tangents {
+            part.compute_tangents();
+        }
+
+        let base = result.vertices.len() as u32;
+        let start = result.indices.len();
+        result.vertices.extend(part.vertices);
+        result
+            .indices
+            .extend(part.indices.iter().map(|index| base + index));
+        result.groups.push(MaterialGroup {
+            material: primitive.material().index(),
+            indices: start..result.indices.len(),
+        });
+    }
+    Ok(result)
+}
+
+impl GltfScene {
+    /// Loads a `.gltf` or `.glb` file, along with any buffers and images it references
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let (document, buffers, images) = ::gltf::import(path)?;
+        Self::convert(&document, &buffers, &images)
+    }
+
+    /// Loads a glTF file from memory. Any buffers and images must be embedded, either in a binary
+    /// chunk or as data URIs.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let ::gltf::Gltf { document, blob } = ::gltf::Gltf::from_slice(bytes)?;
+        let buffers = ::gltf::import_buffers(&document, None, blob)?;
+        // The gltf crate only decodes data URI images when given a directory to resolve other URIs
+        // against, so external images are rejected here instead
+        let images = document
+            .images()
+            .map(|image| match image.source() {
+                ::gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                    Err(::gltf::Error::ExternalReferenceInSliceImport.into())
+                }
+                source => Ok(::gltf::image::Data::from_source(
+                    source,
+                    Some(Path::new("")),
+                    &buffers,
+                )?),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::convert(&document, &buffers, &images)
+    }
+
+    fn convert(
+        document: &::gltf::Document,
+        buffers: &[::gltf::buffer::Data],
+        images: &[::gltf::image::Data],
+    ) -> Result<Self> {
+        let mut scene = GltfScene {
+            meshes: document
+                .meshes()
+                .map(|mesh| convert_mesh(&mesh, buffers))
+                .collect::<Result<_>>()?,
+            materials: document.materials().map(|m| convert_material(&m)).collect(),
+            textures: images.iter().map(convert_image).collect::<Result<_>>()?,
+            nodes: document
+                .nodes()
+                .map(|node| GltfNode {
+                    name: node.name().map(str::to_string),
+                    local_transform: Affine3A::from_mat4(Mat4::from_cols_array_2d(
+                        &node.transform().matrix(),
+                    )),
+                    world_transform: Affine3A::IDENTITY,
+                    mesh: node.mesh().map(|mesh| mesh.index()),
+                    parent: None,
+                    children: node.children().map(|child| child.index()).collect(),
+                })
+                .collect(),
+            roots: Vec::new(),
+        };
+
+        for index in 0..scene.nodes.len() {
+            for child in scene.nodes[index].children.clone() {
+                if let Some(parent) = scene.nodes[child].parent {
+                    return Err(malformed(format!(
+                        "node {child} is a child of both node {parent} and node {index}"
+                    )));
+                }
+                scene.nodes[child].parent = Some(index);
+            }
+        }
+        // With at most one parent per node, walking down from the nodes without a parent reaches
+        // every node, unless some chain of parents loops back on itself
+        let mut reached = vec![false; scene.nodes.len()];
+        let mut stack: Vec<usize> = (0..scene.nodes.len())
+            .filter(|&index| scene.nodes[index].parent.is_none())
+            .collect();
+        while let Some(index) = stack.pop() {
+            reached[index] = true;
+            stack.extend(&scene.nodes[index].children);
+        }
+        if let Some(index) = reached.iter().position(|&reached| !reached) {
+            return Err(malformed(format!(
+                "the ancestors of node {index} form a cycle"
+            )));
+        }
+        scene.roots = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(default) => default.nodes().map(|node| node.index()).collect(),
+            // Without any scenes, every node that is not a child is a root
+            None => (0..scene.nodes.len())
+                .filter(|&index| scene.nodes[index].parent.is_none())
+                .collect(),
+        };
+
+        if let Some(&root) = scene
+            .roots
+            .iter()
+            .find(|&&root| scene.nodes[root].parent.is_some())
+        {
+            return Err(malformed(format!("scene root {root} is also a child")));
+        }
+
+        // The hierarchy is now known to be a forest, so walking down from the roots visits each node
+        // once
+        let mut stack: Vec<(usize, Affine3A)> = scene
+            .roots
+            .iter()
+            .map(|&root| (root, Affine3A::IDENTITY))
+            .collect();
+        while let Some((index, parent_transform)) = stack.pop() {
+            let node = &mut scene.nodes[index];
+            node.world_transform = parent_transform * node.local_transform;
+            let world = node.world_transform;
+            stack.extend(node.children.iter().map(|&child| (child, world)));
+        }
+        Ok(scene)
+    }
+
+    /// Returns the material of a mesh's group with its textures, ready for `PbrShader`. Groups without
+    /// a material get glTF's default material.
+    pub fn group_pbr_material(&self, group: &MaterialGroup) -> PbrMaterial<'_> {
+        self.group_material(group)
+            .map(|material| material.to_pbr(&self.textures))
+            .unwrap_or_default()
+    }
+
+    /// Returns the material of a mesh's group, if it has one
+    pub fn group_material(&self, group: &MaterialGroup) -> Option<&GltfMaterial> {
+        group.material.and_then(|index| self.materials.get(index))
+    }
+
+    /// Draws every mesh reachable from the roots of the scene with `GltfShader`, lit by a directional
+    /// light shining along `light_dir`. Meshes outside the view are culled, and counted in
+    /// `Renderer::get_cull_stats`. Groups with a masked material whose alpha is below its cutoff are
+    /// not drawn.
+    pub fn draw(&self, renderer: &mut Renderer, view_proj: Mat4, light_dir: Vec3) -> Result<()> {
+        let mut stack = self.roots.clone();
+        let mut visited = vec![false; self.nodes.len()];
+        while let Some(index) = stack.pop() {
+            // Loaded scenes never revisit a node, but the hierarchy may have been edited since
+            let (Some(visited), Some(node)) = (visited.get_mut(index), self.nodes.get(index))
+            else {
+                return Err(malformed(format!("node {index} does not exist")));
+            };
+            if std::mem::replace(visited, true) {
+                return Err(malformed(format!(
+                    "node {index} is reached more than once from the roots"
+                )));
+            }
+            stack.extend(&node.children);
+            let Some(mesh) = node.mesh.and_then(|mesh| self.meshes.get(mesh)) else {
+                continue;
+            };
+            let Some(bounds) = mesh.bounds() else {
+                continue;
+            };
+            let clip_from_object = view_proj * Mat4::from(node.world_transform);
+            for group in &mesh.groups {
+                let material = self.group_material(group);
+                // Textures have no alpha channel, so a masked material is either wholly drawn or
+                // wholly discarded
+                if let Some(GltfMaterial {
+                    alpha_mode: AlphaMode::MASK(cutoff),
+                    base_color_factor,
+                    ..
+                }) = material
+                {
+                    if base_color_factor.w < *cutoff {
+                        continue;
+                    }
+                }
+                let mut shader = GltfShader {
+                    model: node.world_transform,
+                    normal_matrix: Mat3::from(node.world_transform.matrix3)
+                        .inverse()
+                        .transpose(),
+                    view_proj,
+                    light_dir: -light_dir.normalize_or_zero(),
+                    base_color: material.map_or(Vec4::ONE, |m| m.base_color_factor),
+                    base_color_texture: material
+                        .and_then(|m| m.base_color_texture)
+                        .and_then(|texture| self.textures.get(texture)),
+                    emissive: material.map_or(Vec3::ZERO, |m| m.emissive_factor),
+                };
+                renderer.try_draw_culled(
+                    &mut shader,
+                    &mesh.vertices,
+                    mesh.group_indices(group),
+                    &bounds,
+                    clip_from_object,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Barycentric)]
+pub struct GltfVertexOut {
+    pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+/// A simple shader for previewing glTF scenes, which shades the base color of a material with a
+/// single directional light and adds its emission
+///
+/// Colors are lit in linear light: the base color texture is decoded from sRGB, and the output is
+/// encoded to sRGB. The alpha of the base color is ignored, so `GltfScene::draw` applies
+/// `AlphaMode::MASK` by skipping the groups it discards.
+pub struct GltfShader<'a> {
+    pub model: Affine3A,
+    /// The inverse transpose of the model matrix, to transform normals with
+    pub normal_matrix: Mat3,
+    pub view_proj: Mat4,
+    /// Direction towards the light
+    pub light_dir: Vec3,
+    pub base_color: Vec4,
+    pub base_color_texture: Option<&'a Texture>,
+    pub emissive: Vec3,
+}
+
+impl Shader<MeshVertex, GltfVertexOut> for GltfShader<'_> {
+    fn vertex(&self, vertex: &MeshVertex) -> (Vec4, GltfVertexOut) {
+        let world_pos = self.model.transform_point3(vertex.position);
+        (
+            self.view_proj * world_pos.extend(1.0),
+            GltfVertexOut {
+                normal: self.normal_matrix * vertex.normal,
+                uv: vertex.uv,
+            },
+        )
+    }
+
+    fn fragment(&self, inputs: GltfVertexOut) -> UVec3 {
+        let mut albedo = self.base_color.xyz();
+        if let Some(texture) = self.base_color_texture {
+            albedo *= decode_srgb(texture.sample_bilinear(inputs.uv));
+        }
+        let diffuse = inputs
+            .normal
+            .normalize_or_zero()
+            .dot(self.light_dir)
+            .max(0.0);
+        let color = albedo * (0.2 + 0.8 * diffuse) + self.emissive;
+        encode_output(color, true)
+    }
+}
@@ -0,0 +1,143 @@
+//! Loading color images into framebuffers.
+//!
+//! Binary PPM images can always be read, and PNG images when the `png` feature is enabled. Loaded
+//! images keep the orientation they are displayed with, so the bottom left pixel of the image becomes
+//! pixel (0, 0) of the framebuffer.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    error::{Error, Result},
+    export::ImageFormat,
+    fb::Framebuffer,
+    math::pack_color,
+};
+
+fn malformed(reason: &str) -> Error {
+    Error::MalformedImage {
+        reason: reason.to_string(),
+    }
+}
+
+/// Reads the next whitespace separated token of a PPM header, skipping comments
+fn read_header_token(reader: &mut impl BufRead) -> Result<String> {
+    let mut token = String::new();
+    let mut in_comment = false;
+    loop {
+        let mut byte = [0];
+        if reader.read(&mut byte)? == 0 {
+            break;
+        }
+        let c = byte[0] as char;
+        if in_comment {
+            in_comment = c != '\n';
+        } else if c == '#' {
+            in_comment = true;
+        } else if c.is_ascii_whitespace() {
+            if !token.is_empty() {
+                break;
+            }
+        } else {
+            token.push(c);
+        }
+    }
+    if token.is_empty() {
+        return Err(malformed("unexpected end of header"));
+    }
+    Ok(token)
+}
+
+/// Builds a framebuffer from rows of pixels stored from the top down, where each pixel is `channels`
+/// bytes. One and two channel pixels are grayscale, three and four channel pixels are RGB, and any
+/// alpha channel is ignored.
+fn from_top_down_rows(width: u32, height: u32, channels: usize, data: &[u8]) -> Framebuffer<u32> {
+    let mut fb = Framebuffer::new(width, height);
+    let mut pixels = data.chunks_exact(channels);
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let Some(pixel) = pixels.next() else {
+                return fb;
+            };
+            let color = match pixel {
+                [l] | [l, _] => pack_color((*l as u32, *l as u32, *l as u32).into()),
+                [r, g, b, ..] => pack_color((*r as u32, *g as u32, *b as u32).into()),
+                [] => 0,
+            };
+            fb.plot_pixel(x, y, color);
+        }
+    }
+    fb
+}
+
+fn read_ppm(reader: &mut impl BufRead) -> Result<Framebuffer<u32>> {
+    if read_header_token(reader)? != "P6" {
+        return Err(malformed("not a binary PPM image"));
+    }
+    let mut read_number = || -> Result<u32> {
+        read_header_token(reader)?
+            .parse()
+            .map_err(|_| malformed("invalid number in header"))
+    };
+    let (width, height, max_value) = (read_number()?, read_number()?, read_number()?);
+    if max_value != 255 {
+        return Err(malformed("only 8-bit PPM images are supported"));
+    }
+
+    // Only allocate as much as the input actually holds, so a bogus header can't exhaust memory
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| malformed("image dimensions are too large"))?;
+    let mut data = Vec::new();
+    reader.take(size as u64).read_to_end(&mut data)?;
+    if data.len() < size {
+        return Err(malformed("pixel data is truncated"));
+    }
+    Ok(from_top_down_rows(width, height, 3, &data))
+}
+
+#[cfg(feature = "png")]
+fn read_png(reader: impl Read) -> Result<Framebuffer<u32>> {
+    let to_error = |err: png::DecodingError| match err {
+        png::DecodingError::IoError(err) => Error::Io(err),
+        err => malformed(&err.to_string()),
+    };
+    let mut decoder = png::Decoder::new(reader);
+    // Expand palettes and low bit depths, and reduce 16-bit channels, so every pixel is 8-bit
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut png_reader = decoder.read_info().map_err(to_error)?;
+    let mut data = vec![0; png_reader.output_buffer_size()];
+    let info = png_reader.next_frame(&mut data).map_err(to_error)?;
+    let channels = info.color_type.samples();
+    Ok(from_top_down_rows(
+        info.width,
+        info.height,
+        channels,
+        &data[..info.buffer_size()],
+    ))
+}
+
+impl Framebuffer<u32> {
+    /// Reads an image in the given format into a new framebuffer
+    pub fn read_image<R: Read>(reader: R, format: ImageFormat) -> Result<Self> {
+        match format {
+            ImageFormat::PPM => read_ppm(&mut BufReader::new(reader)),
+            #[cfg(feature = "png")]
+            ImageFormat::PNG => read_png(reader),
+            _ => Err(Error::UnsupportedFormat {
+                extension: format!("{format:?}").to_ascii_lowercase(),
+            }),
+        }
+    }
+
+    /// Loads an image file into a new framebuffer, picking the format from its extension
+    pub fn load_image(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path)?;
+        Self::read_image(File::open(path)?, format)
+    }
+}
@@ -1,4 +1,22 @@
+pub mod camera;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod error;
+pub mod export;
 pub mod fb;
+pub mod fog;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+pub mod import;
 pub mod math;
+pub mod mesh;
+pub mod pbr;
+pub mod post;
 pub mod renderer;
+pub mod scene;
 pub mod shader;
+pub mod shaders;
+pub mod shadow;
+pub mod ssao;
+pub mod testing;
+pub mod texture;
@@ -0,0 +1,163 @@
+//! A headless renderer for OBJ models, which writes a single frame to an image file.
+//!
+//! Run `softrender --help` for usage.
+
+#[macro_use]
+extern crate softrender_derive;
+
+use std::{collections::HashMap, path::PathBuf, process::ExitCode};
+
+use glam::{Mat4, UVec3, Vec2, Vec3, Vec4};
+use softrender::{
+    cli::{frame_camera, parse_args, Options, ShadingMode, USAGE},
+    math::to_output_color,
+    mesh::{Mesh, MeshVertex},
+    renderer::{DrawMode, Renderer},
+    shader::{Barycentric, Shader},
+    texture::Texture,
+};
+
+#[derive(Clone, Barycentric)]
+struct VertexOut {
+    normal: Vec3,
+    uv: Vec2,
+}
+
+struct CliShader {
+    mode: ShadingMode,
+    view_proj: Mat4,
+    /// Direction towards the light, which sits at the camera
+    light_dir: Vec3,
+    /// The texture for the textured mode, or the diffuse color of a material without one
+    texture: Texture,
+}
+
+impl Shader<MeshVertex, VertexOut> for CliShader {
+    fn vertex(&self, vertex: &MeshVertex) -> (Vec4, VertexOut) {
+        (
+            self.view_proj * vertex.position.extend(1.0),
+            VertexOut {
+                normal: vertex.normal,
+                uv: vertex.uv,
+            },
+        )
+    }
+
+    fn fragment(&self, inputs: VertexOut) -> UVec3 {
+        let normal = inputs.normal.normalize_or_zero();
+        let color = match self.mode {
+            ShadingMode::FLAT => Vec3::splat(0.15 + 0.75 * normal.dot(self.light_dir).max(0.0)),
+            ShadingMode::NORMALS => normal * 0.5 + 0.5,
+            ShadingMode::TEXTURED => self.texture.sample_bilinear(inputs.uv),
+            ShadingMode::WIREFRAME => Vec3::ONE,
+        };
+        to_output_color(color)
+    }
+}
+
+/// Splits the given triangles into unshared vertices, each with the normal of its face
+fn flat_triangles(mesh: &Mesh, indices: &[u32]) -> Vec<MeshVertex> {
+    let mut vertices = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+        let [a, b, c] = corners.map(|corner| corner.position);
+        let face_normal = (b - a).cross(c - a).normalize_or_zero();
+        vertices.extend(corners.map(|corner| MeshVertex {
+            normal: face_normal,
+            ..corner
+        }));
+    }
+    vertices
+}
+
+fn load_texture(path: &PathBuf) -> Result<Texture, String> {
+    Texture::load(path).map_err(|err| format!("failed to load {}: {err}", path.display()))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let mesh = Mesh::load_obj(&options.model)
+        .map_err(|err| format!("failed to load {}: {err}", options.model.display()))?;
+    if mesh.indices.is_empty() {
+        return Err(format!("{} contains no triangles", options.model.display()));
+    }
+    let texture_override = options.texture.as_ref().map(load_texture).transpose()?;
+
+    let bounds = mesh.bounds().ok_or("the model has no vertices")?;
+    let camera = frame_camera(options, &bounds)?;
+    let mut shader = CliShader {
+        mode: options.mode,
+        view_proj: camera.view_projection_matrix(),
+        light_dir: -camera.forward(),
+        texture: Texture::solid(Vec3::ONE),
+    };
+
+    let mut renderer = Renderer::new(options.width, options.height);
+    renderer.clear_framebuffer(options.background);
+    if options.mode == ShadingMode::WIREFRAME {
+        renderer.set_draw_mode(DrawMode::WIREFRAME);
+    }
+
+    // Material textures are loaded once, however many groups share them
+    let mut textures: HashMap<PathBuf, Texture> = HashMap::new();
+    for group in &mesh.groups {
+        if options.mode == ShadingMode::TEXTURED {
+            let material = mesh.group_material(group);
+            shader.texture = match (&texture_override, material) {
+                (Some(texture), _) => texture.clone(),
+                (None, Some(material)) => match &material.diffuse_texture {
+                    Some(path) => match textures.get(path) {
+                        Some(texture) => texture.clone(),
+                        None => {
+                            let texture = load_texture(path)?;
+                            textures.insert(path.clone(), texture.clone());
+                            texture
+                        }
+                    },
+                    None => Texture::solid(material.diffuse),
+                },
+                (None, None) => Texture::solid(Vec3::ONE),
+            };
+        }
+
+        let indices = mesh.group_indices(group);
+        let result = if options.mode == ShadingMode::FLAT {
+            let vertices = flat_triangles(&mesh, indices);
+            let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+            renderer
+                .try_draw(&mut shader, &vertices, &indices)
+                .map(|_| ())
+        } else {
+            renderer
+                .try_draw(&mut shader, &mesh.vertices, indices)
+                .map(|_| ())
+        };
+        result.map_err(|err| format!("failed to draw: {err}"))?;
+    }
+
+    renderer
+        .get_color_buffer()
+        .save_image(&options.output)
+        .map_err(|err| format!("failed to write {}: {err}", options.output.display()))
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
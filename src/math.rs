@@ -1,24 +1,293 @@
-use std::ops::{Div, Mul, Sub};
-
-use glam::{Vec2, Vec4};
+use glam::{Mat4, UVec3, Vec3, Vec4, Vec4Swizzles};
 
 pub struct ClipPlane {
     pub sign: f32,
     pub axis: usize,
 }
 
-pub trait InverseLerp<T: Mul + Sub + Div> {
-    fn inverse_lerp(&self, to: T, point: T) -> f32;
+/// Packs an RGB color with 0-255 channels into the 0RGB format used by the color buffer
+///
+/// Channels above 255 are saturated, rather than overflowing into their neighbours.
+pub fn pack_color(color: UVec3) -> u32 {
+    let color = color.min(UVec3::splat(255));
+    color.z | (color.y << 8) | (color.x << 16)
+}
+
+/// Unpacks a 0RGB color from the color buffer into its 0-255 channels
+pub fn unpack_color(color: u32) -> UVec3 {
+    UVec3::new((color >> 16) & 0xFF, (color >> 8) & 0xFF, color & 0xFF)
+}
+
+/// Blends two packed colors, weighting the source color by alpha and the destination by 1 - alpha
+pub fn blend_color(dst: u32, src: u32, alpha: f32) -> u32 {
+    let dst = unpack_color(dst).as_vec3();
+    let src = unpack_color(src).as_vec3();
+    pack_color(dst.lerp(src, alpha.clamp(0.0, 1.0)).round().as_uvec3())
+}
+
+/// Converts a linear color channel to the sRGB transfer curve used by images and displays
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts an sRGB encoded color channel to linear light
+pub fn srgb_to_linear(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a 0-1 color to the 0-255 color written by fragment shaders
+pub fn to_output_color(color: Vec3) -> UVec3 {
+    (color.clamp(Vec3::ZERO, Vec3::ONE) * 255.0)
+        .round()
+        .as_uvec3()
+}
+
+/// Reflects an incident direction, pointing towards a surface, about the surface's normal
+pub fn reflect(incident: Vec3, normal: Vec3) -> Vec3 {
+    incident - 2.0 * normal.dot(incident) * normal
+}
+
+/// Bends an incident direction, pointing towards a surface, as it passes through the surface, where
+/// `eta` is the ratio of the refractive index on the incident side to the one on the far side. Both
+/// directions must be normalized.
+///
+/// Returns `None` when the ray is totally internally reflected instead.
+pub fn refract(incident: Vec3, normal: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_incident = -normal.dot(incident);
+    let k = 1.0 - eta * eta * (1.0 - cos_incident * cos_incident);
+    if k < 0.0 {
+        return None;
+    }
+    Some(eta * incident + (eta * cos_incident - k.sqrt()) * normal)
+}
+
+/// Maps high dynamic range colors, with channels above one, into the 0-1 range
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    /// Channels above one are clamped
+    #[default]
+    NONE,
+    /// `c / (1 + c)`, which compresses highlights gently but darkens the whole image
+    REINHARD,
+    /// Narkowicz's fit of the ACES filmic curve, with more contrast than Reinhard
+    ACES,
+}
+
+impl ToneMapping {
+    pub fn apply(self, color: Vec3) -> Vec3 {
+        let color = color.max(Vec3::ZERO);
+        match self {
+            ToneMapping::NONE => color.min(Vec3::ONE),
+            ToneMapping::REINHARD => color / (color + 1.0),
+            ToneMapping::ACES => {
+                let mapped =
+                    (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+                mapped.clamp(Vec3::ZERO, Vec3::ONE)
+            }
+        }
+    }
+}
+
+/// A plane through space, made up of every point `p` where `normal.dot(p) + distance == 0`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    /// Unit normal, pointing towards the positive half-space
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Creates a plane from the coefficients of `ax + by + cz + d = 0`, normalizing them
+    pub fn from_coefficients(coefficients: Vec4) -> Self {
+        let length = coefficients.truncate().length();
+        let coefficients = if length > 0.0 {
+            coefficients / length
+        } else {
+            coefficients
+        };
+        Plane {
+            normal: coefficients.truncate(),
+            distance: coefficients.w,
+        }
+    }
+
+    /// The distance from the plane to a point, which is positive on the side the normal points to
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// The volume visible to a camera, bounded by six inward facing planes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near and far planes, in that order
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum of a projection, or combined view-projection, matrix
+    ///
+    /// The planes bound the volume that the renderer clips primitives to, where each of x, y and z
+    /// lies between -w and w in clip space. If `matrix` includes a view or model transform, the planes
+    /// are in the corresponding world or object space.
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let rows = [0, 1, 2, 3].map(|i| matrix.row(i));
+        let plane = |axis: usize, sign: f32| Plane::from_coefficients(rows[3] + rows[axis] * sign);
+        Frustum {
+            planes: [
+                plane(0, 1.0),
+                plane(0, -1.0),
+                plane(1, 1.0),
+                plane(1, -1.0),
+                plane(2, 1.0),
+                plane(2, -1.0),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// Whether any part of a box may lie inside the frustum. Like `intersects_sphere`, boxes near the
+    /// corners of the frustum may be accepted when they are just outside.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal is the last to leave its positive side
+            let furthest = Vec3::select(plane.normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            plane.signed_distance(furthest) >= 0.0
+        })
+    }
+
+    /// Whether any part of a sphere may lie inside the frustum. Spheres near the corners of the
+    /// frustum may be reported as intersecting when they are just outside, but a visible sphere is
+    /// never rejected.
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -radius)
+    }
+}
+
+/// An axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// The smallest box containing every point, or `None` if there are no points
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(first, first), |aabb, point| Aabb {
+            min: aabb.min.min(point),
+            max: aabb.max.max(point),
+        }))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) / 2.0
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// The smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// The smallest axis-aligned box containing this box after an affine transform
+    pub fn transformed(&self, transform: Mat4) -> Aabb {
+        let center = transform.transform_point3(self.center());
+        let half_extents = self.half_extents();
+        // Each axis of the new box spans the absolute contributions of the old box's extents
+        let extents = Vec3::new(
+            transform.row(0).xyz().abs().dot(half_extents),
+            transform.row(1).xyz().abs().dot(half_extents),
+            transform.row(2).xyz().abs().dot(half_extents),
+        );
+        Aabb::new(center - extents, center + extents)
+    }
+}
+
+/// A sphere enclosing a set of points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        BoundingSphere { center, radius }
+    }
+
+    /// The sphere circumscribing a box
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        BoundingSphere::new(aabb.center(), aabb.half_extents().length())
+    }
+
+    /// A sphere around the center of the points' bounding box, which contains every point, or `None`
+    /// if there are no points. It is not always the smallest such sphere.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3> + Clone) -> Option<Self> {
+        let center = Aabb::from_points(points.clone())?.center();
+        let radius = points
+            .into_iter()
+            .map(|point| point.distance_squared(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        Some(BoundingSphere::new(center, radius))
+    }
+
+    /// A sphere containing this sphere after an affine transform, scaling the radius by the largest
+    /// scale of the transform
+    pub fn transformed(&self, transform: Mat4) -> BoundingSphere {
+        let scale = [0, 1, 2]
+            .map(|axis| transform.col(axis).xyz().length())
+            .into_iter()
+            .fold(0.0, f32::max);
+        BoundingSphere::new(transform.transform_point3(self.center), self.radius * scale)
+    }
+}
+
+/// A volume that can be tested against a frustum, to skip drawing objects that cannot be seen
+pub trait BoundingVolume {
+    /// Whether any part of the volume may lie inside the frustum. A volume that is visible must
+    /// always return true, though one just outside may also do so.
+    fn intersects_frustum(&self, frustum: &Frustum) -> bool;
 }
 
-impl InverseLerp<Vec4> for Vec4 {
-    fn inverse_lerp(&self, to: Vec4, point: Vec4) -> f32 {
-        (point - *self).length_squared() / (to - *self).length_squared()
+impl BoundingVolume for Aabb {
+    fn intersects_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.intersects_aabb(self)
     }
 }
 
-impl InverseLerp<Vec2> for Vec2 {
-    fn inverse_lerp(&self, to: Vec2, point: Vec2) -> f32 {
-        (point - *self).length() / (to - *self).length()
+impl BoundingVolume for BoundingSphere {
+    fn intersects_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.intersects_sphere(self.center, self.radius)
     }
 }
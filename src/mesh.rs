@@ -0,0 +1,440 @@
+//! Loading triangle meshes from Wavefront OBJ files, along with their MTL materials.
+//!
+//! OBJ files index positions, texture coordinates and normals separately, while `Renderer::draw` takes
+//! a single index per vertex. The loader de-indexes faces into unified vertices, sharing a vertex
+//! between faces wherever all three of its indices match, and triangulates polygons as fans.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::{
+    error::{Error, Result},
+    math::Aabb,
+};
+
+/// A vertex of a loaded mesh, which can be passed directly to `Renderer::draw`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub position: Vec3,
+    /// The normal from the file, or a smooth normal averaged from the surrounding faces if the file
+    /// has none
+    pub normal: Vec3,
+    /// Texture coordinates, or zero if the file has none
+    pub uv: Vec2,
+    /// The direction of increasing u along the surface, with the handedness of the tangent frame in w,
+    /// such that the bitangent is `normal.cross(tangent.xyz()) * tangent.w`. Zero if the mesh has no
+    /// texture coordinates.
+    pub tangent: Vec4,
+}
+
+/// A material from an MTL file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// Ambient color, `Ka`
+    pub ambient: Vec3,
+    /// Diffuse color, `Kd`
+    pub diffuse: Vec3,
+    /// Specular color, `Ks`
+    pub specular: Vec3,
+    /// Emissive color, `Ke`
+    pub emissive: Vec3,
+    /// Specular exponent, `Ns`
+    pub shininess: f32,
+    /// Opacity, from `d` or one minus `Tr`. The renderer has no blending, so this is only used by
+    /// shaders that choose to, and `Renderer::render_scene` draws every material as opaque.
+    pub opacity: f32,
+    /// Diffuse texture, `map_Kd`, resolved relative to the MTL file
+    pub diffuse_texture: Option<PathBuf>,
+    /// Specular texture, `map_Ks`
+    pub specular_texture: Option<PathBuf>,
+    /// Tangent space normal map, `norm`, `bump` or `map_Bump`
+    pub normal_texture: Option<PathBuf>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::new(),
+            ambient: Vec3::ZERO,
+            diffuse: Vec3::ONE,
+            specular: Vec3::ZERO,
+            emissive: Vec3::ZERO,
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+/// A run of consecutive triangles that share a material
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialGroup {
+    /// Index into `Mesh::materials`, or `None` if the faces use no known material
+    pub material: Option<usize>,
+    /// The range of `Mesh::indices` that the group's triangles occupy
+    pub indices: Range<usize>,
+}
+
+/// A triangle mesh with unified vertices, ready to be drawn
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    /// Every three indices form a triangle, with counter-clockwise winding
+    pub indices: Vec<u32>,
+    pub materials: Vec<Material>,
+    /// Groups of triangles by material, which together cover every index in order
+    pub groups: Vec<MaterialGroup>,
+}
+
+fn malformed(line: usize, reason: impl Into<String>) -> Error {
+    Error::MalformedModel {
+        line,
+        reason: reason.into(),
+    }
+}
+
+fn parse_floats<const N: usize>(line: usize, args: &[&str]) -> Result<[f32; N]> {
+    let mut values = [0.0; N];
+    if args.len() < N {
+        return Err(malformed(line, format!("expected {N} numbers")));
+    }
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| malformed(line, format!("invalid number \"{arg}\"")))?;
+    }
+    Ok(values)
+}
+
+/// Joins lines ending in a backslash with the line that follows, and strips comments. Yields each
+/// logical line along with its line number in the file.
+fn logical_lines(source: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    let mut lines = source.lines().enumerate();
+    std::iter::from_fn(move || {
+        let (number, first) = lines.next()?;
+        let mut line = first.to_string();
+        while line.ends_with('\\') {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.push_str(next),
+                None => break,
+            }
+        }
+        if let Some(comment) = line.find('#') {
+            line.truncate(comment);
+        }
+        Some((number + 1, line))
+    })
+}
+
+/// Resolves an OBJ index, which counts from one, or backwards from the end of the list if negative
+fn resolve_index(line: usize, index: &str, count: usize) -> Result<usize> {
+    let value: i64 = index
+        .parse()
+        .map_err(|_| malformed(line, format!("invalid index \"{index}\"")))?;
+    let resolved = match value {
+        0 => -1,
+        1.. => value - 1,
+        ..=-1 => count as i64 + value,
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(malformed(line, format!("index {value} is out of bounds")));
+    }
+    Ok(resolved as usize)
+}
+
+impl Material {
+    /// Parses the materials of an MTL file. Texture paths are resolved relative to `base_dir`.
+    pub fn parse_mtl(source: &str, base_dir: &Path) -> Result<Vec<Material>> {
+        let mut materials: Vec<Material> = Vec::new();
+        for (line, text) in logical_lines(source) {
+            let mut tokens = text.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            if keyword == "newmtl" {
+                materials.push(Material {
+                    name: args.join(" "),
+                    ..Default::default()
+                });
+                continue;
+            }
+            let Some(material) = materials.last_mut() else {
+                return Err(malformed(line, "material property before any newmtl"));
+            };
+            // Texture options such as `-bm 1.0` precede the file name, which is always last
+            let texture = || args.last().map(|file| base_dir.join(file));
+            match keyword {
+                "Ka" => material.ambient = parse_floats::<3>(line, &args)?.into(),
+                "Kd" => material.diffuse = parse_floats::<3>(line, &args)?.into(),
+                "Ks" => material.specular = parse_floats::<3>(line, &args)?.into(),
+                "Ke" => material.emissive = parse_floats::<3>(line, &args)?.into(),
+                "Ns" => material.shininess = parse_floats::<1>(line, &args)?[0],
+                "d" => material.opacity = parse_floats::<1>(line, &args)?[0],
+                "Tr" => material.opacity = 1.0 - parse_floats::<1>(line, &args)?[0],
+                "map_Kd" => material.diffuse_texture = texture(),
+                "map_Ks" => material.specular_texture = texture(),
+                "norm" | "bump" | "map_Bump" | "map_bump" => material.normal_texture = texture(),
+                // Other properties, such as illumination models, are not used by the renderer
+                _ => (),
+            }
+        }
+        Ok(materials)
+    }
+
+    /// Loads the materials of an MTL file
+    pub fn load_mtl(path: impl AsRef<Path>) -> Result<Vec<Material>> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        Material::parse_mtl(&source, path.parent().unwrap_or(Path::new("")))
+    }
+}
+
+/// Accumulates the contents of an OBJ file as it is parsed
+#[derive(Default)]
+struct ObjBuilder {
+    positions: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    normals: Vec<Vec3>,
+    mesh: Mesh,
+    /// Maps each distinct (position, uv, normal) triple to its unified vertex
+    unified: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    /// Unified vertices that need a normal computed, with the position they came from
+    missing_normals: Vec<(u32, usize)>,
+    material: Option<usize>,
+}
+
+impl ObjBuilder {
+    fn vertex(&mut self, line: usize, corner: &str) -> Result<u32> {
+        let mut parts = corner.split('/');
+        let position = resolve_index(line, parts.next().unwrap_or(""), self.positions.len())?;
+        let mut optional = |count: usize| match parts.next() {
+            Some(index) if !index.is_empty() => resolve_index(line, index, count).map(Some),
+            _ => Ok(None),
+        };
+        let uv = optional(self.uvs.len())?;
+        let normal = optional(self.normals.len())?;
+
+        let key = (position, uv, normal);
+        if let Some(&index) = self.unified.get(&key) {
+            return Ok(index);
+        }
+        let index = self.mesh.vertices.len() as u32;
+        self.mesh.vertices.push(MeshVertex {
+            position: self.positions[position],
+            normal: normal.map_or(Vec3::ZERO, |normal| self.normals[normal]),
+            uv: uv.map_or(Vec2::ZERO, |uv| self.uvs[uv]),
+            tangent: Vec4::ZERO,
+        });
+        if normal.is_none() {
+            self.missing_normals.push((index, position));
+        }
+        self.unified.insert(key, index);
+        Ok(index)
+    }
+
+    fn face(&mut self, line: usize, corners: &[&str]) -> Result<()> {
+        if corners.len() < 3 {
+            return Err(malformed(line, "faces need at least three vertices"));
+        }
+        let vertices = corners
+            .iter()
+            .map(|corner| self.vertex(line, corner))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Start a new group when the material changes
+        let start = self.mesh.indices.len();
+        match self.mesh.groups.last_mut() {
+            Some(group) if group.material == self.material => (),
+            _ => self.mesh.groups.push(MaterialGroup {
+                material: self.material,
+                indices: start..start,
+            }),
+        }
+        for i in 1..vertices.len() - 1 {
+            self.mesh
+                .indices
+                .extend([vertices[0], vertices[i], vertices[i + 1]]);
+        }
+        if let Some(group) = self.mesh.groups.last_mut() {
+            group.indices.end = self.mesh.indices.len();
+        }
+        Ok(())
+    }
+
+    /// Gives vertices without a normal the area weighted average of the normals of every face that
+    /// shares their position
+    fn compute_missing_normals(&mut self) {
+        if self.missing_normals.is_empty() {
+            return;
+        }
+        let mut position_normals = vec![Vec3::ZERO; self.positions.len()];
+        let vertex_positions: HashMap<u32, usize> = self.missing_normals.iter().copied().collect();
+        for triangle in self.mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.mesh.vertices[triangle[i] as usize].position);
+            // The cross product's length is twice the triangle's area
+            let face_normal = (b - a).cross(c - a);
+            for index in triangle {
+                if let Some(&position) = vertex_positions.get(index) {
+                    position_normals[position] += face_normal;
+                }
+            }
+        }
+        for &(index, position) in &self.missing_normals {
+            self.mesh.vertices[index as usize].normal =
+                position_normals[position].normalize_or_zero();
+        }
+    }
+}
+
+impl Mesh {
+    /// Parses an OBJ file. `usemtl` statements are matched by name against `materials`, which become
+    /// the mesh's materials, while `mtllib` statements are ignored.
+    pub fn parse_obj(source: &str, materials: Vec<Material>) -> Result<Mesh> {
+        Mesh::parse_obj_with(source, materials, |_, _| Ok(()))
+    }
+
+    /// Parses an OBJ file, calling `load_library` with each material library it references
+    fn parse_obj_with(
+        source: &str,
+        materials: Vec<Material>,
+        mut load_library: impl FnMut(&str, &mut Vec<Material>) -> Result<()>,
+    ) -> Result<Mesh> {
+        let mut builder = ObjBuilder {
+            mesh: Mesh {
+                materials,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for (line, text) in logical_lines(source) {
+            let mut tokens = text.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            let args: Vec<&str> = tokens.collect();
+            match keyword {
+                // Any vertex colors following the position are ignored
+                "v" => builder
+                    .positions
+                    .push(parse_floats::<3>(line, &args)?.into()),
+                // The optional second texture coordinate defaults to zero, and a third is ignored
+                "vt" => {
+                    let [u] = parse_floats::<1>(line, &args)?;
+                    let v = match args.get(1) {
+                        Some(_) => parse_floats::<1>(line, &args[1..])?[0],
+                        None => 0.0,
+                    };
+                    builder.uvs.push(Vec2::new(u, v));
+                }
+                "vn" => builder.normals.push(parse_floats::<3>(line, &args)?.into()),
+                "f" => builder.face(line, &args)?,
+                "usemtl" => {
+                    let name = args.join(" ");
+                    builder.material = builder
+                        .mesh
+                        .materials
+                        .iter()
+                        .position(|material| material.name == name);
+                }
+                // Each argument names a separate library, so file names can't contain spaces
+                "mtllib" => {
+                    for library in args {
+                        load_library(library, &mut builder.mesh.materials)?;
+                    }
+                }
+                // Objects, groups, smoothing groups, lines and points do not affect the mesh
+                _ => (),
+            }
+        }
+
+        builder.compute_missing_normals();
+        if !builder.uvs.is_empty() {
+            builder.mesh.compute_tangents();
+        }
+        Ok(builder.mesh)
+    }
+
+    /// Loads an OBJ file, along with the materials of any MTL files it references
+    ///
+    /// Material libraries are resolved relative to the OBJ file. Libraries that do not exist are
+    /// skipped, leaving the faces that use their materials without one.
+    pub fn load_obj(path: impl AsRef<Path>) -> Result<Mesh> {
+        let path = path.as_ref();
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let source = fs::read_to_string(path)?;
+        Mesh::parse_obj_with(&source, Vec::new(), |library, materials| {
+            match Material::load_mtl(base_dir.join(library)) {
+                Ok(loaded) => materials.extend(loaded),
+                Err(Error::Io(err)) if err.kind() == ErrorKind::NotFound => (),
+                Err(err) => return Err(err),
+            }
+            Ok(())
+        })
+    }
+
+    /// Computes a tangent for every vertex from the texture coordinates of the triangles around it, as
+    /// needed for normal mapping
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![Vec3::ZERO; self.vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize]);
+            let (edge1, edge2) = (b.position - a.position, c.position - a.position);
+            let (duv1, duv2) = (b.uv - a.uv, c.uv - a.uv);
+            let det = duv1.perp_dot(duv2);
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            // Weighting by the sign alone lets every face contribute according to its area
+            let r = det.signum();
+            let tangent = (edge1 * duv2.y - edge2 * duv1.y) * r;
+            let bitangent = (edge2 * duv1.x - edge1 * duv2.x) * r;
+            for &index in triangle {
+                tangents[index as usize] += tangent;
+                bitangents[index as usize] += bitangent;
+            }
+        }
+
+        for ((vertex, tangent), bitangent) in self.vertices.iter_mut().zip(tangents).zip(bitangents)
+        {
+            // Make the tangent perpendicular to the normal
+            let tangent =
+                (tangent - vertex.normal * vertex.normal.dot(tangent)).normalize_or_zero();
+            let handedness = if vertex.normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            vertex.tangent = tangent.extend(handedness);
+        }
+    }
+
+    /// The bounding box of every vertex, or `None` if the mesh is empty
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|vertex| vertex.position))
+    }
+
+    /// Returns the indices of the triangles in a material group
+    pub fn group_indices(&self, group: &MaterialGroup) -> &[u32] {
+        &self.indices[group.indices.clone()]
+    }
+
+    /// Returns the material of a group, if it has one
+    pub fn group_material(&self, group: &MaterialGroup) -> Option<&Material> {
+        group.material.and_then(|index| self.materials.get(index))
+    }
+}
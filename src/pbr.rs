@@ -0,0 +1,438 @@
+//! Physically based shading, following the metallic-roughness model of glTF 2.0.
+//!
+//! Surfaces reflect light with a Lambertian diffuse term and a Cook-Torrance specular term, using the
+//! GGX microfacet distribution, the height-correlated Smith visibility term and Schlick's Fresnel
+//! approximation, as in the reference implementation of the glTF specification. `PbrShader` computes
+//! linear, unbounded radiance with `shade`, which its fragment shader then exposes, tone maps and
+//! encodes for display.
+//!
+//! Light intensities are in the units of glTF's punctual lights, so a white directional light of
+//! intensity π lights a white, rough, facing surface to a radiance of almost one.
+
+use std::f32::consts::PI;
+
+use glam::{Affine3A, Mat3, Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+use softrender_derive::Barycentric;
+
+use crate::{
+    math::ToneMapping,
+    mesh::{Material, MeshVertex},
+    scene::{DrawContext, LightInstance},
+    shader::{Barycentric, Shader},
+    shaders::{decode_srgb, encode_output, incoming_light, perturb_normal},
+    texture::{CubeFace, Cubemap, Texture},
+};
+
+/// Reflectance at normal incidence of dielectrics, such as plastic or stone
+const DIELECTRIC_F0: f32 = 0.04;
+/// Smallest roughness used for lighting, as perfectly smooth surfaces reflect point lights into
+/// infinitely small, infinitely bright highlights
+const MIN_ROUGHNESS: f32 = 0.03;
+
+/// The GGX (Trowbridge-Reitz) normal distribution function, the density of microfacets facing along
+/// the halfway vector
+pub fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let alpha_squared = roughness.powi(4);
+    let denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    alpha_squared / (PI * denominator * denominator)
+}
+
+/// The height-correlated Smith visibility function for GGX, which combines the masking and shadowing
+/// of microfacets with the `4 n·l n·v` denominator of the specular BRDF
+pub fn visibility_smith_ggx(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let alpha_squared = roughness.powi(4);
+    let view = n_dot_l * (n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared).sqrt();
+    let light = n_dot_v * (n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared).sqrt();
+    let sum = view + light;
+    if sum > 0.0 {
+        0.5 / sum
+    } else {
+        0.0
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance, given the reflectance at normal incidence
+pub fn fresnel_schlick(f0: Vec3, v_dot_h: f32) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * (1.0 - v_dot_h.clamp(0.0, 1.0)).powi(5)
+}
+
+/// The reflectance at normal incidence of a metallic-roughness material
+pub fn specular_f0(base_color: Vec3, metallic: f32) -> Vec3 {
+    Vec3::splat(DIELECTRIC_F0).lerp(base_color, metallic)
+}
+
+/// Evaluates the metallic-roughness BRDF multiplied by the cosine of the light's angle, which is the
+/// radiance reflected towards the viewer for each unit of radiance arriving from the light
+///
+/// All directions are normalized and point away from the surface.
+pub fn cook_torrance(
+    base_color: Vec3,
+    metallic: f32,
+    roughness: f32,
+    normal: Vec3,
+    to_view: Vec3,
+    to_light: Vec3,
+) -> Vec3 {
+    let n_dot_l = normal.dot(to_light);
+    if n_dot_l <= 0.0 {
+        return Vec3::ZERO;
+    }
+    let n_dot_v = normal.dot(to_view).max(1e-4);
+    let halfway = (to_view + to_light).normalize_or_zero();
+    let n_dot_h = normal.dot(halfway).max(0.0);
+    let v_dot_h = to_view.dot(halfway).max(0.0);
+    let roughness = roughness.clamp(MIN_ROUGHNESS, 1.0);
+
+    let fresnel = fresnel_schlick(specular_f0(base_color, metallic), v_dot_h);
+    let specular = fresnel
+        * distribution_ggx(n_dot_h, roughness)
+        * visibility_smith_ggx(n_dot_v, n_dot_l, roughness);
+    let diffuse = (Vec3::ONE - fresnel) * (1.0 - metallic) * base_color / PI;
+    (diffuse + specular) * n_dot_l
+}
+
+/// An approximation of the hemispherical-directional reflectance of the specular BRDF, which scales
+/// the specular color of image based lighting. This is Karis' analytic fit for mobile, in place of
+/// a precomputed lookup table.
+pub fn environment_brdf(f0: Vec3, n_dot_v: f32, roughness: f32) -> Vec3 {
+    let c0 = Vec4::new(-1.0, -0.0275, -0.572, 0.022);
+    let c1 = Vec4::new(1.0, 0.0425, 1.04, -0.04);
+    let r = roughness * c0 + c1;
+    let a004 = (r.x * r.x).min(2f32.powf(-9.28 * n_dot_v)) * r.x + r.y;
+    let scale_bias = Vec2::new(-1.04, 1.04) * a004 + r.zw();
+    f0 * scale_bias.x + scale_bias.y
+}
+
+/// The irradiance from a distant environment, projected onto the first nine spherical harmonics
+///
+/// Nine coefficients capture diffuse lighting from any environment to within a few percent, so they
+/// are a compact replacement for an irradiance cubemap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShIrradiance {
+    /// Coefficients of the environment's radiance, ordered by band and then from m = -l to m = l
+    pub coefficients: [Vec3; 9],
+}
+
+/// The real spherical harmonic basis functions up to the second band, for a normalized direction
+fn sh_basis(direction: Vec3) -> [f32; 9] {
+    let Vec3 { x, y, z } = direction;
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3.0 * z * z - 1.0),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y),
+    ]
+}
+
+impl ShIrradiance {
+    /// The harmonics of an environment with the same radiance in every direction
+    pub fn constant(radiance: Vec3) -> Self {
+        let mut coefficients = [Vec3::ZERO; 9];
+        coefficients[0] = radiance * (4.0 * PI).sqrt();
+        ShIrradiance { coefficients }
+    }
+
+    /// Projects the radiance of an environment cubemap onto the harmonics, weighting every texel by
+    /// the solid angle that it covers
+    pub fn from_cubemap(environment: &Cubemap) -> Self {
+        let size = environment.get_size();
+        let mut coefficients = [Vec3::ZERO; 9];
+        let mut total_weight = 0.0;
+        for face in CubeFace::ALL {
+            let texture = environment.get_face(face);
+            for y in 0..size {
+                for x in 0..size {
+                    let uv = (Vec2::new(x as f32, y as f32) + 0.5) / size as f32;
+                    let direction = face.direction(uv);
+                    // Texels towards the edges of a face are further away, and appear smaller
+                    let weight = direction.length_squared().powf(-1.5);
+                    let radiance = texture.texel(x as i32, y as i32);
+                    for (coefficient, basis) in
+                        coefficients.iter_mut().zip(sh_basis(direction.normalize()))
+                    {
+                        *coefficient += radiance * basis * weight;
+                    }
+                    total_weight += weight;
+                }
+            }
+        }
+        // The weights only approximate the solid angles, but they must add up to the whole sphere
+        let normalization = 4.0 * PI / total_weight;
+        ShIrradiance {
+            coefficients: coefficients.map(|coefficient| coefficient * normalization),
+        }
+    }
+
+    /// The irradiance arriving at a surface facing along `normal`, which needs no normalizing
+    pub fn irradiance(&self, normal: Vec3) -> Vec3 {
+        // Convolution with the clamped cosine lobe scales each band
+        const BAND_SCALES: [f32; 9] = [
+            PI,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+        ];
+        let basis = sh_basis(normal.normalize_or_zero());
+        let irradiance = (0..9).fold(Vec3::ZERO, |sum, i| {
+            sum + self.coefficients[i] * basis[i] * BAND_SCALES[i]
+        });
+        irradiance.max(Vec3::ZERO)
+    }
+
+    /// Bakes the irradiance into a cubemap, for use as `Irradiance::CUBEMAP`
+    pub fn to_cubemap(&self, size: u32) -> Cubemap {
+        Cubemap::from_fn(size, |direction| self.irradiance(direction))
+    }
+}
+
+/// The diffuse light arriving from the surroundings of a scene, in addition to its punctual lights
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy)]
+pub enum Irradiance<'a> {
+    /// The same irradiance on surfaces facing any direction
+    CONSTANT(Vec3),
+    SPHERICAL_HARMONICS(&'a ShIrradiance),
+    /// A cubemap of irradiance, sampled in the direction of the surface normal. This is a
+    /// convolution of the environment, such as one made by `ShIrradiance::to_cubemap`, rather than
+    /// the environment itself.
+    CUBEMAP(&'a Cubemap),
+}
+
+impl Irradiance<'_> {
+    pub fn sample(&self, normal: Vec3) -> Vec3 {
+        match self {
+            Irradiance::CONSTANT(irradiance) => *irradiance,
+            Irradiance::SPHERICAL_HARMONICS(harmonics) => harmonics.irradiance(normal),
+            Irradiance::CUBEMAP(cubemap) => cubemap.sample(normal),
+        }
+    }
+}
+
+/// A metallic-roughness material, with the factors and textures of a glTF material
+///
+/// The base color and emissive textures hold sRGB encoded colors, while the others hold linear data.
+#[derive(Clone, Copy)]
+pub struct PbrMaterial<'a> {
+    /// Linear base color, with alpha in `w`
+    pub base_color: Vec4,
+    pub base_color_texture: Option<&'a Texture>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness is read from the green channel, and metalness from the blue channel
+    pub metallic_roughness_texture: Option<&'a Texture>,
+    pub normal_texture: Option<&'a Texture>,
+    pub normal_scale: f32,
+    /// Ambient occlusion is read from the red channel, and only darkens image based lighting
+    pub occlusion_texture: Option<&'a Texture>,
+    pub occlusion_strength: f32,
+    pub emissive: Vec3,
+    pub emissive_texture: Option<&'a Texture>,
+}
+
+impl Default for PbrMaterial<'_> {
+    fn default() -> Self {
+        PbrMaterial {
+            base_color: Vec4::ONE,
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Vec3::ZERO,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl From<&Material> for PbrMaterial<'_> {
+    /// Approximates an MTL material as a dielectric, matching its specular exponent with the
+    /// equivalent roughness. Textures are only referred to by path, so they are left out.
+    fn from(material: &Material) -> Self {
+        PbrMaterial {
+            base_color: material.diffuse.extend(material.opacity),
+            metallic: 0.0,
+            roughness: (2.0 / (material.shininess + 2.0)).sqrt().sqrt(),
+            emissive: material.emissive,
+            ..Default::default()
+        }
+    }
+}
+
+/// The parameters of a material at a single point, after texturing
+struct SurfacePoint {
+    base_color: Vec3,
+    metallic: f32,
+    roughness: f32,
+    occlusion: f32,
+    emissive: Vec3,
+}
+
+impl PbrMaterial<'_> {
+    fn evaluate(&self, uv: Vec2) -> SurfacePoint {
+        let srgb_texel = |texture: &Texture| decode_srgb(texture.sample_bilinear(uv));
+        let mut base_color = self.base_color.xyz();
+        if let Some(texture) = self.base_color_texture {
+            base_color *= srgb_texel(texture);
+        }
+        let (mut metallic, mut roughness) = (self.metallic, self.roughness);
+        if let Some(texture) = self.metallic_roughness_texture {
+            let texel = texture.sample_bilinear(uv);
+            roughness *= texel.y;
+            metallic *= texel.z;
+        }
+        let occlusion = self.occlusion_texture.map_or(1.0, |texture| {
+            1.0 + self.occlusion_strength * (texture.sample_bilinear(uv).x - 1.0)
+        });
+        let mut emissive = self.emissive;
+        if let Some(texture) = self.emissive_texture {
+            emissive *= srgb_texel(texture);
+        }
+        SurfacePoint {
+            base_color,
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            occlusion,
+            emissive,
+        }
+    }
+}
+
+#[derive(Clone, Barycentric)]
+pub struct PbrVertexOut {
+    pub world_position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    /// World space tangent, with the handedness of the bitangent in `w`
+    pub tangent: Vec4,
+}
+
+/// Shades `MeshVertex` vertices with a metallic-roughness material, lit by any number of lights and
+/// by image based lighting from the surroundings
+#[derive(Clone, Copy)]
+pub struct PbrShader<'a> {
+    pub view_proj: Mat4,
+    pub model: Affine3A,
+    /// Transforms normals into world space
+    pub normal_matrix: Mat3,
+    pub camera_position: Vec3,
+    pub lights: &'a [LightInstance],
+    pub material: PbrMaterial<'a>,
+    /// Diffuse light from the surroundings
+    pub irradiance: Irradiance<'a>,
+    /// Radiance of the surroundings for specular reflections. Without one, reflections are taken
+    /// from the irradiance, as if the surroundings were blurry.
+    pub environment: Option<&'a Cubemap>,
+    /// Scales the radiance before tone mapping
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    /// Whether to encode output colors with the sRGB transfer curve, as displays expect. When off,
+    /// linear colors are written directly.
+    pub srgb_output: bool,
+}
+
+impl<'a> PbrShader<'a> {
+    /// Creates a shader for a scene's draw call, with its lights and no surrounding light. The
+    /// material is approximated from the draw call's MTL material, if it has one.
+    pub fn from_context(context: &DrawContext<'a>) -> Self {
+        PbrShader {
+            view_proj: context.view_proj,
+            model: context.model,
+            normal_matrix: context.normal_matrix,
+            camera_position: context.camera_position,
+            lights: context.lights,
+            material: context.material.map(PbrMaterial::from).unwrap_or_default(),
+            irradiance: Irradiance::CONSTANT(Vec3::ZERO),
+            environment: None,
+            exposure: 1.0,
+            tone_mapping: ToneMapping::ACES,
+            srgb_output: true,
+        }
+    }
+
+    /// Computes the linear radiance leaving a fragment towards the camera, before exposure and tone
+    /// mapping
+    pub fn shade(&self, interpolated: &PbrVertexOut) -> Vec3 {
+        let surface = self.material.evaluate(interpolated.uv);
+        let mut normal = interpolated.normal.normalize_or_zero();
+        if let Some(texture) = self.material.normal_texture {
+            normal = perturb_normal(
+                normal,
+                interpolated.tangent,
+                texture.sample_bilinear(interpolated.uv),
+                self.material.normal_scale,
+            );
+        }
+        let to_view = (self.camera_position - interpolated.world_position).normalize_or_zero();
+
+        let direct = self.lights.iter().fold(Vec3::ZERO, |sum, light| {
+            let (radiance, to_light) = incoming_light(light, interpolated.world_position);
+            sum + radiance
+                * cook_torrance(
+                    surface.base_color,
+                    surface.metallic,
+                    surface.roughness,
+                    normal,
+                    to_view,
+                    to_light,
+                )
+        });
+
+        // Image based lighting, with the diffuse term from irradiance and the specular term from
+        // the environment, scaled by the split sum approximation of the BRDF
+        let n_dot_v = normal.dot(to_view).max(1e-4);
+        let f0 = specular_f0(surface.base_color, surface.metallic);
+        let specular_color = environment_brdf(f0, n_dot_v, surface.roughness);
+        let reflected = (2.0 * n_dot_v * normal - to_view).normalize_or_zero();
+        let blurred = self.irradiance.sample(reflected) / PI;
+        // Without prefiltered mipmaps, rough reflections fade towards the irradiance instead
+        let prefiltered = self.environment.map_or(blurred, |environment| {
+            environment
+                .sample(reflected)
+                .lerp(blurred, surface.roughness * surface.roughness)
+        });
+        let diffuse_color =
+            (Vec3::ONE - specular_color) * (1.0 - surface.metallic) * surface.base_color;
+        let ambient =
+            diffuse_color * self.irradiance.sample(normal) / PI + specular_color * prefiltered;
+
+        direct + ambient * surface.occlusion + surface.emissive
+    }
+}
+
+impl Shader<MeshVertex, PbrVertexOut> for PbrShader<'_> {
+    fn vertex(&self, vertex: &MeshVertex) -> (Vec4, PbrVertexOut) {
+        let world_position = self.model.transform_point3(vertex.position);
+        (
+            self.view_proj * world_position.extend(1.0),
+            PbrVertexOut {
+                world_position,
+                normal: self.normal_matrix * vertex.normal,
+                uv: vertex.uv,
+                tangent: self
+                    .model
+                    .transform_vector3(vertex.tangent.xyz())
+                    .extend(vertex.tangent.w),
+            },
+        )
+    }
+
+    fn fragment(&self, interpolated: PbrVertexOut) -> UVec3 {
+        let color = self
+            .tone_mapping
+            .apply(self.shade(&interpolated) * self.exposure);
+        encode_output(color, self.srgb_output)
+    }
+}
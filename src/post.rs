@@ -0,0 +1,513 @@
+//! Full-screen post-processing of rendered images.
+//!
+//! A `PostProcess` pass computes every pixel of a new image from the rendered color and depth
+//! buffers, and any further buffers it needs. Pixels are computed independently, so a
+//! `PostPipeline` splits the image between threads, and runs its passes one after another with each
+//! reading the output of the last.
+
+use std::{num::NonZeroUsize, thread};
+
+use glam::{Vec2, Vec3};
+
+use crate::{
+    error::{Error, Result},
+    fb::Framebuffer,
+    math::{pack_color, to_output_color, unpack_color},
+    texture::Texture,
+};
+
+/// The buffers that a post-processing pass reads from
+///
+/// Every buffer should be the size of the color buffer. Reads past the edge of a buffer are clamped
+/// to its nearest pixel.
+#[derive(Clone, Copy)]
+pub struct PostInputs<'a> {
+    /// The image produced by the renderer, or by the previous pass of a pipeline
+    pub color: &'a Framebuffer<u32>,
+    pub depth: &'a Framebuffer<f32>,
+    /// Any further buffers the passes need, such as an ambient occlusion term
+    pub extra: &'a [&'a Framebuffer<f32>],
+}
+
+/// Reads a pixel, clamping coordinates that lie outside the framebuffer
+fn clamped_pixel<T: Default + Copy>(fb: &Framebuffer<T>, x: i32, y: i32) -> T {
+    let max_x = fb.get_width() as i32 - 1;
+    let max_y = fb.get_height() as i32 - 1;
+    if max_x < 0 || max_y < 0 {
+        return T::default();
+    }
+    fb.get_pixel(x.clamp(0, max_x) as u32, y.clamp(0, max_y) as u32)
+}
+
+impl PostInputs<'_> {
+    pub fn get_width(&self) -> u32 {
+        self.color.get_width()
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.color.get_height()
+    }
+
+    /// Reads a pixel of the color buffer as a color in the 0-1 range
+    pub fn color(&self, x: i32, y: i32) -> Vec3 {
+        unpack_color(clamped_pixel(self.color, x, y)).as_vec3() / 255.0
+    }
+
+    /// Samples the color buffer between pixels, blending the four nearest. Pixel centers lie on
+    /// integer coordinates.
+    pub fn sample_color(&self, position: Vec2) -> Vec3 {
+        let base = position.floor();
+        let frac = position - base;
+        let (x, y) = (base.x as i32, base.y as i32);
+        let bottom = self.color(x, y).lerp(self.color(x + 1, y), frac.x);
+        let top = self.color(x, y + 1).lerp(self.color(x + 1, y + 1), frac.x);
+        bottom.lerp(top, frac.y)
+    }
+
+    pub fn depth(&self, x: i32, y: i32) -> f32 {
+        clamped_pixel(self.depth, x, y)
+    }
+
+    /// Reads a pixel of one of the extra buffers
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no extra buffer at `index`.
+    pub fn extra(&self, index: usize, x: i32, y: i32) -> f32 {
+        clamped_pixel(self.extra[index], x, y)
+    }
+}
+
+/// A full-screen pass, computing each pixel of its output independently from its inputs
+///
+/// Closures taking the inputs and the pixel's coordinates are passes too.
+pub trait PostProcess: Sync {
+    /// Computes the color of the output pixel at (x, y), in the 0-1 range
+    fn process(&self, inputs: &PostInputs, x: u32, y: u32) -> Vec3;
+}
+
+impl<F: Fn(&PostInputs, u32, u32) -> Vec3 + Sync> PostProcess for F {
+    fn process(&self, inputs: &PostInputs, x: u32, y: u32) -> Vec3 {
+        self(inputs, x, y)
+    }
+}
+
+/// A chain of post-processing passes, run in the order they were added
+pub struct PostPipeline {
+    passes: Vec<Box<dyn PostProcess>>,
+    threads: usize,
+}
+
+impl Default for PostPipeline {
+    fn default() -> Self {
+        PostPipeline::new()
+    }
+}
+
+impl PostPipeline {
+    /// Creates an empty pipeline, using as many threads as the machine can run at once
+    pub fn new() -> Self {
+        PostPipeline {
+            passes: Vec::new(),
+            threads: default_threads(),
+        }
+    }
+
+    /// Adds a pass to the end of the pipeline
+    pub fn add(&mut self, pass: impl PostProcess + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets how many threads each pass is split between. Values below one are clamped to one.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Runs every pass over the image in `color`, and returns the result
+    pub fn run(
+        &self,
+        color: &Framebuffer<u32>,
+        depth: &Framebuffer<f32>,
+        extra: &[&Framebuffer<f32>],
+    ) -> Framebuffer<u32> {
+        let mut image = color.clone();
+        for pass in &self.passes {
+            let inputs = PostInputs {
+                color: &image,
+                depth,
+                extra,
+            };
+            image = self.run_pass(pass.as_ref(), &inputs);
+        }
+        image
+    }
+
+    fn run_pass(&self, pass: &dyn PostProcess, inputs: &PostInputs) -> Framebuffer<u32> {
+        compute_parallel(
+            inputs.get_width(),
+            inputs.get_height(),
+            self.threads,
+            |x, y| pack_color(to_output_color(pass.process(inputs, x, y))),
+        )
+    }
+}
+
+/// As many threads as the machine can run at once
+pub(crate) fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Fills a new framebuffer by computing every pixel independently, handing each thread a band of
+/// rows
+pub(crate) fn compute_parallel<T: Default + Copy + Send>(
+    width: u32,
+    height: u32,
+    threads: usize,
+    pixel: impl Fn(u32, u32) -> T + Sync,
+) -> Framebuffer<T> {
+    let mut output = Framebuffer::new(width, height);
+    if width == 0 || height == 0 {
+        return output;
+    }
+    let rows_per_band = (height as usize).div_ceil(threads.max(1));
+    let pixel = &pixel;
+    thread::scope(|scope| {
+        for (band, pixels) in output
+            .get_raw_mut()
+            .chunks_mut(rows_per_band * width as usize)
+            .enumerate()
+        {
+            scope.spawn(move || {
+                for (row, pixels) in pixels.chunks_mut(width as usize).enumerate() {
+                    // Rows are stored from the top down
+                    let y = height - 1 - (band * rows_per_band + row) as u32;
+                    for (x, value) in pixels.iter_mut().enumerate() {
+                        *value = pixel(x as u32, y);
+                    }
+                }
+            });
+        }
+    });
+    output
+}
+
+/// Weights of a normalized gaussian kernel, from its center out to three standard deviations
+pub(crate) fn gaussian_weights(sigma: f32) -> Vec<f32> {
+    let sigma = sigma.max(f32::EPSILON);
+    let radius = (sigma * 3.0).ceil() as usize;
+    let mut weights = (0..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    // Every weight but the center is used on both sides
+    let total = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+    for weight in &mut weights {
+        *weight /= total;
+    }
+    weights
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlurAxis {
+    HORIZONTAL,
+    VERTICAL,
+}
+
+/// Blurs the image along one axis with a gaussian kernel. A horizontal and a vertical pass one after
+/// the other blur in both directions, at a fraction of the cost of a two dimensional kernel.
+pub struct GaussianBlur {
+    weights: Vec<f32>,
+    axis: BlurAxis,
+}
+
+impl GaussianBlur {
+    /// Creates a blur with a standard deviation of `sigma` pixels
+    pub fn new(sigma: f32, axis: BlurAxis) -> Self {
+        GaussianBlur {
+            weights: gaussian_weights(sigma),
+            axis,
+        }
+    }
+
+    /// The pair of passes blurring in both directions
+    pub fn both_axes(sigma: f32) -> [GaussianBlur; 2] {
+        [
+            GaussianBlur::new(sigma, BlurAxis::HORIZONTAL),
+            GaussianBlur::new(sigma, BlurAxis::VERTICAL),
+        ]
+    }
+}
+
+impl PostProcess for GaussianBlur {
+    fn process(&self, inputs: &PostInputs, x: u32, y: u32) -> Vec3 {
+        let (x, y) = (x as i32, y as i32);
+        let (dx, dy) = match self.axis {
+            BlurAxis::HORIZONTAL => (1, 0),
+            BlurAxis::VERTICAL => (0, 1),
+        };
+        let mut color = inputs.color(x, y) * self.weights[0];
+        for (i, weight) in self.weights.iter().enumerate().skip(1) {
+            let i = i as i32;
+            color += (inputs.color(x + dx * i, y + dy * i) + inputs.color(x - dx * i, y - dy * i))
+                * *weight;
+        }
+        color
+    }
+}
+
+/// How bright a color looks, weighting its channels by the eye's sensitivity to them
+fn luma(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.299, 0.587, 0.114))
+}
+
+/// How many pixels FXAA searches along an edge in each direction to find where it ends
+const FXAA_SEARCH_STEPS: u32 = 12;
+
+/// Fast approximate anti-aliasing, which smooths jagged edges by finding them from contrast in the
+/// finished image
+///
+/// Each pixel on an edge is blended with its neighbour across the edge, by how close it is to the end
+/// of the edge's current step, which turns stairs into gradients.
+pub struct Fxaa {
+    /// The contrast, relative to the brightest nearby pixel, below which pixels are left alone
+    pub edge_threshold: f32,
+    /// The contrast below which pixels are left alone, to skip dark areas
+    pub edge_threshold_min: f32,
+    /// How much to smooth single pixel details, from zero to one
+    pub subpixel: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Fxaa {
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0312,
+            subpixel: 0.75,
+        }
+    }
+}
+
+impl PostProcess for Fxaa {
+    fn process(&self, inputs: &PostInputs, x: u32, y: u32) -> Vec3 {
+        let (x, y) = (x as i32, y as i32);
+        let luma_at = |dx: i32, dy: i32| luma(inputs.color(x + dx, y + dy));
+        let center_color = inputs.color(x, y);
+        let m = luma(center_color);
+        let (n, s, e, w) = (luma_at(0, 1), luma_at(0, -1), luma_at(1, 0), luma_at(-1, 0));
+        let max = m.max(n).max(s).max(e).max(w);
+        let min = m.min(n).min(s).min(e).min(w);
+        let range = max - min;
+        if range < self.edge_threshold_min.max(max * self.edge_threshold) {
+            return center_color;
+        }
+
+        let (ne, nw, se, sw) = (
+            luma_at(1, 1),
+            luma_at(-1, 1),
+            luma_at(1, -1),
+            luma_at(-1, -1),
+        );
+        // Whichever way the luma changes most sharply runs across the edge
+        let horizontal = (nw + sw - 2.0 * w).abs()
+            + 2.0 * (n + s - 2.0 * m).abs()
+            + (ne + se - 2.0 * e).abs();
+        let vertical = (nw + ne - 2.0 * n).abs()
+            + 2.0 * (w + e - 2.0 * m).abs()
+            + (sw + se - 2.0 * s).abs();
+        let is_horizontal = horizontal >= vertical;
+
+        // Single pixel details, which are too small to have an edge to follow
+        let average = (2.0 * (n + s + e + w) + ne + nw + se + sw) / 12.0;
+        let subpixel = ((average - m).abs() / range).clamp(0.0, 1.0);
+        let subpixel = subpixel * subpixel * (3.0 - 2.0 * subpixel);
+        let subpixel_blend = subpixel * subpixel * self.subpixel;
+
+        // Step across the edge towards the neighbour it contrasts with most
+        let (positive, negative) = if is_horizontal { (n, s) } else { (e, w) };
+        let (sign, opposite) = if (positive - m).abs() >= (negative - m).abs() {
+            (1.0, positive)
+        } else {
+            (-1.0, negative)
+        };
+        let gradient = (opposite - m).abs();
+        let (across, along) = if is_horizontal {
+            (Vec2::new(0.0, sign), Vec2::X)
+        } else {
+            (Vec2::new(sign, 0.0), Vec2::Y)
+        };
+
+        // Walk along the edge, halfway between this pixel and its neighbour, until the luma there
+        // changes enough to mark the end of the edge
+        let pixel = Vec2::new(x as f32, y as f32);
+        let start = pixel + across * 0.5;
+        let edge_luma = (m + opposite) / 2.0;
+        let search = |direction: Vec2| {
+            let mut delta = 0.0;
+            for step in 1..=FXAA_SEARCH_STEPS {
+                delta = luma(inputs.sample_color(start + direction * step as f32)) - edge_luma;
+                if delta.abs() >= gradient / 4.0 {
+                    return (step as f32, delta);
+                }
+            }
+            (FXAA_SEARCH_STEPS as f32, delta)
+        };
+        let (distance_positive, delta_positive) = search(along);
+        let (distance_negative, delta_negative) = search(-along);
+        let (distance, delta) = if distance_positive < distance_negative {
+            (distance_positive, delta_positive)
+        } else {
+            (distance_negative, delta_negative)
+        };
+        // Only the side of the step that the nearer end moves away from is blended
+        let edge_blend = if (delta < 0.0) != (m < edge_luma) {
+            0.5 - distance / (distance_positive + distance_negative)
+        } else {
+            0.0
+        };
+
+        inputs.sample_color(pixel + across * edge_blend.max(subpixel_blend))
+    }
+}
+
+/// Sharpens the image by exaggerating the difference between each pixel and its neighbours
+pub struct Sharpen {
+    /// How strongly to sharpen, where zero leaves the image as it is
+    pub strength: f32,
+}
+
+impl PostProcess for Sharpen {
+    fn process(&self, inputs: &PostInputs, x: u32, y: u32) -> Vec3 {
+        let (x, y) = (x as i32, y as i32);
+        let center = inputs.color(x, y);
+        let neighbours = inputs.color(x, y + 1)
+            + inputs.color(x, y - 1)
+            + inputs.color(x + 1, y)
+            + inputs.color(x - 1, y);
+        center + (center * 4.0 - neighbours) * self.strength
+    }
+}
+
+/// A three dimensional color lookup table, which maps every color to a new one for color grading
+///
+/// Colors between the entries of the table are blended trilinearly.
+#[derive(Clone)]
+pub struct ColorLut {
+    size: u32,
+    /// Entries with red varying fastest, then green, then blue
+    entries: Vec<Vec3>,
+}
+
+/// The number of entries in a color lookup table with `size` entries along each axis
+fn entry_count(size: u32) -> usize {
+    (size as usize)
+        .checked_pow(3)
+        .unwrap_or_else(|| panic!("a color lookup table of size {size} has too many entries"))
+}
+
+impl ColorLut {
+    /// Creates a table with `size` entries along each axis, ordered with red varying fastest, then
+    /// green, then blue
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is below two, or the number of entries is not `size` cubed.
+    pub fn new(size: u32, entries: Vec<Vec3>) -> Self {
+        assert!(size >= 2, "color lookup tables need at least two entries per axis");
+        assert_eq!(
+            entries.len(),
+            entry_count(size),
+            "entry count does not match the size of the color lookup table"
+        );
+        ColorLut { size, entries }
+    }
+
+    /// Creates a table by evaluating a function for every entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` cubed does not fit in a `usize`.
+    pub fn from_fn(size: u32, mut f: impl FnMut(Vec3) -> Vec3) -> Self {
+        let size = size.max(2);
+        let scale = 1.0 / (size - 1) as f32;
+        let mut entries = Vec::with_capacity(entry_count(size));
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    entries.push(f(Vec3::new(r as f32, g as f32, b as f32) * scale));
+                }
+            }
+        }
+        ColorLut::new(size, entries)
+    }
+
+    /// A table that leaves every color as it is
+    pub fn identity(size: u32) -> Self {
+        ColorLut::from_fn(size, |color| color)
+    }
+
+    /// Reads a table from the common strip layout, an image `size` pixels high and `size` squared
+    /// wide. Blue selects one of the square slices from left to right, and within a slice, red runs
+    /// from left to right and green from top to bottom.
+    pub fn from_texture(texture: &Texture) -> Result<Self> {
+        let size = texture.get_height();
+        if size < 2 || size.checked_mul(size) != Some(texture.get_width()) {
+            return Err(Error::SizeMismatch {
+                expected: (size.saturating_mul(size), size),
+                actual: (texture.get_width(), size),
+            });
+        }
+        let mut entries = Vec::with_capacity(entry_count(size));
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    // Texture rows are counted from the bottom up
+                    entries.push(texture.texel((b * size + r) as i32, (size - 1 - g) as i32));
+                }
+            }
+        }
+        Ok(ColorLut::new(size, entries))
+    }
+
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    fn entry(&self, r: u32, g: u32, b: u32) -> Vec3 {
+        let size = self.size as usize;
+        self.entries[(b as usize * size + g as usize) * size + r as usize]
+    }
+
+    /// Maps a color through the table, clamping it to the 0-1 range first
+    pub fn lookup(&self, color: Vec3) -> Vec3 {
+        let max = (self.size - 1) as f32;
+        let coords = color.clamp(Vec3::ZERO, Vec3::ONE) * max;
+        // Keep the base one entry short of the end, so the entry past it always exists
+        let base = coords.floor().min(Vec3::splat(max - 1.0));
+        let frac = coords - base;
+        let (r, g, b) = (base.x as u32, base.y as u32, base.z as u32);
+
+        let lerp_r = |g: u32, b: u32| self.entry(r, g, b).lerp(self.entry(r + 1, g, b), frac.x);
+        let near = lerp_r(g, b).lerp(lerp_r(g + 1, b), frac.y);
+        let far = lerp_r(g, b + 1).lerp(lerp_r(g + 1, b + 1), frac.y);
+        near.lerp(far, frac.z)
+    }
+}
+
+impl PostProcess for ColorLut {
+    fn process(&self, inputs: &PostInputs, x: u32, y: u32) -> Vec3 {
+        self.lookup(inputs.color(x as i32, y as i32))
+    }
+}
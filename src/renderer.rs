@@ -1,11 +1,15 @@
 use crate::{
+    error::{Error, Result},
     fb::Framebuffer,
-    math::{ClipPlane, InverseLerp},
+    fog::Fog,
+    math::{blend_color, pack_color, to_output_color, BoundingVolume, ClipPlane, Frustum},
+    post::PostPipeline,
     shader::{Barycentric, Shader},
+    texture::Cubemap,
 };
 
 use arrayvec::ArrayVec;
-use glam::{vec4, IVec2, Mat4, Vec2, Vec2Swizzles, Vec3, Vec4, Vec4Swizzles};
+use glam::{vec4, BVec3, IVec2, Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
 
 struct BoundingBox2D {
     origin: IVec2,
@@ -13,66 +17,446 @@ struct BoundingBox2D {
     height: i32,
 }
 
+#[allow(non_camel_case_types)]
 pub enum DrawMode {
     REGULAR,
     WIREFRAME,
+    /// Shades triangles as usual, and outlines their edges in the renderer's wireframe color
+    SHADED_WIREFRAME,
+    /// Draws only the edges of triangles that are not hidden behind other triangles. Triangle
+    /// interiors are filled with the most recent clear color, and edges are colored by the shader.
+    HIDDEN_LINE,
+    /// Only writes to the depth buffer. The fragment shader is never run and the color buffer is left
+    /// untouched, which makes for a cheap depth pre-pass or shadow map pass. Since
+    /// `Shader::point_fragment` isn't run either, points write depth over their whole square, even
+    /// where they would discard fragments.
+    DEPTH_ONLY,
 }
 
-fn calculate_screenspace_matrix(width: f32, height: f32) -> Mat4 {
-    let max_cols = width - 1.0;
-    let max_rows = height - 1.0;
+/// How the indices passed to a draw call are assembled into primitives
+pub enum Topology {
+    /// Every three indices form a triangle
+    TRIANGLES,
+    /// Every two indices form a line segment
+    LINES,
+    /// Every index forms a single point, rasterized as a screen-aligned square
+    POINTS,
+}
+
+/// A rectangle of the framebuffer that primitives are mapped onto and clipped to, where (x, y) is its
+/// bottom left pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// The largest pixel coordinates that lie inside the viewport
+    fn max_pixel(&self) -> Vec2 {
+        Vec2::new(
+            (self.x + self.width) as f32 - 1.0,
+            (self.y + self.height) as f32 - 1.0,
+        )
+    }
+
+    fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x - self.x < self.width && y >= self.y && y - self.y < self.height
+    }
+}
+
+fn calculate_screenspace_matrix(viewport: &Viewport) -> Mat4 {
+    let max_cols = viewport.width as f32 - 1.0;
+    let max_rows = viewport.height as f32 - 1.0;
     Mat4::from_cols(
         vec4(max_cols / 2.0, 0.0, 0.0, 0.0),
         vec4(0.0, max_rows / 2.0, 0.0, 0.0),
         vec4(0.0, 0.0, 1.0, 0.0),
-        vec4(max_cols / 2.0, max_rows / 2.0, 1.0, 1.0),
+        vec4(
+            viewport.x as f32 + max_cols / 2.0,
+            viewport.y as f32 + max_rows / 2.0,
+            1.0,
+            1.0,
+        ),
     )
 }
 
+/// Counts of the draw calls made through `draw_culled` since the statistics were last reset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullStats {
+    /// Draw calls whose bounds were at least partly visible, and so were drawn
+    pub drawn: usize,
+    /// Draw calls whose bounds lay entirely outside the view, and so were skipped
+    pub culled: usize,
+}
+
 pub struct Renderer {
     cb: Framebuffer<u32>,
     db: Framebuffer<f32>,
     draw_mode: DrawMode,
+    topology: Topology,
+    line_width: f32,
+    line_antialiasing: bool,
+    point_size: f32,
+    wireframe_color: u32,
+    clear_color: u32,
+    viewport: Viewport,
     screenspace_matrix: Mat4,
+    cull_stats: CullStats,
+    fog: Option<FogState>,
+}
+
+/// Fog, along with the matrices needed to rebuild each fragment's world position for height fog
+struct FogState {
+    fog: Fog,
+    world_from_clip: Mat4,
+    camera_position: Vec3,
 }
 
 // TODO: Determine how stateful this renderer should be. Store state, or pass as args to draw call?
 impl Renderer {
-    pub fn new(width: u16, height: u16) -> Self {
-        let a = calculate_screenspace_matrix(width as f32, height as f32);
+    pub fn new(width: u32, height: u32) -> Self {
+        let viewport = Viewport {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
         Renderer {
             cb: Framebuffer::new(width, height),
             db: Framebuffer::new(width, height),
             draw_mode: DrawMode::REGULAR,
-            screenspace_matrix: a,
+            topology: Topology::TRIANGLES,
+            line_width: 1.0,
+            line_antialiasing: false,
+            point_size: 1.0,
+            wireframe_color: 0xFFFFFF,
+            clear_color: 0,
+            viewport,
+            screenspace_matrix: calculate_screenspace_matrix(&viewport),
+            cull_stats: CullStats::default(),
+            fog: None,
         }
     }
 
-    pub fn set_fb_size(&mut self, width: u16, height: u16) {
+    /// Resizes the framebuffers, and resets the viewport to cover all of them
+    pub fn set_fb_size(&mut self, width: u32, height: u32) {
         self.cb.resize(width, height, 0);
         self.db.resize(width, height, 0.0);
-        self.screenspace_matrix = calculate_screenspace_matrix(width as f32, height as f32);
+        self.viewport = Viewport {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        self.screenspace_matrix = calculate_screenspace_matrix(&self.viewport);
     }
 
+    /// Restricts drawing to a rectangle of the framebuffer, whose bottom left pixel lies at (x, y).
+    /// Normalized device coordinates are mapped onto this rectangle, so several views can be drawn
+    /// side by side into one larger buffer, such as a window atlas.
+    ///
+    /// Returns an error, leaving the viewport unchanged, if the rectangle does not fit within the
+    /// framebuffer.
+    pub fn set_viewport(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+        // Borrowing the region performs the bounds checks for us
+        self.cb.view(x, y, width, height)?;
+        self.viewport = Viewport {
+            x,
+            y,
+            width,
+            height,
+        };
+        self.screenspace_matrix = calculate_screenspace_matrix(&self.viewport);
+        Ok(())
+    }
+
+    pub fn get_viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    /// Returns the color buffer, as drawn so far
+    pub fn get_color_buffer(&self) -> &Framebuffer<u32> {
+        &self.cb
+    }
+
+    /// Returns the depth buffer, as drawn so far. Depths are in normalized device coordinates, where
+    /// 1.0 is the far plane and the value the buffer is cleared to.
+    pub fn get_depth_buffer(&self) -> &Framebuffer<f32> {
+        &self.db
+    }
+
+    /// Clears the color and depth buffers within the current viewport, and resets the culling
+    /// statistics for the new frame
     pub fn clear_framebuffer(&mut self, new_color: u32) {
         // TODO: Allow specifying which to clear
-        self.cb.fill(new_color);
-        self.db.fill(1.0);
+        self.cull_stats = CullStats::default();
+        let Viewport {
+            x,
+            y,
+            width,
+            height,
+        } = self.viewport;
+        self.clear_color = new_color;
+        // The viewport is always kept within the bounds of both buffers
+        if let (Ok(mut cb), Ok(mut db)) = (
+            self.cb.view_mut(x, y, width, height),
+            self.db.view_mut(x, y, width, height),
+        ) {
+            cb.fill(new_color);
+            db.fill(1.0);
+        }
     }
 
     pub fn set_draw_mode(&mut self, new_mode: DrawMode) {
         self.draw_mode = new_mode;
     }
 
+    pub fn set_topology(&mut self, new_topology: Topology) {
+        self.topology = new_topology;
+    }
+
+    /// Sets the width, in pixels, of rasterized lines. Widths below one pixel are clamped to one.
+    pub fn set_line_width(&mut self, new_width: f32) {
+        self.line_width = new_width.max(1.0);
+    }
+
+    /// Enables or disables coverage-based anti-aliasing of rasterized lines
+    pub fn set_line_antialiasing(&mut self, enabled: bool) {
+        self.line_antialiasing = enabled;
+    }
+
+    /// Sets the color used to outline triangle edges in `DrawMode::SHADED_WIREFRAME`
+    pub fn set_wireframe_color(&mut self, new_color: u32) {
+        self.wireframe_color = new_color;
+    }
+
+    /// Sets the default width, in pixels, of rasterized points. Shaders may override this per-vertex
+    /// through `Shader::point_size`.
+    pub fn set_point_size(&mut self, new_size: f32) {
+        self.point_size = new_size.max(1.0);
+    }
+
+    /// Enables fog for subsequent draws, or disables it with `None`
+    ///
+    /// Fog is blended into the output of every shader, after shading. The view depth of each fragment
+    /// is its interpolated clip space w, which is the distance along the view direction for
+    /// perspective projections, and always one for orthographic ones. Height fog also needs the world
+    /// position of each fragment, which is rebuilt from its depth through `view` and `projection`.
+    /// These must match the matrices being drawn with, so set the fog again whenever they change.
+    pub fn set_fog(&mut self, fog: Option<Fog>, view: Mat4, projection: Mat4) {
+        self.fog = fog.map(|fog| FogState {
+            fog,
+            world_from_clip: (projection * view).inverse(),
+            camera_position: view.inverse().transform_point3(Vec3::ZERO),
+        });
+    }
+
+    pub fn get_fog(&self) -> Option<&Fog> {
+        self.fog.as_ref().map(|state| &state.fog)
+    }
+
+    /// Draws the primitives described by `ibo` with the given shader, and returns the color buffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index buffer is invalid for the vertex buffer and current topology. See `try_draw`
+    /// for a non-panicking alternative.
     pub fn draw<S: Shader<Vertex, VI>, Vertex, VI: Barycentric + Clone>(
         &mut self,
         shader: &mut S,
         vbo: &[Vertex],
         ibo: &[u32],
     ) -> &Framebuffer<u32> {
+        match self.try_draw(shader, vbo, ibo) {
+            Ok(cb) => cb,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Draws the primitives described by `ibo` with the given shader, and returns the color buffer
+    ///
+    /// The index buffer is validated before anything is drawn, so an error leaves the framebuffers
+    /// untouched. It must contain a whole number of primitives for the current topology, and every
+    /// index must refer to a vertex in `vbo`.
+    pub fn try_draw<S: Shader<Vertex, VI>, Vertex, VI: Barycentric + Clone>(
+        &mut self,
+        shader: &mut S,
+        vbo: &[Vertex],
+        ibo: &[u32],
+    ) -> Result<&Framebuffer<u32>> {
+        self.validate_indices(vbo.len(), ibo)?;
+
         // Rough draft of the pipeline. Will likely change.
         // TODO: Multithreading
+        match self.topology {
+            Topology::TRIANGLES => self.draw_triangles(shader, vbo, ibo),
+            Topology::LINES => self.draw_lines(shader, vbo, ibo),
+            Topology::POINTS => self.draw_points(shader, vbo, ibo),
+        }
+
+        // We've completed a drawcall into the framebuffer, present it to the user so they can
+        // do whatever they need with it
+        Ok(&self.cb)
+    }
+
+    /// Draws like `draw`, unless `bounds` lies entirely outside the view, in which case nothing is
+    /// shaded at all
+    ///
+    /// `bounds` must enclose every vertex in object space, and `clip_from_object` must be the
+    /// transform that the shader applies to reach clip space, usually the model-view-projection
+    /// matrix. Whether the call was drawn or culled is counted in `get_cull_stats`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index buffer is invalid for the vertex buffer and current topology. See
+    /// `try_draw_culled` for a non-panicking alternative.
+    pub fn draw_culled<S: Shader<Vertex, VI>, Vertex, VI: Barycentric + Clone>(
+        &mut self,
+        shader: &mut S,
+        vbo: &[Vertex],
+        ibo: &[u32],
+        bounds: &impl BoundingVolume,
+        clip_from_object: Mat4,
+    ) -> &Framebuffer<u32> {
+        match self.try_draw_culled(shader, vbo, ibo, bounds, clip_from_object) {
+            Ok(cb) => cb,
+            Err(err) => panic!("{err}"),
+        }
+    }
 
+    /// Draws like `try_draw`, unless `bounds` lies entirely outside the view. See `draw_culled`.
+    pub fn try_draw_culled<S: Shader<Vertex, VI>, Vertex, VI: Barycentric + Clone>(
+        &mut self,
+        shader: &mut S,
+        vbo: &[Vertex],
+        ibo: &[u32],
+        bounds: &impl BoundingVolume,
+        clip_from_object: Mat4,
+    ) -> Result<&Framebuffer<u32>> {
+        // Extracting the planes from the full transform puts them in object space, so the bounds
+        // never need transforming
+        if !bounds.intersects_frustum(&Frustum::from_matrix(clip_from_object)) {
+            self.validate_indices(vbo.len(), ibo)?;
+            self.cull_stats.culled += 1;
+            return Ok(&self.cb);
+        }
+        self.try_draw(shader, vbo, ibo)?;
+        self.cull_stats.drawn += 1;
+        Ok(&self.cb)
+    }
+
+    /// Returns how many calls to `draw_culled` were drawn and culled since the last call to
+    /// `clear_framebuffer` or `reset_cull_stats`
+    pub fn get_cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+
+    pub fn reset_cull_stats(&mut self) {
+        self.cull_stats = CullStats::default();
+    }
+
+    /// Counts a draw call that was culled before reaching `draw_culled`, as `render_scene` does
+    pub(crate) fn count_culled(&mut self) {
+        self.cull_stats.culled += 1;
+    }
+
+    /// Fills the background of the view with a cubemap of the distant surroundings, and returns the
+    /// color buffer
+    ///
+    /// Only pixels whose depth is still at the far plane are filled, so the skybox can be drawn after
+    /// the opaque parts of the scene to shade just the pixels they left uncovered. The depth buffer is
+    /// left as it is. `view_proj` is the camera's view-projection matrix, and only its rotation
+    /// matters, so the skybox never gets any closer. Colors are written as sampled, in the 0-1 range.
+    pub fn draw_skybox(&mut self, skybox: &Cubemap, view_proj: Mat4) -> &Framebuffer<u32> {
+        if !self.writes_color() {
+            return &self.cb;
+        }
+        let clip_to_world = view_proj.inverse();
+        let Viewport {
+            x,
+            y,
+            width,
+            height,
+        } = self.viewport;
+        // The inverse of the viewport transform, mapping pixel centers back to ndc
+        let max_pixel = Vec2::new(
+            (width as f32 - 1.0).max(1.0),
+            (height as f32 - 1.0).max(1.0),
+        );
+        for py in y..y + height {
+            for px in x..x + width {
+                // SAFETY: The viewport is always kept within the bounds of both buffers.
+                if unsafe { self.db.get_pixel_unchecked(px, py) } < 1.0 {
+                    continue;
+                }
+                let ndc = Vec2::new((px - x) as f32, (py - y) as f32) / max_pixel * 2.0 - 1.0;
+                // The view ray runs from the near plane to the far plane through this pixel
+                let near = clip_to_world.project_point3(ndc.extend(0.0));
+                let far = clip_to_world.project_point3(ndc.extend(1.0));
+                let color = pack_color(to_output_color(skybox.sample(far - near)));
+                // SAFETY: As above
+                unsafe { self.cb.plot_pixel_unchecked(px, py, color) };
+            }
+        }
+        &self.cb
+    }
+
+    /// Runs a post-processing pipeline over the color buffer, replacing it with the result, and
+    /// returns the color buffer
+    ///
+    /// The passes read the depth buffer as it was left by drawing. The whole of the color buffer is
+    /// processed, regardless of the viewport.
+    pub fn post_process(&mut self, pipeline: &PostPipeline) -> &Framebuffer<u32> {
+        self.post_process_with(pipeline, &[])
+    }
+
+    /// Runs a post-processing pipeline like `post_process`, handing the passes further buffers to
+    /// read, such as an ambient occlusion term
+    pub fn post_process_with(
+        &mut self,
+        pipeline: &PostPipeline,
+        extra: &[&Framebuffer<f32>],
+    ) -> &Framebuffer<u32> {
+        if !pipeline.is_empty() {
+            self.cb = pipeline.run(&self.cb, &self.db, extra);
+        }
+        &self.cb
+    }
+
+    /// Checks that an index buffer describes whole primitives for the current topology, and only
+    /// references vertices that exist
+    fn validate_indices(&self, vertex_count: usize, ibo: &[u32]) -> Result<()> {
+        let vertices_per_primitive = match self.topology {
+            Topology::TRIANGLES => 3,
+            Topology::LINES => 2,
+            Topology::POINTS => 1,
+        };
+        if ibo.len() % vertices_per_primitive != 0 {
+            return Err(Error::IncompletePrimitive {
+                index_count: ibo.len(),
+                vertices_per_primitive,
+            });
+        }
+
+        match ibo.iter().position(|&index| index as usize >= vertex_count) {
+            Some(position) => Err(Error::IndexOutOfBounds {
+                position,
+                index: ibo[position],
+                vertex_count,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn draw_triangles<S: Shader<Vertex, VI>, Vertex, VI: Barycentric + Clone>(
+        &mut self,
+        shader: &mut S,
+        vbo: &[Vertex],
+        ibo: &[u32],
+    ) {
         // Each triangle will always have 3 indices/vertices
         for i in (0..ibo.len()).step_by(3) {
             let v0_idx = ibo[i] as usize;
@@ -80,11 +464,12 @@ impl Renderer {
             let v2_idx = ibo[i + 2] as usize;
 
             // After the vertex shader is run, our vertices now exist in clip space.
-            let final_tris = self.clip_triangle(
-                shader.vertex(&vbo[v0_idx]),
-                shader.vertex(&vbo[v1_idx]),
-                shader.vertex(&vbo[v2_idx]),
-            );
+            let v0 = shader.vertex(&vbo[v0_idx]);
+            // The first vertex of the triangle is its provoking vertex, which supplies the value of any
+            // flat attributes across the entire triangle, regardless of how it ends up being clipped.
+            let provoking = v0.1.clone();
+            let final_tris =
+                self.clip_triangle(v0, shader.vertex(&vbo[v1_idx]), shader.vertex(&vbo[v2_idx]));
 
             // Now we iterate over every triangle in our fan for the rest of this original user primitive
             for j in 0..final_tris.len() {
@@ -101,8 +486,15 @@ impl Renderer {
                 let screen_p1 = (self.screenspace_matrix * clip_pos[1].xyz().extend(1.0)).xy();
                 let screen_p2 = (self.screenspace_matrix * clip_pos[2].xyz().extend(1.0)).xy();
 
+                // Only the edges of the fan that lie on the boundary of the clipped polygon belong to
+                // the original primitive. The ones inside it should never be outlined.
+                let edges = [j == 0, true, j == final_tris.len() - 1];
+
                 match self.draw_mode {
-                    DrawMode::REGULAR => {
+                    DrawMode::REGULAR
+                    | DrawMode::SHADED_WIREFRAME
+                    | DrawMode::HIDDEN_LINE
+                    | DrawMode::DEPTH_ONLY => {
                         self.plot_triangle(
                             screen_p0,
                             screen_p1,
@@ -110,38 +502,112 @@ impl Renderer {
                             &clip_pos,
                             shader,
                             &final_tris[j].1,
+                            &provoking,
+                            edges,
                         );
                     }
                     DrawMode::WIREFRAME => {
-                        self.plot_line(
-                            screen_p0,
-                            screen_p1,
+                        // Lines carry their depth and clip-space w along with their screen position,
+                        // so that they can be depth tested and perspective-correctly interpolated.
+                        let line_p0 =
+                            Vec4::new(screen_p0.x, screen_p0.y, clip_pos[0].z, clip_pos[0].w);
+                        let line_p1 =
+                            Vec4::new(screen_p1.x, screen_p1.y, clip_pos[1].z, clip_pos[1].w);
+                        let line_p2 =
+                            Vec4::new(screen_p2.x, screen_p2.y, clip_pos[2].z, clip_pos[2].w);
+                        self.rasterize_line(
+                            line_p0,
+                            line_p1,
                             shader,
                             &final_tris[j].1[0],
                             &final_tris[j].1[1],
+                            &provoking,
                         );
-                        self.plot_line(
-                            screen_p1,
-                            screen_p2,
+                        self.rasterize_line(
+                            line_p1,
+                            line_p2,
                             shader,
                             &final_tris[j].1[1],
                             &final_tris[j].1[2],
+                            &provoking,
                         );
-                        self.plot_line(
-                            screen_p2,
-                            screen_p0,
+                        self.rasterize_line(
+                            line_p2,
+                            line_p0,
                             shader,
                             &final_tris[j].1[2],
                             &final_tris[j].1[0],
+                            &provoking,
                         );
                     }
                 }
             }
         }
+    }
 
-        // We've completed a drawcall into the framebuffer, present it to the user so they can
-        // do whatever they need with it
-        &self.cb
+    fn draw_lines<S: Shader<Vertex, VI>, Vertex, VI: Barycentric + Clone>(
+        &mut self,
+        shader: &mut S,
+        vbo: &[Vertex],
+        ibo: &[u32],
+    ) {
+        // Each line will always have 2 indices/vertices
+        for i in (0..ibo.len()).step_by(2) {
+            let v0_idx = ibo[i] as usize;
+            let v1_idx = ibo[i + 1] as usize;
+
+            let v0 = shader.vertex(&vbo[v0_idx]);
+            // As with triangles, the first vertex of the line is its provoking vertex
+            let provoking = v0.1.clone();
+            let Some((v0, v1)) = self.clip_line(v0, shader.vertex(&vbo[v1_idx])) else {
+                // The line lies entirely outside the viewing frustum
+                continue;
+            };
+
+            // Perspective divide, then convert from ndc to screenspace, keeping the ndc depth and
+            // clip-space w around for the line rasterizer.
+            let ndc_p0 = v0.0.xyz() / v0.0.w;
+            let ndc_p1 = v1.0.xyz() / v1.0.w;
+            let screen_p0 = (self.screenspace_matrix * ndc_p0.extend(1.0)).xy();
+            let screen_p1 = (self.screenspace_matrix * ndc_p1.extend(1.0)).xy();
+
+            self.rasterize_line(
+                Vec4::new(screen_p0.x, screen_p0.y, ndc_p0.z, v0.0.w),
+                Vec4::new(screen_p1.x, screen_p1.y, ndc_p1.z, v1.0.w),
+                shader,
+                &v0.1,
+                &v1.1,
+                &provoking,
+            );
+        }
+    }
+
+    fn draw_points<S: Shader<Vertex, VI>, Vertex, VI: Barycentric + Clone>(
+        &mut self,
+        shader: &mut S,
+        vbo: &[Vertex],
+        ibo: &[u32],
+    ) {
+        for &idx in ibo {
+            let vertex = &vbo[idx as usize];
+            let (clip_pos, attribs) = shader.vertex(vertex);
+
+            // Points are never clipped, only discarded entirely when their center lies outside the
+            // viewing frustum. Points straddling the edge of the screen are cut off during rasterization.
+            let inside =
+                (0..3).all(|axis| -clip_pos.w <= clip_pos[axis] && clip_pos[axis] <= clip_pos.w);
+            if !inside {
+                continue;
+            }
+
+            let size = shader
+                .point_size(vertex)
+                .unwrap_or(self.point_size)
+                .max(1.0);
+            let ndc_pos = clip_pos.xyz() / clip_pos.w;
+            let screen_pos = (self.screenspace_matrix * ndc_pos.extend(1.0)).xy();
+            self.plot_point(screen_pos, ndc_pos.z, clip_pos.w, size, shader, attribs);
+        }
     }
 
     /// Clips a triangle primitive against the viewing frustum, using the homogenous coordinate w
@@ -160,11 +626,11 @@ impl Renderer {
     /// # Arguments
     ///
     /// * v0 - A tuple containing the first vertex's (in counter-clockwise order) clip-space position
-    ///        and its vertex attributes.
+    ///   and its vertex attributes.
     /// * v1 - A tuple containing the second vertex's (in counter-clockwise order) clip-space position
-    ///        and its vertex attributes.
+    ///   and its vertex attributes.
     /// * v2 - A tuple containing the third vertex's (in counter-clockwise order) clip-space position
-    ///        and its vertex attributes.
+    ///   and its vertex attributes.
     ///
     /// # Returns
     ///
@@ -272,6 +738,49 @@ impl Renderer {
         final_tris
     }
 
+    /// Clips a line primitive against the viewing frustum, using the homogenous coordinate w
+    ///
+    /// This works identically to `clip_triangle`, except that a line segment can never produce new
+    /// vertices: each clip plane can only shorten it by moving one of its two endpoints.
+    ///
+    /// # Arguments
+    ///
+    /// * v0 - A tuple containing the start vertex's clip-space position and its vertex attributes.
+    /// * v1 - A tuple containing the end vertex's clip-space position and its vertex attributes.
+    ///
+    /// # Returns
+    ///
+    /// The clipped line segment, or None if the line lies entirely outside the viewing frustum.
+    fn clip_line<VI: Barycentric>(
+        &self,
+        mut v0: (Vec4, VI),
+        mut v1: (Vec4, VI),
+    ) -> Option<((Vec4, VI), (Vec4, VI))> {
+        let mut clip_plane = ClipPlane { sign: 1.0, axis: 2 };
+
+        for clip_plane_idx in 0..6 {
+            let inside_clip_plane = if clip_plane_idx % 2 == 0 {
+                clip_plane.sign = -1.0;
+                clip_plane.axis = (clip_plane.axis + 1) % 3;
+                |w: f32, x: f32| -w <= x
+            } else {
+                clip_plane.sign = 1.0;
+                |w: f32, x: f32| x <= w
+            };
+
+            let v0_inside = inside_clip_plane(v0.0[3], v0.0[clip_plane.axis]);
+            let v1_inside = inside_clip_plane(v1.0[3], v1.0[clip_plane.axis]);
+            match (v0_inside, v1_inside) {
+                (true, true) => {}
+                (false, false) => return None,
+                (true, false) => v1 = self.compute_clipping_intersection(&v0, &v1, &clip_plane),
+                (false, true) => v0 = self.compute_clipping_intersection(&v1, &v0, &clip_plane),
+            }
+        }
+
+        Some((v0, v1))
+    }
+
     /// Computes the interpolated intersection point between a line segment and a clipping plane
     ///
     /// If the given line segment does not actually intersect the plane, the vertex returned will be
@@ -302,52 +811,39 @@ impl Renderer {
                 - (plane.sign * from_pos[3] - from_pos[plane.axis]));
         // Find the clip space position where the line segment intersects with the plane
         let intersect_pos = to_pos.lerp(*from_pos, interp_val);
-        // Perform a interpolation of the two vertices' attributes by using the line segment's
-        // barycentric coordinates
-        let intersect_attribs = self.tri_barycentric_interpolate_edge(
-            from_pos.xy(),
-            to_pos.xy(),
-            intersect_pos.xy(),
-            from_attrib,
-            to_attrib,
-        );
+
+        // Attributes vary linearly in clip space, just like the position itself, so they must be
+        // interpolated with exactly the same parameter as the position was.
+        let perspective = Vec2::new(interp_val, 1.0 - interp_val);
+        // Noperspective attributes instead vary linearly in screen space. After the perspective divide,
+        // each endpoint's contribution to the intersection is weighted by its w.
+        let mut linear = perspective * Vec2::new(from_pos.w, to_pos.w);
+        linear /= linear.x + linear.y;
+        if !linear.is_finite() {
+            linear = perspective;
+        }
+        let intersect_attribs =
+            from_attrib.line_interpolated_qualified(perspective, linear, to_attrib, from_attrib);
 
         (intersect_pos, intersect_attribs)
     }
 
-    /// Interpolates vertex attributes of a line using barycentric coordinates
-    ///
-    /// # Arguments
-    ///
-    /// * from - The start vertex of the line segment, in clip space, compressed to two dimensions
-    /// * to - The end vertex of the line segment, in clip space, compressed to two dimensions
-    /// * point - The clip space position to find barycentric coordinates for
-    /// * attrib1 - The vertex attributes belonging to the start of the line segment
-    /// * attrib2 - The vertex attributes belonging to the end of the line segment
+    /// Computes the pixels that a triangle may cover, clamped to the edges of the viewport
     ///
-    /// # Returns
-    ///
-    /// A new set of interpolated vertex attributess
-    fn tri_barycentric_interpolate_edge<VI: Barycentric>(
-        &self,
-        from: Vec2,
-        to: Vec2,
-        point: Vec2,
-        attrib1: &VI,
-        attrib2: &VI,
-    ) -> VI {
-        let barycentric_y = from.inverse_lerp(to, point);
-        let mut barycentric_coords = Vec2::new(1.0 - barycentric_y, barycentric_y);
-        barycentric_coords = barycentric_coords.clamp(Vec2::ZERO, Vec2::new(1.0, 1.0));
-        attrib1.line_interpolated(barycentric_coords, attrib2)
-    }
+    /// Since the box never extends past the viewport, every pixel inside it can be accessed without
+    /// bounds checks. If the triangle lies entirely outside the viewport, the box will have a
+    /// negative size.
+    fn tri_bounding_box(&self, p0: Vec2, p1: Vec2, p2: Vec2) -> BoundingBox2D {
+        let min_col = self.viewport.x as i32;
+        let min_row = self.viewport.y as i32;
+        let max_col = (self.viewport.x + self.viewport.width) as i32 - 1;
+        let max_row = (self.viewport.y + self.viewport.height) as i32 - 1;
 
-    fn tri_bounding_box(&self, p0: IVec2, p1: IVec2, p2: IVec2) -> BoundingBox2D {
-        let min_x = p0.x.min(p1.x.min(p2.x));
-        let max_x = p0.x.max(p1.x.max(p2.x));
+        let min_x = (p0.x.min(p1.x.min(p2.x)).floor() as i32).max(min_col);
+        let max_x = (p0.x.max(p1.x.max(p2.x)).ceil() as i32).min(max_col);
 
-        let min_y = p0.y.min(p1.y.min(p2.y));
-        let max_y = p0.y.max(p1.y.max(p2.y));
+        let min_y = (p0.y.min(p1.y.min(p2.y)).floor() as i32).max(min_row);
+        let max_y = (p0.y.max(p1.y.max(p2.y)).ceil() as i32).min(max_row);
 
         BoundingBox2D {
             origin: IVec2 { x: min_x, y: min_y },
@@ -362,6 +858,7 @@ impl Renderer {
         (p1 - p0).perp_dot(p2 - p0)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn plot_triangle<S: Shader<V, VI>, V, VI: Barycentric>(
         &mut self,
         p0: Vec2,
@@ -370,10 +867,12 @@ impl Renderer {
         clip_pos: &[Vec4; 3],
         program: &mut S,
         program_inputs: &[VI; 3],
+        provoking: &VI,
+        edges: [bool; 3],
     ) {
         let area = self.tri_area_signed_squared(p0, p1, p2);
 
-        let bb = self.tri_bounding_box(p0.as_ivec2(), p1.as_ivec2(), p2.as_ivec2());
+        let bb = self.tri_bounding_box(p0, p1, p2);
 
         // Determine the starting non-normalized barycentric values of our pixel iterations, in this case,
         // the bottom left corner of the triangle's bounding box
@@ -397,6 +896,15 @@ impl Renderer {
             1.0 / clip_pos[2].w,
         );
 
+        // Each edge function is the distance from the pixel to that edge, scaled by the edge's length.
+        // Dividing the length back out gives us the distance in pixels, which is used to outline edges.
+        let edge_distance_scale = Vec3::new(
+            1.0 / (p1 - p0).length(),
+            1.0 / (p2 - p1).length(),
+            1.0 / (p0 - p2).length(),
+        );
+        let outlined_edges = BVec3::new(edges[0], edges[1], edges[2]);
+
         for y in bb.origin.y..=bb.origin.y + bb.height {
             // Save the result of our edge function at the start of every row
             // for when we need to increment up a column
@@ -404,10 +912,6 @@ impl Renderer {
             let saved_efb = efb;
             let saved_efc = efc;
             for x in bb.origin.x..=bb.origin.x + bb.width {
-                // TODO: Consider guarding attempts to access memory outside the screen
-                // Currently, this should never happen due to clipping, but if we choose
-                // to use guard-band clipping it may become necessary.
-
                 // Geometrically, we attempt to divide our primitive into three "subtriangles" all converging
                 // at a given pixel. If all three subtriangles have a counter-clockwise winding order,
                 // then the areas of all three triangles will be positive and this means the pixel lies
@@ -419,11 +923,13 @@ impl Renderer {
 
                     // Convert screen barycentric coords to worldspace for perspective correction
                     let mut barycentric_worldspace = barycentric_coords * clip_w_inv;
-                    // Again, Barycentric coordinates need to sum to 1
-                    barycentric_worldspace = barycentric_worldspace
+                    // The weights sum to 1 / w, perspective correctly interpolated
+                    let clip_w = 1.0
                         / (barycentric_worldspace.x
                             + barycentric_worldspace.y
                             + barycentric_worldspace.z);
+                    // Again, Barycentric coordinates need to sum to 1
+                    barycentric_worldspace *= clip_w;
 
                     // Calculate this triangle's z depth at this fragment via barycentric coordinates
                     // The perspective divide has already occured on these z values, which should
@@ -435,22 +941,73 @@ impl Renderer {
                         &clip_pos[2].z,
                     );
 
-                    // TODO: Consider early-z discard
-
-                    // Run fragment shader
-                    let interpolated = program_inputs[0].interpolated(
-                        barycentric_worldspace,
-                        &program_inputs[1],
-                        &program_inputs[2],
-                    );
-                    let frag_output = program.fragment(interpolated);
-                    let fb_color = frag_output.z | (frag_output.y << 8) | (frag_output.x << 16);
-
                     // We only update the buffers if the z test determines that this primitive is closer
-                    // than any other primitive we have processed so far.
-                    if z_depth < self.db.get_pixel(x as u16, y as u16) {
-                        self.db.plot_pixel(x as u16, y as u16, z_depth);
-                        self.cb.plot_pixel(x as u16, y as u16, fb_color);
+                    // than any other primitive we have processed so far. Testing before running the
+                    // fragment shader saves shading fragments that would be discarded anyway.
+                    // SAFETY: The bounding box is clamped to the viewport, so x and y are always within
+                    // the bounds of both framebuffers.
+                    if z_depth < unsafe { self.db.get_pixel_unchecked(x as u32, y as u32) } {
+                        let interpolated = || {
+                            program_inputs[0].interpolated_qualified(
+                                barycentric_worldspace,
+                                barycentric_coords,
+                                &program_inputs[1],
+                                &program_inputs[2],
+                                provoking,
+                            )
+                        };
+                        let fb_color = match self.draw_mode {
+                            DrawMode::DEPTH_ONLY => None,
+                            DrawMode::SHADED_WIREFRAME | DrawMode::HIDDEN_LINE => {
+                                // Edges that should not be outlined are treated as being infinitely far away
+                                let edge_distance = Vec3::select(
+                                    outlined_edges,
+                                    Vec3::new(efa, efb, efc) * edge_distance_scale,
+                                    Vec3::splat(f32::INFINITY),
+                                )
+                                .min_element();
+                                let coverage = self.edge_coverage(edge_distance);
+                                let (fill, edge) = match self.draw_mode {
+                                    DrawMode::SHADED_WIREFRAME => (
+                                        self.fogged(
+                                            program.fragment(interpolated()),
+                                            x,
+                                            y,
+                                            z_depth,
+                                            clip_w,
+                                        ),
+                                        self.wireframe_color,
+                                    ),
+                                    _ if coverage > 0.0 => (
+                                        self.clear_color,
+                                        self.fogged(
+                                            program.fragment(interpolated()),
+                                            x,
+                                            y,
+                                            z_depth,
+                                            clip_w,
+                                        ),
+                                    ),
+                                    _ => (self.clear_color, self.clear_color),
+                                };
+                                Some(blend_color(fill, edge, coverage))
+                            }
+                            _ => Some(self.fogged(
+                                program.fragment(interpolated()),
+                                x,
+                                y,
+                                z_depth,
+                                clip_w,
+                            )),
+                        };
+
+                        // SAFETY: As above, x and y lie within the clamped bounding box.
+                        unsafe {
+                            self.db.plot_pixel_unchecked(x as u32, y as u32, z_depth);
+                            if let Some(fb_color) = fb_color {
+                                self.cb.plot_pixel_unchecked(x as u32, y as u32, fb_color);
+                            }
+                        }
                     }
                 }
                 efa += dya;
@@ -466,80 +1023,363 @@ impl Renderer {
         }
     }
 
-    fn plot_line<S: Shader<V, VI>, V, VI: Barycentric>(
+    /// Whether fragments are shaded and written to the color buffer, or only their depth is kept
+    fn writes_color(&self) -> bool {
+        !matches!(self.draw_mode, DrawMode::DEPTH_ONLY)
+    }
+
+    /// Packs a fragment shader's output for the color buffer, blending it into the fog if enabled
+    ///
+    /// `clip_w` is the fragment's interpolated clip space w, which is used as its view depth.
+    fn fogged(&self, color: UVec3, x: i32, y: i32, z_depth: f32, clip_w: f32) -> u32 {
+        let Some(state) = &self.fog else {
+            return pack_color(color);
+        };
+        // Undo the viewport transform to get back to normalized device coordinates
+        let max_pixel = Vec2::new(
+            (self.viewport.width as f32 - 1.0).max(1.0),
+            (self.viewport.height as f32 - 1.0).max(1.0),
+        );
+        let pixel = Vec2::new(
+            (x - self.viewport.x as i32) as f32,
+            (y - self.viewport.y as i32) as f32,
+        );
+        let ndc = (pixel / max_pixel * 2.0 - 1.0).extend(z_depth);
+        let world_position = state.world_from_clip.project_point3(ndc);
+        let amount = state
+            .fog
+            .amount(clip_w, state.camera_position, world_position);
+        let shaded = color.as_vec3() / 255.0;
+        pack_color(to_output_color(state.fog.apply(shaded, amount)))
+    }
+
+    /// Computes how much of a pixel is covered by a triangle's outline, given the distance in pixels
+    /// from the pixel to the closest edge of the triangle
+    ///
+    /// Only the half of the outline lying inside the triangle is drawn, the other half belongs to the
+    /// neighbouring triangle sharing that edge. Outlines follow the renderer's line width and
+    /// anti-aliasing state.
+    fn edge_coverage(&self, edge_distance: f32) -> f32 {
+        let half_width = self.line_width / 2.0;
+        if self.line_antialiasing {
+            (half_width + 0.5 - edge_distance).clamp(0.0, 1.0)
+        } else if edge_distance < half_width {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Rasterizes a line segment, picking the rasterizer appropriate for the current line state
+    ///
+    /// Thin, aliased lines are stepped pixel-by-pixel along their driving axis, while wide or
+    /// anti-aliased lines are rasterized by computing how much of each pixel they cover.
+    fn rasterize_line<S: Shader<V, VI>, V, VI: Barycentric>(
         &mut self,
-        mut p1: Vec2,
-        mut p2: Vec2,
+        p1: Vec4,
+        p2: Vec4,
         program: &S,
         p1_input: &VI,
         p2_input: &VI,
+        provoking: &VI,
     ) {
-        // TODO: This line algorithm doesn't seem to handle subpixel precision correctly.
-        // Skip points
-        if p1 == p2 {
-            // TODO: What should we really do to handle this?
-            return;
+        if self.line_width > 1.0 || self.line_antialiasing {
+            self.plot_wide_line(p1, p2, program, p1_input, p2_input, provoking);
+        } else {
+            self.plot_line(p1, p2, program, p1_input, p2_input, provoking);
         }
+    }
 
+    /// Rasterizes a single line segment into the color and depth buffers
+    ///
+    /// The line is stepped one pixel at a time along its driving axis, sampling at pixel centers, which
+    /// lie on integer screen-space coordinates. Depth is interpolated linearly in screen space (the
+    /// perspective divide has already occured), while vertex attributes are interpolated
+    /// perspective-correctly using the clip-space w of each endpoint. Fragments are only shaded and
+    /// written if they pass the depth test. A line whose endpoints round to the same pixel is
+    /// rasterized as a single fragment using the attributes of its first endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * p1 - The screen-space x and y of the start of the line, with its ndc depth in z and its
+    ///   clip-space w in w
+    /// * p2 - The screen-space x and y of the end of the line, with its ndc depth in z and its
+    ///   clip-space w in w
+    /// * program - The shader program to run for every fragment of the line
+    /// * p1_input - The vertex attributes belonging to the start of the line
+    /// * p2_input - The vertex attributes belonging to the end of the line
+    /// * provoking - The vertex attributes supplying the value of flat attributes
+    fn plot_line<'a, S: Shader<V, VI>, V, VI: Barycentric>(
+        &mut self,
+        mut p1: Vec4,
+        mut p2: Vec4,
+        program: &S,
+        mut p1_input: &'a VI,
+        mut p2_input: &'a VI,
+        provoking: &VI,
+    ) {
         // Determine the "Driving Axis", that is, whether the line is more vertical or horizontal
         // If driving axis is Y-axis, we need to flip so that we are iterating 1 per row instead of column
         let y_long = (p1.y - p2.y).abs() > (p1.x - p2.x).abs();
         if y_long {
-            p1 = p1.yx();
-            p2 = p2.yx();
+            p1 = p1.yxzw();
+            p2 = p2.yxzw();
         }
 
-        // Save a copy of the original points before we potentially swap them, so that
-        // barycentric coordinates work correctly.
-        let p1_orig = p1;
-        let p2_orig = p2;
-
         // The X-coordinate in our points now acts as the coordinate of the driving axis, regardless of what
         // axis it is in ndc. We need to ensure p1 always comes "before" p2 on the driving axis, to ensure
-        // our for loop runs independently of ordering of the two points, so we re-order the points if
-        // necessary.
+        // our for loop runs independently of ordering of the two points, so we re-order the points
+        // (and their attributes) if necessary.
         if p1.x > p2.x {
             std::mem::swap(&mut p1, &mut p2);
+            std::mem::swap(&mut p1_input, &mut p2_input);
         }
 
         let dx = p2.x - p1.x;
         let dy = p2.y - p1.y;
-        let dy_abs = dy.abs();
-        let mut eps = dy_abs - dx.abs();
-        let mut y = p1.y;
-
-        // Whether we increment or decrement the screen-space y coordinate depends on the sign of
-        // the line's dy. This is checked ahead of time to avoid an additional branch in the for loop.
-        let sign = if dy >= 0.0 { 1.0 } else { -1.0 };
-
-        for x in p1.x as i32..p2.x as i32 {
-            // Barycentric coordinates for a line: treat it like an edge on a triangle
-            // Basically, we just lerp between x and y, and set z to 0
-            let pixel = IVec2::new(x, y as i32);
-            let interpolated = self.tri_barycentric_interpolate_edge(
-                p1_orig,
-                p2_orig,
-                pixel.as_vec2(),
-                p1_input,
-                p2_input,
-            );
 
-            let frag_output = program.fragment(interpolated);
-            let fb_color = frag_output.z | (frag_output.y << 8) | (frag_output.x << 16);
-            if y_long {
+        // Pixel centers lie on integer coordinates, so we only visit the pixels whose centers are
+        // closest to the start and end of the line, rather than truncating the endpoints.
+        let start = p1.x.round() as i32;
+        let end = p2.x.round() as i32;
+
+        for x in start..=end {
+            // How far along the line this pixel center is, in screen space. Degenerate lines have no
+            // length along the driving axis, and collapse to the first endpoint.
+            let t = if dx > 0.0 {
+                ((x as f32 - p1.x) / dx).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let y = (p1.y + dy * t).round() as i32;
+
+            // Ndc depth is affine in screen space, so it can be interpolated directly.
+            let z_depth = p1.z + (p2.z - p1.z) * t;
+            let (screen_x, screen_y) = if y_long {
                 // Swap back to screen-space
-                self.cb.plot_pixel(y as u16, x as u16, fb_color);
+                (y, x)
             } else {
                 // x and y are already in screen-space
-                self.cb.plot_pixel(x as u16, y as u16, fb_color);
+                (x, y)
+            };
+            // Clipping keeps lines inside the viewport, but rounding may still nudge an endpoint
+            // just past the edge, so such pixels are skipped rather than trusted.
+            let (Ok(screen_x), Ok(screen_y)) = (u32::try_from(screen_x), u32::try_from(screen_y))
+            else {
+                continue;
+            };
+            if !self.viewport.contains(screen_x, screen_y) {
+                continue;
+            }
+            let Ok(depth) = self.db.try_get_pixel(screen_x, screen_y) else {
+                continue;
+            };
+            if z_depth >= depth {
+                continue;
+            }
+            if !self.writes_color() {
+                // SAFETY: The depth buffer access above succeeded.
+                unsafe { self.db.plot_pixel_unchecked(screen_x, screen_y, z_depth) };
+                continue;
+            }
+
+            // Barycentric coordinates for a line: treat it like an edge on a triangle, and
+            // convert the screen-space weights to worldspace for perspective correction, exactly
+            // as we do for triangles.
+            let mut barycentric_worldspace = Vec2::new((1.0 - t) / p1.w, t / p2.w);
+            let clip_w = 1.0 / (barycentric_worldspace.x + barycentric_worldspace.y);
+            barycentric_worldspace *= clip_w;
+            let interpolated = p1_input.line_interpolated_qualified(
+                barycentric_worldspace,
+                Vec2::new(1.0 - t, t),
+                p2_input,
+                provoking,
+            );
+
+            let frag_output = program.fragment(interpolated);
+            let fb_color = self.fogged(
+                frag_output,
+                screen_x as i32,
+                screen_y as i32,
+                z_depth,
+                clip_w,
+            );
+            // SAFETY: The depth buffer access above succeeded, and both buffers share dimensions.
+            unsafe {
+                self.db.plot_pixel_unchecked(screen_x, screen_y, z_depth);
+                self.cb.plot_pixel_unchecked(screen_x, screen_y, fb_color);
+            }
+        }
+    }
+
+    /// Rasterizes a line segment of arbitrary width, optionally anti-aliased
+    ///
+    /// The line is treated as a capsule: every pixel whose center lies within half the line width of
+    /// the segment is covered. For anti-aliased lines, coverage instead falls off linearly over the
+    /// pixel straddling the edge of the capsule, and the fragment's color is blended with the color
+    /// buffer by that coverage. Only fragments covering at least half a pixel write to the depth buffer,
+    /// so that the faint edges of a line do not hide geometry drawn after it.
+    ///
+    /// # Arguments
+    ///
+    /// * p1 - The screen-space x and y of the start of the line, with its ndc depth in z and its
+    ///   clip-space w in w
+    /// * p2 - The screen-space x and y of the end of the line, with its ndc depth in z and its
+    ///   clip-space w in w
+    /// * program - The shader program to run for every fragment of the line
+    /// * p1_input - The vertex attributes belonging to the start of the line
+    /// * p2_input - The vertex attributes belonging to the end of the line
+    /// * provoking - The vertex attributes supplying the value of flat attributes
+    fn plot_wide_line<S: Shader<V, VI>, V, VI: Barycentric>(
+        &mut self,
+        p1: Vec4,
+        p2: Vec4,
+        program: &S,
+        p1_input: &VI,
+        p2_input: &VI,
+        provoking: &VI,
+    ) {
+        let half_width = self.line_width.max(1.0) / 2.0;
+        // Anti-aliased lines fade out over an extra half pixel on either side
+        let reach = if self.line_antialiasing {
+            half_width + 0.5
+        } else {
+            half_width
+        };
+
+        let start = p1.xy();
+        let dir = p2.xy() - start;
+        let length_squared = dir.length_squared();
+
+        // Only visit the pixels within reach of the line, clamped to the edges of the viewport
+        let viewport_min = Vec2::new(self.viewport.x as f32, self.viewport.y as f32);
+        let min = (start.min(p2.xy()) - reach).floor().max(viewport_min);
+        let max = (start.max(p2.xy()) + reach)
+            .ceil()
+            .min(self.viewport.max_pixel());
+
+        for y in min.y as i32..=max.y as i32 {
+            for x in min.x as i32..=max.x as i32 {
+                let pixel = Vec2::new(x as f32, y as f32);
+                // Find the closest point on the segment to this pixel center. Degenerate lines
+                // collapse to their first endpoint, and are drawn as a round dot.
+                let t = if length_squared > 0.0 {
+                    ((pixel - start).dot(dir) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = pixel.distance(start + dir * t);
+
+                let coverage = if self.line_antialiasing {
+                    (reach - distance).clamp(0.0, 1.0)
+                } else if distance <= half_width {
+                    1.0
+                } else {
+                    0.0
+                };
+                if coverage <= 0.0 {
+                    continue;
+                }
+
+                let z_depth = p1.z + (p2.z - p1.z) * t;
+                // SAFETY: The pixels visited are clamped to the viewport, so x and y are always within
+                // the bounds of both framebuffers.
+                if z_depth >= unsafe { self.db.get_pixel_unchecked(x as u32, y as u32) } {
+                    continue;
+                }
+                if !self.writes_color() {
+                    if coverage >= 0.5 {
+                        // SAFETY: As above
+                        unsafe { self.db.plot_pixel_unchecked(x as u32, y as u32, z_depth) };
+                    }
+                    continue;
+                }
+
+                let mut barycentric_worldspace = Vec2::new((1.0 - t) / p1.w, t / p2.w);
+                let clip_w = 1.0 / (barycentric_worldspace.x + barycentric_worldspace.y);
+                barycentric_worldspace *= clip_w;
+                let interpolated = p1_input.line_interpolated_qualified(
+                    barycentric_worldspace,
+                    Vec2::new(1.0 - t, t),
+                    p2_input,
+                    provoking,
+                );
+
+                let frag_output = program.fragment(interpolated);
+                let mut fb_color = self.fogged(frag_output, x, y, z_depth, clip_w);
+                if coverage < 1.0 {
+                    // SAFETY: As above
+                    let dst = unsafe { self.cb.get_pixel_unchecked(x as u32, y as u32) };
+                    fb_color = blend_color(dst, fb_color, coverage);
+                }
+                // SAFETY: As above
+                unsafe {
+                    if coverage >= 0.5 {
+                        self.db.plot_pixel_unchecked(x as u32, y as u32, z_depth);
+                    }
+                    self.cb.plot_pixel_unchecked(x as u32, y as u32, fb_color);
+                }
             }
+        }
+    }
 
-            if eps >= 0.0 {
-                y += sign;
+    /// Rasterizes a single point as a screen-aligned square
+    ///
+    /// Every pixel whose center lies within the square is covered, and receives the same depth and
+    /// vertex attributes. The fragment shader is additionally given the pixel's position within the
+    /// square, ranging from (0, 0) at its bottom left to (1, 1) at its top right, so that it may shade
+    /// the point as a sprite. Discarded fragments leave both buffers untouched, except in
+    /// `DrawMode::DEPTH_ONLY`, where no fragments are shaded and the whole square writes its depth.
+    ///
+    /// # Arguments
+    ///
+    /// * center - The screen-space position of the center of the point
+    /// * z_depth - The ndc depth of the point
+    /// * clip_w - The clip space w of the point
+    /// * size - The width of the point, in pixels
+    /// * program - The shader program to run for every fragment of the point
+    /// * input - The vertex attributes belonging to the point
+    fn plot_point<S: Shader<V, VI>, V, VI: Barycentric + Clone>(
+        &mut self,
+        center: Vec2,
+        z_depth: f32,
+        clip_w: f32,
+        size: f32,
+        program: &S,
+        input: VI,
+    ) {
+        let origin = center - size / 2.0;
+        // Pixels are covered if their center lies within [origin, origin + size), which gives
+        // exactly size pixels along each axis for integer sizes.
+        let viewport_min = Vec2::new(self.viewport.x as f32, self.viewport.y as f32);
+        let min = origin.ceil().max(viewport_min);
+        let max = ((origin + size).ceil() - 1.0).min(self.viewport.max_pixel());
 
-                eps -= dx;
+        for y in min.y as i32..=max.y as i32 {
+            for x in min.x as i32..=max.x as i32 {
+                // SAFETY: The pixels visited are clamped to the viewport, so x and y are always within
+                // the bounds of both framebuffers.
+                if z_depth >= unsafe { self.db.get_pixel_unchecked(x as u32, y as u32) } {
+                    continue;
+                }
+                if !self.writes_color() {
+                    // SAFETY: As above
+                    unsafe { self.db.plot_pixel_unchecked(x as u32, y as u32, z_depth) };
+                    continue;
+                }
+
+                let point_coord = (Vec2::new(x as f32, y as f32) - origin) / size;
+                let Some(frag_output) = program.point_fragment(input.clone(), point_coord) else {
+                    // The shader discarded this fragment
+                    continue;
+                };
+                let fb_color = self.fogged(frag_output, x, y, z_depth, clip_w);
+                // SAFETY: As above
+                unsafe {
+                    self.db.plot_pixel_unchecked(x as u32, y as u32, z_depth);
+                    self.cb.plot_pixel_unchecked(x as u32, y as u32, fb_color);
+                }
             }
-            eps += dy_abs;
         }
     }
 }
@@ -0,0 +1,436 @@
+//! A lightweight scene graph, of nodes holding meshes, cameras and lights.
+//!
+//! Each node has a transform relative to its parent, and a world transform combining those of every
+//! ancestor, which `Scene::update_transforms` recomputes after nodes are moved. Like a `Camera`, nodes
+//! face down their local -Z axis, which is the direction that directional and spot lights shine in.
+//! `Renderer::render_scene` draws every mesh in the scene from a camera, culling meshes outside the
+//! view and sorting the rest.
+
+use std::ops::Range;
+
+use glam::{Affine3A, Mat3, Mat4, Vec3};
+
+use crate::{
+    camera::Camera,
+    error::Result,
+    math::{Aabb, BoundingVolume, Frustum},
+    mesh::{Material, Mesh, MeshVertex},
+    renderer::Renderer,
+    shader::{Barycentric, Shader},
+};
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Shines along the node's forward axis from infinitely far away
+    DIRECTIONAL,
+    /// Shines in every direction from the node's position, fading to nothing at `range`
+    POINT { range: f32 },
+    /// A point light restricted to a cone around the node's forward axis. The light fades out between
+    /// the inner and outer half-angles of the cone, in radians.
+    SPOT {
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Linear color of the light, which is scaled by `intensity`
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn directional(color: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::DIRECTIONAL,
+            color,
+            intensity,
+        }
+    }
+
+    pub fn point(color: Vec3, intensity: f32, range: f32) -> Self {
+        Light {
+            kind: LightKind::POINT { range },
+            color,
+            intensity,
+        }
+    }
+
+    pub fn spot(
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Light {
+            kind: LightKind::SPOT {
+                range,
+                inner_angle,
+                outer_angle,
+            },
+            color,
+            intensity,
+        }
+    }
+}
+
+/// A light placed in the world by a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightInstance {
+    pub light: Light,
+    pub position: Vec3,
+    /// Normalized direction that the light shines in
+    pub direction: Vec3,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeContent {
+    /// A node that only groups and transforms its children
+    EMPTY,
+    /// Draws a mesh, an index into `Scene::meshes`. If `material` is set, it is an index into
+    /// `Scene::materials` used for the whole mesh, instead of the materials of the mesh's groups.
+    MESH {
+        mesh: usize,
+        material: Option<usize>,
+    },
+    /// A camera, whose position and orientation are taken from the node. See `Scene::camera`.
+    CAMERA(Camera),
+    LIGHT(Light),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneNode {
+    pub name: Option<String>,
+    /// Transform relative to the parent node
+    pub local_transform: Affine3A,
+    /// Transform relative to the scene, combining the transforms of every ancestor. This is only
+    /// updated by `Scene::update_transforms`.
+    pub world_transform: Affine3A,
+    pub content: NodeContent,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl SceneNode {
+    pub fn get_parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn get_children(&self) -> &[usize] {
+        &self.children
+    }
+}
+
+/// A hierarchy of nodes, and the meshes and materials that they refer to
+///
+/// Nodes are created with `add_node` and referred to by index, which stays valid for the lifetime of
+/// the scene.
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    nodes: Vec<SceneNode>,
+    lights: Vec<LightInstance>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    /// Adds a mesh for nodes to refer to, returning its index
+    pub fn add_mesh(&mut self, mesh: Mesh) -> usize {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+
+    /// Adds a material for nodes to refer to, returning its index
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    /// Adds a node as a child of `parent`, or at the top level of the scene, returning its index. Its
+    /// world transform is computed straight away.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is not the index of a node.
+    pub fn add_node(
+        &mut self,
+        parent: Option<usize>,
+        local_transform: Affine3A,
+        content: NodeContent,
+    ) -> usize {
+        let index = self.nodes.len();
+        let parent_transform = match parent {
+            Some(parent) => {
+                let parent = &mut self.nodes[parent];
+                parent.children.push(index);
+                parent.world_transform
+            }
+            None => Affine3A::IDENTITY,
+        };
+        self.nodes.push(SceneNode {
+            name: None,
+            local_transform,
+            world_transform: parent_transform * local_transform,
+            content,
+            parent,
+            children: Vec::new(),
+        });
+        if matches!(self.nodes[index].content, NodeContent::LIGHT(_)) {
+            self.place_lights();
+        }
+        index
+    }
+
+    pub fn get_nodes(&self) -> &[SceneNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, index: usize) -> &SceneNode {
+        &self.nodes[index]
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut SceneNode {
+        &mut self.nodes[index]
+    }
+
+    /// Returns the index of the first node with the given name
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.name.as_deref() == Some(name))
+    }
+
+    /// The nodes at the top level of the scene, which have no parent
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&index| self.nodes[index].parent.is_none())
+    }
+
+    /// Moves a node and its descendants under a new parent, or to the top level of the scene. Call
+    /// `update_transforms` afterwards to recompute their world transforms.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is not a node, or if `parent` is the node itself or one of its
+    /// descendants.
+    pub fn set_parent(&mut self, node: usize, parent: Option<usize>) {
+        if let Some(parent) = parent {
+            let mut ancestor = Some(parent);
+            while let Some(index) = ancestor {
+                assert!(index != node, "node {node} cannot be its own ancestor");
+                ancestor = self.nodes[index].parent;
+            }
+        }
+        if let Some(old_parent) = self.nodes[node].parent {
+            self.nodes[old_parent]
+                .children
+                .retain(|&child| child != node);
+        }
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(node);
+        }
+        self.nodes[node].parent = parent;
+    }
+
+    /// Recomputes the world transform of every node from the local transforms, and places the lights
+    /// again
+    pub fn update_transforms(&mut self) {
+        // `set_parent` forbids cycles, so walking down from the roots visits each node once
+        let mut stack: Vec<(usize, Affine3A)> = self
+            .roots()
+            .map(|root| (root, Affine3A::IDENTITY))
+            .collect();
+        while let Some((index, parent_transform)) = stack.pop() {
+            let node = &mut self.nodes[index];
+            node.world_transform = parent_transform * node.local_transform;
+            let world = node.world_transform;
+            stack.extend(node.children.iter().map(|&child| (child, world)));
+        }
+        self.place_lights();
+    }
+
+    /// Returns the camera of a camera node, placed at the node's world position and orientation. Any
+    /// scale in the node's transform is ignored.
+    pub fn camera(&self, node: usize) -> Option<Camera> {
+        let node = self.nodes.get(node)?;
+        let NodeContent::CAMERA(camera) = &node.content else {
+            return None;
+        };
+        let (_, rotation, translation) = node.world_transform.to_scale_rotation_translation();
+        let mut camera = camera.clone();
+        camera.set_position(translation);
+        camera.set_orientation(rotation);
+        Some(camera)
+    }
+
+    /// Every light in the scene, placed at its node's world position and direction. Like world
+    /// transforms, these are only updated by `update_transforms` and `add_node`.
+    pub fn lights(&self) -> &[LightInstance] {
+        &self.lights
+    }
+
+    fn place_lights(&mut self) {
+        self.lights = self
+            .nodes
+            .iter()
+            .filter_map(|node| match node.content {
+                NodeContent::LIGHT(light) => Some(LightInstance {
+                    light,
+                    position: node.world_transform.translation.into(),
+                    direction: node
+                        .world_transform
+                        .transform_vector3(Vec3::NEG_Z)
+                        .normalize_or_zero(),
+                }),
+                _ => None,
+            })
+            .collect();
+    }
+}
+
+/// Everything needed to create the shader for one draw call of `Renderer::render_scene`
+pub struct DrawContext<'a> {
+    /// Index of the node being drawn
+    pub node: usize,
+    pub mesh: &'a Mesh,
+    /// The node's material, or else the material of the mesh's group being drawn, if it has one
+    pub material: Option<&'a Material>,
+    /// The node's world transform
+    pub model: Affine3A,
+    /// Transforms normals into world space
+    pub normal_matrix: Mat3,
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_proj: Mat4,
+    pub camera_position: Vec3,
+    pub lights: &'a [LightInstance],
+}
+
+/// A single draw call of `render_scene`, before it is sorted
+struct DrawItem<'a> {
+    node: usize,
+    mesh: &'a Mesh,
+    bounds: Aabb,
+    indices: Range<usize>,
+    material: Option<&'a Material>,
+    /// Distance in front of the camera, along its forward axis, of the center of the bounds
+    depth: f32,
+}
+
+impl Renderer {
+    /// Draws every mesh node of a scene from the point of view of `camera`
+    ///
+    /// `make_shader` is called once per draw call to create its shader, usually from the matrices,
+    /// material and lights in the `DrawContext`. Each material group of a mesh is a separate draw
+    /// call, unless the node sets its own material. Meshes outside the camera's view are culled, and
+    /// counted in `get_cull_stats`, without creating their shaders. Draws are made from front to back,
+    /// so that hidden surfaces fail the depth test early. The renderer has no blending, so materials are
+    /// drawn as opaque whatever their opacity.
+    ///
+    /// World transforms are not recomputed, so call `Scene::update_transforms` after moving nodes.
+    pub fn render_scene<'s, S, VI, F>(
+        &mut self,
+        scene: &'s Scene,
+        camera: &Camera,
+        mut make_shader: F,
+    ) -> Result<()>
+    where
+        S: Shader<MeshVertex, VI>,
+        VI: Barycentric + Clone,
+        F: FnMut(&DrawContext<'s>) -> S,
+    {
+        let view = camera.view_matrix();
+        let projection = camera.projection_matrix();
+        let view_proj = projection * view;
+
+        let mut mesh_bounds: Vec<Option<Option<Aabb>>> = vec![None; scene.meshes.len()];
+        let mut items = Vec::new();
+        for (index, node) in scene.nodes.iter().enumerate() {
+            let NodeContent::MESH { mesh, material } = node.content else {
+                continue;
+            };
+            let Some(mesh_ref) = scene.meshes.get(mesh) else {
+                continue;
+            };
+            let Some(bounds) = *mesh_bounds[mesh].get_or_insert_with(|| mesh_ref.bounds()) else {
+                continue;
+            };
+            let center =
+                view.transform_point3(node.world_transform.transform_point3(bounds.center()));
+            let item = |indices, material| DrawItem {
+                node: index,
+                mesh: mesh_ref,
+                bounds,
+                indices,
+                material,
+                depth: -center.z,
+            };
+
+            match material {
+                Some(material) => items.push(item(
+                    0..mesh_ref.indices.len(),
+                    scene.materials.get(material),
+                )),
+                None if mesh_ref.groups.is_empty() => {
+                    items.push(item(0..mesh_ref.indices.len(), None))
+                }
+                None => items.extend(mesh_ref.groups.iter().map(|group| {
+                    item(
+                        group.indices.clone(),
+                        group
+                            .material
+                            .and_then(|material| mesh_ref.materials.get(material)),
+                    )
+                })),
+            }
+        }
+
+        // Culling before sorting also avoids creating shaders that would never be used
+        items.retain(|item| {
+            let clip_from_object = view_proj * Mat4::from(scene.nodes[item.node].world_transform);
+            let visible = item
+                .bounds
+                .intersects_frustum(&Frustum::from_matrix(clip_from_object));
+            if !visible {
+                self.count_culled();
+            }
+            visible
+        });
+
+        items.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+
+        for item in items {
+            let model = scene.nodes[item.node].world_transform;
+            let context = DrawContext {
+                node: item.node,
+                mesh: item.mesh,
+                material: item.material,
+                model,
+                normal_matrix: Mat3::from(model.matrix3).inverse().transpose(),
+                view,
+                projection,
+                view_proj,
+                camera_position: camera.get_position(),
+                lights: scene.lights(),
+            };
+            let mut shader = make_shader(&context);
+            self.try_draw_culled(
+                &mut shader,
+                &item.mesh.vertices,
+                &item.mesh.indices[item.indices],
+                &item.bounds,
+                view_proj * Mat4::from(model),
+            )?;
+        }
+        Ok(())
+    }
+}
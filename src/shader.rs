@@ -1,13 +1,60 @@
-use glam::{UVec3, Vec2, Vec3, Vec4};
+use glam::{
+    Affine2, Affine3A, DAffine2, DAffine3, DMat2, DMat3, DMat4, DQuat, DVec2, DVec3, DVec4, Mat2,
+    Mat3, Mat3A, Mat4, Quat, UVec3, Vec2, Vec3, Vec3A, Vec4,
+};
 
 pub trait Shader<VertexIn, VertexOut> {
     fn vertex(&self, pos: &VertexIn) -> (Vec4, VertexOut);
     fn fragment(&self, interpolated: VertexOut) -> UVec3;
+
+    /// Optionally overrides the renderer's point size, in pixels, for a single vertex when drawing
+    /// points. Returning None uses the size set by `Renderer::set_point_size`.
+    fn point_size(&self, _vertex: &VertexIn) -> Option<f32> {
+        None
+    }
+
+    /// The fragment shader used when drawing points. `point_coord` is the fragment's position within
+    /// the point, from (0, 0) at its bottom left to (1, 1) at its top right, which allows a point to be
+    /// shaded as a sprite. Returning None discards the fragment, for example to draw round points.
+    /// By default, this simply runs the regular fragment shader.
+    fn point_fragment(&self, interpolated: VertexOut, _point_coord: Vec2) -> Option<UVec3> {
+        Some(self.fragment(interpolated))
+    }
 }
 
-pub trait Barycentric {
+pub trait Barycentric: Sized {
     fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self;
     fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self;
+
+    /// Interpolates across a triangle while honouring interpolation qualifiers
+    ///
+    /// The rasterizer provides both perspective-correct and screen-space (`noperspective`) barycentric
+    /// coordinates, as well as the attributes of the triangle's provoking vertex, which `flat`
+    /// attributes take their value from. Types without qualifiers only need the perspective-correct
+    /// coordinates, which is what the default implementation uses. `#[derive(Barycentric)]` overrides
+    /// this for any struct with `#[flat]` or `#[noperspective]` fields.
+    fn interpolated_qualified(
+        &self,
+        perspective: Vec3,
+        _linear: Vec3,
+        second: &Self,
+        third: &Self,
+        _provoking: &Self,
+    ) -> Self {
+        self.interpolated(perspective, second, third)
+    }
+
+    /// Interpolates along a line while honouring interpolation qualifiers. See
+    /// `interpolated_qualified` for details.
+    fn line_interpolated_qualified(
+        &self,
+        perspective: Vec2,
+        _linear: Vec2,
+        second: &Self,
+        _provoking: &Self,
+    ) -> Self {
+        self.line_interpolated(perspective, second)
+    }
 }
 
 impl Barycentric for f32 {
@@ -52,3 +99,233 @@ impl Barycentric for () {
     fn interpolated(&self, _coords: Vec3, _second: &Self, _third: &Self) -> Self {}
     fn line_interpolated(&self, _coords: Vec2, _second: &Self) -> Self {}
 }
+
+/// Implements Barycentric for single-precision types that support weighting by a scalar and addition
+macro_rules! impl_barycentric_f32 {
+    ($($ty:ty),*) => {$(
+        impl Barycentric for $ty {
+            fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self {
+                *self * coords.x + *second * coords.y + *third * coords.z
+            }
+            fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self {
+                *self * coords.x + *second * coords.y
+            }
+        }
+    )*};
+}
+
+impl_barycentric_f32!(Vec3A, Mat2, Mat3, Mat3A, Mat4);
+
+/// Implements Barycentric for double-precision types. The barycentric coordinates are widened to f64
+/// before weighting, so no precision is lost in the attribute itself.
+macro_rules! impl_barycentric_f64 {
+    ($($ty:ty),*) => {$(
+        impl Barycentric for $ty {
+            fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self {
+                let coords = coords.as_dvec3();
+                *self * coords.x + *second * coords.y + *third * coords.z
+            }
+            fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self {
+                let coords = coords.as_dvec2();
+                *self * coords.x + *second * coords.y
+            }
+        }
+    )*};
+}
+
+impl_barycentric_f64!(f64, DVec2, DVec3, DVec4, DMat2, DMat3, DMat4);
+
+/// Implements Barycentric for affine transforms by interpolating their linear part and translation
+/// separately. Note that the result is only a valid rotation if the inputs are close to each other,
+/// as with any component-wise interpolation of matrices.
+macro_rules! impl_barycentric_affine {
+    ($($ty:ident { $linear:ident, $translation:ident }),*) => {$(
+        impl Barycentric for $ty {
+            fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self {
+                $ty {
+                    $linear: self.$linear.interpolated(coords, &second.$linear, &third.$linear),
+                    $translation: self.$translation.interpolated(
+                        coords,
+                        &second.$translation,
+                        &third.$translation,
+                    ),
+                }
+            }
+            fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self {
+                $ty {
+                    $linear: self.$linear.line_interpolated(coords, &second.$linear),
+                    $translation: self.$translation.line_interpolated(coords, &second.$translation),
+                }
+            }
+        }
+    )*};
+}
+
+impl_barycentric_affine!(
+    Affine2 {
+        matrix2,
+        translation
+    },
+    Affine3A {
+        matrix3,
+        translation
+    },
+    DAffine2 {
+        matrix2,
+        translation
+    },
+    DAffine3 {
+        matrix3,
+        translation
+    }
+);
+
+/// Implements Barycentric for quaternions using normalized linear interpolation (nlerp). Each
+/// quaternion is first flipped into the same hemisphere as the first one, so that the interpolation
+/// always takes the shortest path between rotations.
+macro_rules! impl_barycentric_quat {
+    ($($ty:ty => $coords3:expr, $coords2:expr),*) => {$(
+        impl Barycentric for $ty {
+            fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self {
+                let coords = $coords3(&coords);
+                let second = if self.dot(*second) < 0.0 { -*second } else { *second };
+                let third = if self.dot(*third) < 0.0 { -*third } else { *third };
+                (*self * coords.x + second * coords.y + third * coords.z).normalize()
+            }
+            fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self {
+                let coords = $coords2(&coords);
+                let second = if self.dot(*second) < 0.0 { -*second } else { *second };
+                (*self * coords.x + second * coords.y).normalize()
+            }
+        }
+    )*};
+}
+
+impl_barycentric_quat!(
+    Quat => |coords: &Vec3| *coords, |coords: &Vec2| *coords,
+    DQuat => Vec3::as_dvec3, Vec2::as_dvec2
+);
+
+/// Implements Barycentric for integer types. The interpolation itself is performed in f64, and the
+/// result is rounded to the nearest integer, with halfway cases rounded away from zero. Results
+/// outside the range of the type saturate at its minimum or maximum value, although this can only
+/// happen through extrapolation, as barycentric coordinates inside a primitive never leave the range
+/// of the values being interpolated.
+macro_rules! impl_barycentric_int {
+    ($($ty:ty),*) => {$(
+        impl Barycentric for $ty {
+            fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self {
+                let coords = coords.as_dvec3();
+                (*self as f64 * coords.x + *second as f64 * coords.y + *third as f64 * coords.z)
+                    .round() as $ty
+            }
+            fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self {
+                let coords = coords.as_dvec2();
+                (*self as f64 * coords.x + *second as f64 * coords.y).round() as $ty
+            }
+        }
+    )*};
+}
+
+impl_barycentric_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// Arrays are interpolated element-wise, which allows attributes such as skinning weights
+/// (`[f32; 4]`) or 8-bit colors (`[u8; 4]`) to be used directly.
+impl<T: Barycentric, const N: usize> Barycentric for [T; N] {
+    fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self {
+        std::array::from_fn(|i| self[i].interpolated(coords, &second[i], &third[i]))
+    }
+
+    fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self {
+        std::array::from_fn(|i| self[i].line_interpolated(coords, &second[i]))
+    }
+
+    fn interpolated_qualified(
+        &self,
+        perspective: Vec3,
+        linear: Vec3,
+        second: &Self,
+        third: &Self,
+        provoking: &Self,
+    ) -> Self {
+        std::array::from_fn(|i| {
+            self[i].interpolated_qualified(
+                perspective,
+                linear,
+                &second[i],
+                &third[i],
+                &provoking[i],
+            )
+        })
+    }
+
+    fn line_interpolated_qualified(
+        &self,
+        perspective: Vec2,
+        linear: Vec2,
+        second: &Self,
+        provoking: &Self,
+    ) -> Self {
+        std::array::from_fn(|i| {
+            self[i].line_interpolated_qualified(perspective, linear, &second[i], &provoking[i])
+        })
+    }
+}
+
+/// Optional attributes are only interpolated if every vertex provides them. If any of the vertices
+/// is None, the result is None as well.
+impl<T: Barycentric> Barycentric for Option<T> {
+    fn interpolated(&self, coords: Vec3, second: &Self, third: &Self) -> Self {
+        match (self, second, third) {
+            (Some(first), Some(second), Some(third)) => {
+                Some(first.interpolated(coords, second, third))
+            }
+            _ => None,
+        }
+    }
+
+    fn line_interpolated(&self, coords: Vec2, second: &Self) -> Self {
+        match (self, second) {
+            (Some(first), Some(second)) => Some(first.line_interpolated(coords, second)),
+            _ => None,
+        }
+    }
+
+    fn interpolated_qualified(
+        &self,
+        perspective: Vec3,
+        linear: Vec3,
+        second: &Self,
+        third: &Self,
+        provoking: &Self,
+    ) -> Self {
+        match (self, second, third) {
+            (Some(first), Some(second), Some(third)) => Some(first.interpolated_qualified(
+                perspective,
+                linear,
+                second,
+                third,
+                provoking.as_ref().unwrap_or(first),
+            )),
+            _ => None,
+        }
+    }
+
+    fn line_interpolated_qualified(
+        &self,
+        perspective: Vec2,
+        linear: Vec2,
+        second: &Self,
+        provoking: &Self,
+    ) -> Self {
+        match (self, second) {
+            (Some(first), Some(second)) => Some(first.line_interpolated_qualified(
+                perspective,
+                linear,
+                second,
+                provoking.as_ref().unwrap_or(first),
+            )),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,495 @@
+//! Ready-made shaders for common cases, along with the vertex types they draw.
+//!
+//! Colors are handled as linear values in the 0-1 range, and are only scaled to 0-255 when a fragment
+//! is written. Textures are assumed to hold sRGB images, so color textures are decoded to linear light
+//! when sampled, and shaders that sample them can encode their output to sRGB with `srgb_output`, as
+//! `PbrShader` does. Lit shaders draw `MeshVertex` vertices, with positions and normals transformed into
+//! world space, where lighting is evaluated for the lights of a `Scene`. Each shader can be created
+//! from the `DrawContext` of `Renderer::render_scene`, or filled in by hand.
+
+use glam::{Affine3A, Mat3, Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+use softrender_derive::Barycentric;
+
+use crate::{
+    math::{linear_to_srgb, srgb_to_linear, to_output_color},
+    mesh::{Material, MeshVertex},
+    scene::{DrawContext, LightInstance, LightKind},
+    shader::{Barycentric, Shader},
+    texture::Texture,
+};
+
+/// The material used by lit shaders when a draw call has none
+static DEFAULT_MATERIAL: Material = Material {
+    name: String::new(),
+    ambient: Vec3::ZERO,
+    diffuse: Vec3::ONE,
+    specular: Vec3::ZERO,
+    emissive: Vec3::ZERO,
+    shininess: 0.0,
+    opacity: 1.0,
+    diffuse_texture: None,
+    specular_texture: None,
+    normal_texture: None,
+};
+
+/// Decodes an sRGB texel of a color texture to linear light
+pub(crate) fn decode_srgb(texel: Vec3) -> Vec3 {
+    Vec3::from(texel.to_array().map(srgb_to_linear))
+}
+
+/// Writes a linear color, first encoding it with the sRGB transfer curve if `srgb_output` is set
+pub(crate) fn encode_output(color: Vec3, srgb_output: bool) -> UVec3 {
+    if srgb_output {
+        to_output_color(Vec3::from(color.to_array().map(linear_to_srgb)))
+    } else {
+        to_output_color(color)
+    }
+}
+
+/// Vertex types with a position, which `UnlitShader` can draw
+pub trait VertexPosition {
+    fn position(&self) -> Vec3;
+}
+
+impl VertexPosition for Vec3 {
+    fn position(&self) -> Vec3 {
+        *self
+    }
+}
+
+impl VertexPosition for MeshVertex {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+/// A vertex with its own color, for `VertexColorShader`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorVertex {
+    pub position: Vec3,
+    /// Linear color in the 0-1 range
+    pub color: Vec3,
+}
+
+impl VertexPosition for ColorVertex {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+/// A vertex with texture coordinates, for `TexturedShader`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TexturedVertex {
+    pub position: Vec3,
+    pub uv: Vec2,
+}
+
+impl VertexPosition for TexturedVertex {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filtering {
+    NEAREST,
+    #[default]
+    BILINEAR,
+}
+
+impl Filtering {
+    fn sample(self, texture: &Texture, uv: Vec2) -> Vec3 {
+        match self {
+            Filtering::NEAREST => texture.sample_nearest(uv),
+            Filtering::BILINEAR => texture.sample_bilinear(uv),
+        }
+    }
+}
+
+/// Fills every fragment with a single color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnlitShader {
+    /// Transforms vertex positions into clip space
+    pub mvp: Mat4,
+    pub color: Vec3,
+}
+
+impl UnlitShader {
+    /// Creates a shader for a scene's draw call, using the diffuse color of its material
+    pub fn from_context(context: &DrawContext) -> Self {
+        UnlitShader {
+            mvp: context.view_proj * Mat4::from(context.model),
+            color: context.material.map_or(Vec3::ONE, |m| m.diffuse),
+        }
+    }
+}
+
+impl<V: VertexPosition> Shader<V, ()> for UnlitShader {
+    fn vertex(&self, vertex: &V) -> (Vec4, ()) {
+        (self.mvp * vertex.position().extend(1.0), ())
+    }
+
+    fn fragment(&self, _interpolated: ()) -> UVec3 {
+        to_output_color(self.color)
+    }
+}
+
+#[derive(Clone, Barycentric)]
+pub struct ColorVertexOut {
+    pub color: Vec3,
+}
+
+/// Interpolates the colors of `ColorVertex` vertices across each primitive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexColorShader {
+    /// Transforms vertex positions into clip space
+    pub mvp: Mat4,
+}
+
+impl Shader<ColorVertex, ColorVertexOut> for VertexColorShader {
+    fn vertex(&self, vertex: &ColorVertex) -> (Vec4, ColorVertexOut) {
+        (
+            self.mvp * vertex.position.extend(1.0),
+            ColorVertexOut {
+                color: vertex.color,
+            },
+        )
+    }
+
+    fn fragment(&self, interpolated: ColorVertexOut) -> UVec3 {
+        to_output_color(interpolated.color)
+    }
+}
+
+#[derive(Clone, Barycentric)]
+pub struct TexturedVertexOut {
+    pub uv: Vec2,
+}
+
+/// Colors fragments from a texture, multiplied by a tint
+#[derive(Clone, Copy)]
+pub struct TexturedShader<'a> {
+    /// Transforms vertex positions into clip space
+    pub mvp: Mat4,
+    pub texture: &'a Texture,
+    pub tint: Vec3,
+    pub filtering: Filtering,
+    /// Whether to encode output colors with the sRGB transfer curve, as displays expect. When off,
+    /// linear colors are written directly.
+    pub srgb_output: bool,
+}
+
+impl<'a> TexturedShader<'a> {
+    /// Creates a shader that draws a texture as it is, with bilinear filtering
+    pub fn new(mvp: Mat4, texture: &'a Texture) -> Self {
+        TexturedShader {
+            mvp,
+            texture,
+            tint: Vec3::ONE,
+            filtering: Filtering::BILINEAR,
+            srgb_output: true,
+        }
+    }
+}
+
+impl TexturedShader<'_> {
+    fn shade(&self, uv: Vec2) -> UVec3 {
+        encode_output(
+            decode_srgb(self.filtering.sample(self.texture, uv)) * self.tint,
+            self.srgb_output,
+        )
+    }
+}
+
+impl Shader<TexturedVertex, TexturedVertexOut> for TexturedShader<'_> {
+    fn vertex(&self, vertex: &TexturedVertex) -> (Vec4, TexturedVertexOut) {
+        (
+            self.mvp * vertex.position.extend(1.0),
+            TexturedVertexOut { uv: vertex.uv },
+        )
+    }
+
+    fn fragment(&self, interpolated: TexturedVertexOut) -> UVec3 {
+        self.shade(interpolated.uv)
+    }
+}
+
+impl Shader<MeshVertex, TexturedVertexOut> for TexturedShader<'_> {
+    fn vertex(&self, vertex: &MeshVertex) -> (Vec4, TexturedVertexOut) {
+        (
+            self.mvp * vertex.position.extend(1.0),
+            TexturedVertexOut { uv: vertex.uv },
+        )
+    }
+
+    fn fragment(&self, interpolated: TexturedVertexOut) -> UVec3 {
+        self.shade(interpolated.uv)
+    }
+}
+
+/// Applies a texel of a tangent space normal map to a normalized surface normal, where `tangent` holds
+/// the handedness of the bitangent in `w`
+pub(crate) fn perturb_normal(normal: Vec3, tangent: Vec4, texel: Vec3, scale: f32) -> Vec3 {
+    // Interpolation skews the tangent, so it is made perpendicular to the normal again
+    let bitangent_sign = tangent.w.signum();
+    let tangent = tangent.xyz();
+    let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+    let bitangent = normal.cross(tangent) * bitangent_sign;
+    let sampled = texel * 2.0 - 1.0;
+    let sampled = (sampled.truncate() * scale).extend(sampled.z);
+    (Mat3::from_cols(tangent, bitangent, normal) * sampled)
+        .try_normalize()
+        .unwrap_or(normal)
+}
+
+/// The light arriving at a surface from one light, and the direction it arrives from
+pub(crate) fn incoming_light(light: &LightInstance, position: Vec3) -> (Vec3, Vec3) {
+    let radiance = light.light.color * light.light.intensity;
+    let (range, cone) = match light.light.kind {
+        LightKind::DIRECTIONAL => return (radiance, -light.direction),
+        LightKind::POINT { range } => (range, None),
+        LightKind::SPOT {
+            range,
+            inner_angle,
+            outer_angle,
+        } => (range, Some((inner_angle.cos(), outer_angle.cos()))),
+    };
+
+    let offset = light.position - position;
+    let distance_squared = offset.length_squared().max(1e-4);
+    let to_light = offset / distance_squared.sqrt();
+    // Inverse-square falloff, smoothly windowed to reach zero at the light's range
+    let window = (1.0 - (distance_squared / (range * range)).powi(2))
+        .max(0.0)
+        .powi(2);
+    let mut attenuation = window / distance_squared;
+    if let Some((cos_inner, cos_outer)) = cone {
+        let cos_angle = light.direction.dot(-to_light);
+        let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer).max(1e-4)).clamp(0.0, 1.0);
+        attenuation *= t * t * (3.0 - 2.0 * t);
+    }
+    (radiance * attenuation, to_light)
+}
+
+/// Sums the Blinn-Phong diffuse and specular lighting of every light, before they are multiplied by
+/// the material's colors
+fn blinn_phong(
+    lights: &[LightInstance],
+    position: Vec3,
+    normal: Vec3,
+    to_camera: Vec3,
+    shininess: f32,
+) -> (Vec3, Vec3) {
+    lights
+        .iter()
+        .fold((Vec3::ZERO, Vec3::ZERO), |(diffuse, specular), light| {
+            let (radiance, to_light) = incoming_light(light, position);
+            let n_dot_l = normal.dot(to_light);
+            if n_dot_l <= 0.0 {
+                return (diffuse, specular);
+            }
+            let halfway = (to_light + to_camera).normalize_or_zero();
+            let highlight = normal.dot(halfway).max(0.0).powf(shininess.max(1.0));
+            (
+                diffuse + radiance * n_dot_l,
+                specular + radiance * highlight,
+            )
+        })
+}
+
+#[derive(Clone, Barycentric)]
+pub struct GouraudVertexOut {
+    pub uv: Vec2,
+    /// Diffuse lighting, including ambient light, to be multiplied by the diffuse color
+    pub diffuse: Vec3,
+    /// Specular lighting, already multiplied by the specular color
+    pub specular: Vec3,
+}
+
+/// Evaluates Blinn-Phong lighting once per vertex, and interpolates the result across each triangle
+///
+/// This is cheaper than `BlinnPhongShader`, but highlights that fall between vertices are lost.
+#[derive(Clone, Copy)]
+pub struct GouraudShader<'a> {
+    pub view_proj: Mat4,
+    pub model: Affine3A,
+    /// Transforms normals into world space
+    pub normal_matrix: Mat3,
+    pub camera_position: Vec3,
+    pub lights: &'a [LightInstance],
+    /// Light reaching every surface from all directions, which is reflected with the diffuse color
+    pub ambient_light: Vec3,
+    pub material: &'a Material,
+    pub diffuse_texture: Option<&'a Texture>,
+    /// Whether to encode output colors with the sRGB transfer curve, as displays expect. When off,
+    /// linear colors are written directly.
+    pub srgb_output: bool,
+}
+
+impl<'a> GouraudShader<'a> {
+    /// Creates a shader for a scene's draw call, with its material and lights and no ambient light
+    pub fn from_context(context: &DrawContext<'a>) -> Self {
+        GouraudShader {
+            view_proj: context.view_proj,
+            model: context.model,
+            normal_matrix: context.normal_matrix,
+            camera_position: context.camera_position,
+            lights: context.lights,
+            ambient_light: Vec3::ZERO,
+            material: context.material.unwrap_or(&DEFAULT_MATERIAL),
+            diffuse_texture: None,
+            srgb_output: true,
+        }
+    }
+}
+
+impl Shader<MeshVertex, GouraudVertexOut> for GouraudShader<'_> {
+    fn vertex(&self, vertex: &MeshVertex) -> (Vec4, GouraudVertexOut) {
+        let position = self.model.transform_point3(vertex.position);
+        let normal = (self.normal_matrix * vertex.normal).normalize_or_zero();
+        let to_camera = (self.camera_position - position).normalize_or_zero();
+        let (diffuse, specular) = blinn_phong(
+            self.lights,
+            position,
+            normal,
+            to_camera,
+            self.material.shininess,
+        );
+        (
+            self.view_proj * position.extend(1.0),
+            GouraudVertexOut {
+                uv: vertex.uv,
+                diffuse: diffuse + self.ambient_light,
+                specular: specular * self.material.specular,
+            },
+        )
+    }
+
+    fn fragment(&self, interpolated: GouraudVertexOut) -> UVec3 {
+        let mut diffuse_color = self.material.diffuse;
+        if let Some(texture) = self.diffuse_texture {
+            diffuse_color *= decode_srgb(texture.sample_bilinear(interpolated.uv));
+        }
+        encode_output(
+            interpolated.diffuse * diffuse_color + interpolated.specular + self.material.emissive,
+            self.srgb_output,
+        )
+    }
+}
+
+#[derive(Clone, Barycentric)]
+pub struct BlinnPhongVertexOut {
+    pub world_position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    /// World space tangent, with the handedness of the bitangent in `w`
+    pub tangent: Vec4,
+}
+
+/// Evaluates Blinn-Phong lighting for every fragment, from any number of lights
+///
+/// With a `normal_texture`, normals are perturbed by a tangent space normal map, which needs the
+/// tangents of `MeshVertex`, as computed by `Mesh::compute_tangents`.
+#[derive(Clone, Copy)]
+pub struct BlinnPhongShader<'a> {
+    pub view_proj: Mat4,
+    pub model: Affine3A,
+    /// Transforms normals into world space
+    pub normal_matrix: Mat3,
+    pub camera_position: Vec3,
+    pub lights: &'a [LightInstance],
+    /// Light reaching every surface from all directions, which is reflected with the diffuse color
+    pub ambient_light: Vec3,
+    pub material: &'a Material,
+    /// Multiplies the material's diffuse color
+    pub diffuse_texture: Option<&'a Texture>,
+    /// Multiplies the material's specular color
+    pub specular_texture: Option<&'a Texture>,
+    /// Tangent space normals, with each component mapped from -1..1 to the 0-1 range
+    pub normal_texture: Option<&'a Texture>,
+    /// Scales the tangent plane components of the normal map, to strengthen or weaken its bumps
+    pub normal_scale: f32,
+    /// Whether to encode output colors with the sRGB transfer curve, as displays expect. When off,
+    /// linear colors are written directly.
+    pub srgb_output: bool,
+}
+
+impl<'a> BlinnPhongShader<'a> {
+    /// Creates a shader for a scene's draw call, with its material and lights and no ambient light
+    pub fn from_context(context: &DrawContext<'a>) -> Self {
+        BlinnPhongShader {
+            view_proj: context.view_proj,
+            model: context.model,
+            normal_matrix: context.normal_matrix,
+            camera_position: context.camera_position,
+            lights: context.lights,
+            ambient_light: Vec3::ZERO,
+            material: context.material.unwrap_or(&DEFAULT_MATERIAL),
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            srgb_output: true,
+        }
+    }
+
+    /// The surface normal of a fragment, perturbed by the normal map if there is one
+    fn surface_normal(&self, interpolated: &BlinnPhongVertexOut) -> Vec3 {
+        let normal = interpolated.normal.normalize_or_zero();
+        let Some(normal_texture) = self.normal_texture else {
+            return normal;
+        };
+        perturb_normal(
+            normal,
+            interpolated.tangent,
+            normal_texture.sample_bilinear(interpolated.uv),
+            self.normal_scale,
+        )
+    }
+}
+
+impl Shader<MeshVertex, BlinnPhongVertexOut> for BlinnPhongShader<'_> {
+    fn vertex(&self, vertex: &MeshVertex) -> (Vec4, BlinnPhongVertexOut) {
+        let world_position = self.model.transform_point3(vertex.position);
+        (
+            self.view_proj * world_position.extend(1.0),
+            BlinnPhongVertexOut {
+                world_position,
+                normal: self.normal_matrix * vertex.normal,
+                uv: vertex.uv,
+                tangent: self
+                    .model
+                    .transform_vector3(vertex.tangent.xyz())
+                    .extend(vertex.tangent.w),
+            },
+        )
+    }
+
+    fn fragment(&self, interpolated: BlinnPhongVertexOut) -> UVec3 {
+        let normal = self.surface_normal(&interpolated);
+        let to_camera = (self.camera_position - interpolated.world_position).normalize_or_zero();
+        let (diffuse, specular) = blinn_phong(
+            self.lights,
+            interpolated.world_position,
+            normal,
+            to_camera,
+            self.material.shininess,
+        );
+
+        let mut diffuse_color = self.material.diffuse;
+        if let Some(texture) = self.diffuse_texture {
+            diffuse_color *= decode_srgb(texture.sample_bilinear(interpolated.uv));
+        }
+        let mut specular_color = self.material.specular;
+        if let Some(texture) = self.specular_texture {
+            specular_color *= decode_srgb(texture.sample_bilinear(interpolated.uv));
+        }
+        encode_output(
+            (diffuse + self.ambient_light) * diffuse_color
+                + specular * specular_color
+                + self.material.emissive,
+            self.srgb_output,
+        )
+    }
+}
@@ -0,0 +1,179 @@
+//! Shadow mapping in two passes.
+//!
+//! The scene is first drawn from the light's point of view with `DrawMode::DEPTH_ONLY`, using a
+//! view-projection matrix from `light_view_proj`, into a renderer of its own. Its depth buffer is then
+//! handed to a `ShadowSampler`, which fragment shaders of the main pass use to find how much of the
+//! light reaches each point.
+//!
+//! Depth is compared against the map rather than read from it, and the comparisons are filtered
+//! (percentage-closer filtering) so that shadow edges are soft instead of blocky.
+
+use glam::{Mat4, Vec2, Vec3, Vec3Swizzles, Vec4Swizzles};
+
+use crate::{
+    fb::Framebuffer,
+    math::BoundingSphere,
+    scene::{LightInstance, LightKind},
+};
+
+/// The narrowest a spot light's shadow frustum is allowed to get, relative to its far plane, so that
+/// depth precision isn't lost to a near plane right at the light
+const MIN_NEAR_RATIO: f32 = 0.01;
+
+/// Compares depths against a shadow map, a depth buffer rendered from the light
+///
+/// The depth buffer must have been drawn with a viewport covering all of it.
+pub struct ShadowSampler<'a> {
+    depth: &'a Framebuffer<f32>,
+    /// Offset subtracted from the depth of every point before comparing it, to stop surfaces from
+    /// shadowing themselves where the map is too coarse to represent them exactly
+    pub bias: f32,
+    /// How many texels to each side of the sample position are compared and averaged. Zero compares
+    /// only the nearest four texels.
+    pub pcf_radius: u32,
+}
+
+impl<'a> ShadowSampler<'a> {
+    pub fn new(depth: &'a Framebuffer<f32>) -> Self {
+        ShadowSampler {
+            depth,
+            bias: 0.002,
+            pcf_radius: 1,
+        }
+    }
+
+    /// Returns the fraction of light reaching `world_position`, from zero when it is fully shadowed to
+    /// one when it is fully lit
+    ///
+    /// `light_view_proj` must be the view-projection matrix that the shadow map was drawn with.
+    pub fn visibility(&self, light_view_proj: Mat4, world_position: Vec3) -> f32 {
+        let clip = light_view_proj * world_position.extend(1.0);
+        if clip.w <= 0.0 {
+            // Behind a spot light, where it never shines anyway
+            return 1.0;
+        }
+        self.compare_ndc(clip.xyz() / clip.w)
+    }
+
+    /// Compares a position in the light's normalized device coordinates against the map, as
+    /// `visibility` does
+    ///
+    /// Positions outside of the light's view are never shadowed.
+    pub fn compare_ndc(&self, ndc: Vec3) -> f32 {
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 || !(0.0..=1.0).contains(&ndc.z) {
+            return 1.0;
+        }
+        // The inverse of the renderer's viewport transform, which puts pixel centers on integers
+        let max_pixel = Vec2::new(
+            self.depth.get_width() as f32 - 1.0,
+            self.depth.get_height() as f32 - 1.0,
+        );
+        let pixel = (ndc.xy() * 0.5 + 0.5) * max_pixel;
+
+        let radius = self.pcf_radius as i32;
+        let mut lit = 0.0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                lit += self.compare(pixel + Vec2::new(dx as f32, dy as f32), ndc.z);
+            }
+        }
+        lit / ((2 * radius + 1) * (2 * radius + 1)) as f32
+    }
+
+    /// Compares `depth` against the four texels around a position in pixels, and blends the results
+    /// bilinearly. Positions off the edge of the map are clamped to it, and an empty map shadows
+    /// nothing.
+    pub fn compare(&self, pixel: Vec2, depth: f32) -> f32 {
+        if self.depth.get_width() == 0 || self.depth.get_height() == 0 {
+            return 1.0;
+        }
+        let max = Vec2::new(
+            self.depth.get_width() as f32 - 1.0,
+            self.depth.get_height() as f32 - 1.0,
+        );
+        let pixel = pixel.clamp(Vec2::ZERO, max);
+        let base = pixel.floor();
+        let weight = pixel - base;
+        let reference = depth - self.bias;
+        let lit = |x: f32, y: f32| {
+            let stored = self
+                .depth
+                .get_pixel(x.min(max.x) as u32, y.min(max.y) as u32);
+            if reference <= stored {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        let bottom = lit(base.x, base.y) * (1.0 - weight.x) + lit(base.x + 1.0, base.y) * weight.x;
+        let top = lit(base.x, base.y + 1.0) * (1.0 - weight.x)
+            + lit(base.x + 1.0, base.y + 1.0) * weight.x;
+        bottom * (1.0 - weight.y) + top * weight.y
+    }
+}
+
+/// Picks an up vector for a view looking along `direction` that is never parallel to it
+fn view_up(direction: Vec3) -> Vec3 {
+    if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    }
+}
+
+/// Returns the view-projection matrix of a directional light shining along `direction`, with an
+/// orthographic projection fitted tightly around `bounds`
+///
+/// `bounds` should enclose everything that casts or receives shadows.
+pub fn directional_light_view_proj(direction: Vec3, bounds: &BoundingSphere) -> Mat4 {
+    let direction = direction.try_normalize().unwrap_or(Vec3::NEG_Z);
+    let radius = bounds.radius.max(f32::EPSILON);
+    let eye = bounds.center - direction * radius;
+    let view = Mat4::look_at_rh(eye, bounds.center, view_up(direction));
+    let projection = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, 2.0 * radius);
+    projection * view
+}
+
+/// Returns the view-projection matrix of a spot light at `position` shining along `direction`, with a
+/// square perspective projection just wide enough for its cone
+pub fn spot_light_view_proj(
+    position: Vec3,
+    direction: Vec3,
+    outer_angle: f32,
+    near: f32,
+    far: f32,
+) -> Mat4 {
+    let direction = direction.try_normalize().unwrap_or(Vec3::NEG_Z);
+    let view = Mat4::look_at_rh(position, position + direction, view_up(direction));
+    // Perspective projections can't take in a whole hemisphere
+    let fov = (outer_angle * 2.0).clamp(0.01, 3.1);
+    let near = near.max(far * MIN_NEAR_RATIO).max(f32::EPSILON);
+    Mat4::perspective_rh(fov, 1.0, near, far.max(near * 2.0)) * view
+}
+
+/// Returns the view-projection matrix to draw a light's shadow map with, so that it covers
+/// everything within `bounds` that the light reaches
+///
+/// Point lights shine in every direction, which a single shadow map can't capture, so they return
+/// `None`.
+pub fn light_view_proj(light: &LightInstance, bounds: &BoundingSphere) -> Option<Mat4> {
+    match light.light.kind {
+        LightKind::DIRECTIONAL => Some(directional_light_view_proj(light.direction, bounds)),
+        LightKind::SPOT {
+            range, outer_angle, ..
+        } => {
+            // Only the part of the light's range that overlaps the bounds needs depth precision
+            let distance = light.position.distance(bounds.center);
+            let far = range.min(distance + bounds.radius);
+            let near = distance - bounds.radius;
+            Some(spot_light_view_proj(
+                light.position,
+                light.direction,
+                outer_angle,
+                near,
+                far,
+            ))
+        }
+        LightKind::POINT { .. } => None,
+    }
+}
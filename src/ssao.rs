@@ -0,0 +1,287 @@
+//! Screen-space ambient occlusion, darkening creases and corners that little ambient light reaches.
+//!
+//! Occlusion is found from the depth buffer alone. The view-space position of every pixel is rebuilt
+//! from its depth and the projection matrix, and its normal from the positions of its neighbours.
+//! Points scattered through the hemisphere above each surface are then projected back onto the
+//! screen, and those lying behind the geometry drawn there count as occluded. The kernel is rotated
+//! differently for neighbouring pixels, trading banding for noise, which a blur that doesn't cross
+//! depth edges then smooths away.
+
+use std::f32::consts::TAU;
+
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4Swizzles};
+
+use crate::{
+    fb::Framebuffer,
+    post::{compute_parallel, default_threads, gaussian_weights, PostInputs, PostPipeline},
+    renderer::Renderer,
+};
+
+/// A 4x4 ordered dither, which spreads the rotations of the kernel evenly over every 4x4 block of
+/// pixels
+const ROTATIONS: [u32; 16] = [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5];
+
+/// The radical inverse of `i` in the given base, a low discrepancy sequence in the 0-1 range
+fn radical_inverse(mut i: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut scale = 1.0 / base as f32;
+    while i > 0 {
+        result += (i % base) as f32 * scale;
+        i /= base;
+        scale /= base as f32;
+    }
+    result
+}
+
+/// Computes an ambient occlusion term from a depth buffer, and darkens rendered images by it
+pub struct Ssao {
+    /// Offsets within the unit hemisphere around +Z, gathered towards its center
+    kernel: Vec<Vec3>,
+    /// Radius of the hemisphere searched for occluders, in view space units
+    pub radius: f32,
+    /// How far, in view space units, geometry must lie in front of a sample to occlude it, which
+    /// stops flat surfaces from occluding themselves
+    pub bias: f32,
+    /// Exponent applied to the unoccluded fraction, where values above one darken occlusion further
+    pub strength: f32,
+    /// Standard deviation of the blur, in pixels. Zero leaves the occlusion unblurred.
+    pub blur_sigma: f32,
+}
+
+impl Ssao {
+    /// Creates an effect testing `sample_count` points within `radius` of every surface
+    pub fn new(sample_count: usize, radius: f32) -> Self {
+        let sample_count = sample_count.max(1) as u32;
+        let kernel = (0..sample_count)
+            .map(|i| {
+                // Directions spread evenly over the hemisphere
+                let z = (i as f32 + 0.5) / sample_count as f32;
+                let angle = TAU * radical_inverse(i, 2);
+                let r = (1.0 - z * z).sqrt();
+                let direction = Vec3::new(r * angle.cos(), r * angle.sin(), z);
+                // More samples close to the surface, where occluders matter most
+                let distance = radical_inverse(i + 1, 3);
+                direction * (0.1 + 0.9 * distance * distance)
+            })
+            .collect();
+        Ssao {
+            kernel,
+            radius,
+            bias: radius * 0.025,
+            strength: 1.0,
+            blur_sigma: 1.0,
+        }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.kernel.len()
+    }
+
+    /// Computes the fraction of ambient light reaching every pixel, from zero when fully occluded to
+    /// one when fully open
+    ///
+    /// `depth` must have been drawn with `projection` and a viewport covering all of it. Pixels left at
+    /// the far plane are never occluded.
+    pub fn compute(&self, depth: &Framebuffer<f32>, projection: Mat4) -> Framebuffer<f32> {
+        let (width, height) = (depth.get_width(), depth.get_height());
+        let threads = default_threads();
+        let max_pixel = Vec2::new(
+            (width as f32 - 1.0).max(1.0),
+            (height as f32 - 1.0).max(1.0),
+        );
+        let view_from_clip = projection.inverse();
+        let positions = compute_parallel(width, height, threads, |x, y| {
+            let ndc = Vec2::new(x as f32, y as f32) / max_pixel * 2.0 - 1.0;
+            view_from_clip.project_point3(ndc.extend(depth.get_pixel(x, y)))
+        });
+        let view = DepthView {
+            depth,
+            positions: &positions,
+            max_pixel,
+        };
+
+        let occlusion = compute_parallel(width, height, threads, |x, y| {
+            self.occlusion(&view, projection, x, y)
+        });
+        if self.blur_sigma <= 0.0 {
+            return occlusion;
+        }
+        let weights = gaussian_weights(self.blur_sigma);
+        let horizontal = compute_parallel(width, height, threads, |x, y| {
+            self.blur(&view, &occlusion, &weights, UVec2::new(x, y), (1, 0))
+        });
+        compute_parallel(width, height, threads, |x, y| {
+            self.blur(&view, &horizontal, &weights, UVec2::new(x, y), (0, 1))
+        })
+    }
+
+    /// Darkens the renderer's color buffer by the ambient occlusion computed from its depth buffer,
+    /// and returns the color buffer
+    ///
+    /// `projection` is the projection matrix the scene was drawn with.
+    pub fn apply<'a>(&self, renderer: &'a mut Renderer, projection: Mat4) -> &'a Framebuffer<u32> {
+        let occlusion = self.compute(renderer.get_depth_buffer(), projection);
+        let mut pipeline = PostPipeline::new();
+        pipeline.add(|inputs: &PostInputs, x, y| {
+            let (x, y) = (x as i32, y as i32);
+            inputs.color(x, y) * inputs.extra(0, x, y)
+        });
+        renderer.post_process_with(&pipeline, &[&occlusion])
+    }
+
+    fn occlusion(&self, view: &DepthView, projection: Mat4, x: u32, y: u32) -> f32 {
+        if view.is_background(x as i32, y as i32) {
+            return 1.0;
+        }
+        let position = view.position(x as i32, y as i32);
+        let Some(normal) = view.normal(x as i32, y as i32) else {
+            return 1.0;
+        };
+
+        let rotation = ROTATIONS[(y % 4 * 4 + x % 4) as usize] as f32;
+        let angle = TAU * (rotation + 0.5) / 16.0;
+        let (u, v) = normal.any_orthonormal_pair();
+        let tangent = u * angle.cos() + v * angle.sin();
+        let bitangent = normal.cross(tangent);
+
+        let mut occluded = 0.0;
+        for offset in &self.kernel {
+            let sample = position
+                + (tangent * offset.x + bitangent * offset.y + normal * offset.z) * self.radius;
+            let clip = projection * sample.extend(1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let pixel = (clip.xy() / clip.w + 1.0) / 2.0 * view.max_pixel;
+            // Samples off the edge of the screen can't be tested, and are assumed to be open
+            let Some(scene_z) = view.view_z(pixel) else {
+                continue;
+            };
+            // View space looks down -Z, so geometry in front of the sample has a greater z
+            if scene_z >= sample.z + self.bias {
+                // Geometry far in front of the surface is something else entirely, and fades out
+                let range = (self.radius / (position.z - scene_z).abs()).clamp(0.0, 1.0);
+                occluded += range * range * (3.0 - 2.0 * range);
+            }
+        }
+        (1.0 - occluded / self.kernel.len() as f32).powf(self.strength)
+    }
+
+    /// Blurs the occlusion along one axis, leaving out pixels too far in front of or behind this one
+    /// to belong to the same surface
+    fn blur(
+        &self,
+        view: &DepthView,
+        occlusion: &Framebuffer<f32>,
+        weights: &[f32],
+        pixel: UVec2,
+        (dx, dy): (i32, i32),
+    ) -> f32 {
+        let (x, y) = (pixel.x as i32, pixel.y as i32);
+        let center = occlusion.get_pixel(pixel.x, pixel.y);
+        if view.is_background(x, y) {
+            return center;
+        }
+        let z = view.position(x, y).z;
+        let mut sum = center * weights[0];
+        let mut total = weights[0];
+        for (i, weight) in weights.iter().enumerate().skip(1) {
+            for sign in [-1, 1] {
+                let (tx, ty) = (x + dx * i as i32 * sign, y + dy * i as i32 * sign);
+                if !view.contains(tx, ty)
+                    || view.is_background(tx, ty)
+                    || (view.position(tx, ty).z - z).abs() > self.radius
+                {
+                    continue;
+                }
+                sum += occlusion.get_pixel(tx as u32, ty as u32) * weight;
+                total += weight;
+            }
+        }
+        sum / total
+    }
+}
+
+/// A depth buffer, along with the view-space position of every pixel
+struct DepthView<'a> {
+    depth: &'a Framebuffer<f32>,
+    positions: &'a Framebuffer<Vec3>,
+    max_pixel: Vec2,
+}
+
+impl DepthView<'_> {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as u32) < self.depth.get_width()
+            && (y as u32) < self.depth.get_height()
+    }
+
+    fn is_background(&self, x: i32, y: i32) -> bool {
+        self.depth.get_pixel(x as u32, y as u32) >= 1.0
+    }
+
+    fn position(&self, x: i32, y: i32) -> Vec3 {
+        self.positions.get_pixel(x as u32, y as u32)
+    }
+
+    /// The view-space z of the geometry drawn at a point between pixel centers, blended from the four
+    /// nearest pixels. Surfaces seen at a grazing angle move a long way in depth from one pixel to the
+    /// next, so the nearest pixel alone would have them occlude themselves. Returns `None` off the
+    /// screen, or where the nearest pixel is background, whose pixels are otherwise left out.
+    fn view_z(&self, pixel: Vec2) -> Option<f32> {
+        let nearest = pixel.round();
+        let (x, y) = (nearest.x as i32, nearest.y as i32);
+        if !self.contains(x, y) || self.is_background(x, y) {
+            return None;
+        }
+        let base = pixel.floor();
+        let frac = pixel - base;
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - frac.x) * (1.0 - frac.y)),
+            (1, 0, frac.x * (1.0 - frac.y)),
+            (0, 1, (1.0 - frac.x) * frac.y),
+            (1, 1, frac.x * frac.y),
+        ] {
+            let (x, y) = (base.x as i32 + dx, base.y as i32 + dy);
+            if weight <= 0.0 || !self.contains(x, y) || self.is_background(x, y) {
+                continue;
+            }
+            sum += self.position(x, y).z * weight;
+            total += weight;
+        }
+        Some(sum / total)
+    }
+
+    /// Rebuilds the normal of the surface at a pixel, facing the camera, from the positions of its
+    /// neighbours. Of the neighbours on either side, the one nearer in depth is used, so that normals
+    /// along the silhouettes of objects aren't bent towards whatever lies behind them.
+    fn normal(&self, x: i32, y: i32) -> Option<Vec3> {
+        let position = self.position(x, y);
+        let difference = |dx: i32, dy: i32| {
+            let mut best: Option<Vec3> = None;
+            for sign in [-1, 1] {
+                let (nx, ny) = (x + dx * sign, y + dy * sign);
+                if !self.contains(nx, ny) || self.is_background(nx, ny) {
+                    continue;
+                }
+                let delta = (self.position(nx, ny) - position) * sign as f32;
+                if best.is_none_or(|best| delta.z.abs() < best.z.abs()) {
+                    best = Some(delta);
+                }
+            }
+            best
+        };
+        let normal = difference(1, 0)?
+            .cross(difference(0, 1)?)
+            .try_normalize()?;
+        // The camera sits at the origin of view space
+        Some(if normal.dot(position) > 0.0 {
+            -normal
+        } else {
+            normal
+        })
+    }
+}
@@ -0,0 +1,264 @@
+//! Helpers for golden-image regression tests.
+//!
+//! A test renders a scene headlessly with `render_headless`, then checks the result against a
+//! reference image with `assert_matches_reference`. References are stored as binary PPM files. When
+//! the `SOFTRENDER_BLESS` environment variable is set, the reference is overwritten with the current
+//! render instead, which is how new references are created and intentional changes accepted.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    export::ImageFormat,
+    fb::Framebuffer,
+    math::unpack_color,
+    renderer::Renderer,
+};
+
+/// The environment variable that, when set, causes references to be rewritten rather than compared
+pub const BLESS_VAR: &str = "SOFTRENDER_BLESS";
+
+/// Colors used by the diff image
+const DIFF_MISMATCH: u32 = 0xFF0000;
+const DIFF_TOLERATED: u32 = 0xFFFF00;
+
+/// Creates a renderer of the given size, clears it, lets `draw` issue draw calls, and returns the
+/// resulting color buffer
+pub fn render_headless(
+    width: u32,
+    height: u32,
+    clear_color: u32,
+    draw: impl FnOnce(&mut Renderer),
+) -> Framebuffer<u32> {
+    let mut renderer = Renderer::new(width, height);
+    renderer.clear_framebuffer(clear_color);
+    draw(&mut renderer);
+    renderer.get_color_buffer().clone()
+}
+
+/// How far a render may stray from its reference before the comparison fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// The largest difference allowed in any channel of a pixel before it counts as mismatched
+    pub per_pixel: u8,
+    /// If set, pixels that exceed `per_pixel` still match as long as their perceptual difference,
+    /// from 0 to 1, is no greater than this
+    pub perceptual: Option<f32>,
+    /// The number of mismatched pixels allowed
+    pub max_mismatched: usize,
+    /// The largest root mean square error allowed over every channel of every pixel, in the 0-255
+    /// range of a channel
+    pub max_rmse: f64,
+}
+
+impl Tolerance {
+    /// A tolerance that only accepts identical images
+    pub fn exact() -> Self {
+        Tolerance {
+            per_pixel: 0,
+            perceptual: None,
+            max_mismatched: 0,
+            max_rmse: 0.0,
+        }
+    }
+}
+
+impl Default for Tolerance {
+    /// Allows the small differences in rounding that occur between platforms, but no visibly
+    /// different pixels
+    fn default() -> Self {
+        Tolerance {
+            per_pixel: 2,
+            perceptual: Some(0.05),
+            max_mismatched: 0,
+            max_rmse: 1.0,
+        }
+    }
+}
+
+/// The result of comparing a render against its reference
+pub struct Comparison {
+    /// Root mean square error over every channel of every pixel
+    pub rmse: f64,
+    /// The number of pixels that differ by more than the tolerance allows
+    pub mismatched: usize,
+    /// The largest difference in any channel of any pixel
+    pub max_channel_delta: u8,
+    /// A faded copy of the reference, with mismatched pixels in red and pixels that differ but are
+    /// tolerated in yellow
+    pub diff: Framebuffer<u32>,
+}
+
+impl Comparison {
+    /// Whether the comparison falls within the given tolerance
+    pub fn passed(&self, tolerance: &Tolerance) -> bool {
+        self.mismatched <= tolerance.max_mismatched && self.rmse <= tolerance.max_rmse
+    }
+}
+
+/// Converts a packed color into the YIQ color space, with each channel in the 0-1 range
+fn to_yiq(color: u32) -> (f32, f32, f32) {
+    let rgb = unpack_color(color).as_vec3() / 255.0;
+    let y = 0.298_895_3 * rgb.x + 0.586_622_5 * rgb.y + 0.114_482_23 * rgb.z;
+    let i = 0.595_977_99 * rgb.x - 0.274_176_3 * rgb.y - 0.321_801_7 * rgb.z;
+    let q = 0.211_470_17 * rgb.x - 0.522_617_2 * rgb.y + 0.311_147_03 * rgb.z;
+    (y, i, q)
+}
+
+/// Measures how different two colors look, from 0 for identical colors to 1 for black against white
+///
+/// Uses the weighted YIQ distance of Kotsarenko and Ramos, which tracks perceived difference much
+/// more closely than a distance in RGB. The few pairs of saturated colors that are further apart than
+/// black and white are clamped to 1.
+pub fn perceptual_delta(a: u32, b: u32) -> f32 {
+    // The weighted distance between black and white
+    const MAX_DELTA: f32 = 0.5053;
+
+    let (ya, ia, qa) = to_yiq(a);
+    let (yb, ib, qb) = to_yiq(b);
+    let (dy, di, dq) = (ya - yb, ia - ib, qa - qb);
+    let delta = 0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq;
+    (delta / MAX_DELTA).sqrt().min(1.0)
+}
+
+/// Compares a render against its reference, pixel by pixel
+pub fn compare(
+    actual: &Framebuffer<u32>,
+    reference: &Framebuffer<u32>,
+    tolerance: &Tolerance,
+) -> Result<Comparison> {
+    let (width, height) = (reference.get_width(), reference.get_height());
+    if actual.get_width() != width || actual.get_height() != height {
+        return Err(Error::SizeMismatch {
+            expected: (width, height),
+            actual: (actual.get_width(), actual.get_height()),
+        });
+    }
+
+    let mut diff = Framebuffer::new(width, height);
+    let mut squared_error = 0.0;
+    let mut mismatched = 0;
+    let mut max_channel_delta = 0;
+    let pixels = actual.get_raw().iter().zip(reference.get_raw());
+    // Both buffers share a layout, so their raw pixels can be compared in order
+    for (i, (&a, &r)) in pixels.enumerate() {
+        let delta = unpack_color(a).as_ivec3() - unpack_color(r).as_ivec3();
+        squared_error += delta.as_dvec3().length_squared();
+        let channel_delta = delta.abs().max_element() as u8;
+        max_channel_delta = max_channel_delta.max(channel_delta);
+
+        let diff_color = if channel_delta == 0 {
+            // Fade the reference towards white, so the differences stand out
+            let luma = to_yiq(r).0;
+            let gray = (255.0 - (1.0 - luma) * 255.0 * 0.1).round() as u32;
+            gray << 16 | gray << 8 | gray
+        } else if channel_delta <= tolerance.per_pixel
+            || tolerance
+                .perceptual
+                .is_some_and(|threshold| perceptual_delta(a, r) <= threshold)
+        {
+            DIFF_TOLERATED
+        } else {
+            mismatched += 1;
+            DIFF_MISMATCH
+        };
+        let x = i as u32 % width;
+        let y = height - 1 - i as u32 / width;
+        diff.plot_pixel(x, y, diff_color);
+    }
+
+    let channel_count = width as f64 * height as f64 * 3.0;
+    Ok(Comparison {
+        rmse: if channel_count > 0.0 {
+            (squared_error / channel_count).sqrt()
+        } else {
+            0.0
+        },
+        mismatched,
+        max_channel_delta,
+        diff,
+    })
+}
+
+/// Loads a reference image, as written by `assert_matches_reference`
+pub fn load_reference(path: impl AsRef<Path>) -> Result<Framebuffer<u32>> {
+    Framebuffer::load_image(path)
+}
+
+/// Builds the path of an output written next to a reference, such as `scene.diff.ppm`
+fn sibling_path(reference: &Path, suffix: &str) -> PathBuf {
+    let stem = reference
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    reference.with_file_name(format!("{stem}.{suffix}.ppm"))
+}
+
+/// Checks a render against the reference image at `path`
+///
+/// If the `SOFTRENDER_BLESS` environment variable is set, the reference is written from the render
+/// instead. Otherwise, on failure the render and a diff image are written next to the reference, as
+/// `<name>.actual.ppm` and `<name>.diff.ppm`.
+///
+/// # Panics
+///
+/// Panics if the render does not match the reference within the tolerance, or if the reference
+/// cannot be read.
+pub fn assert_matches_reference(
+    actual: &Framebuffer<u32>,
+    path: impl AsRef<Path>,
+    tolerance: &Tolerance,
+) {
+    let path = path.as_ref();
+    if std::env::var_os(BLESS_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("failed to create the reference directory");
+        }
+        actual
+            .write_image(
+                &mut fs::File::create(path).expect("failed to create the reference"),
+                ImageFormat::PPM,
+            )
+            .expect("failed to write the reference");
+        return;
+    }
+
+    let reference = load_reference(path).unwrap_or_else(|err| {
+        panic!(
+            "failed to load reference {}: {err}. Set {BLESS_VAR}=1 to create it.",
+            path.display()
+        )
+    });
+    let comparison = compare(actual, &reference, tolerance)
+        .unwrap_or_else(|err| panic!("render does not match {}: {err}", path.display()));
+    if comparison.passed(tolerance) {
+        return;
+    }
+
+    let actual_path = sibling_path(path, "actual");
+    let diff_path = sibling_path(path, "diff");
+    let saved = actual
+        .save_image(&actual_path)
+        .and_then(|_| comparison.diff.save_image(&diff_path));
+    let outputs = match saved {
+        Ok(()) => format!(
+            "wrote {} and {}",
+            actual_path.display(),
+            diff_path.display()
+        ),
+        Err(err) => format!("failed to write the diff image: {err}"),
+    };
+    panic!(
+        "render does not match {}: {} mismatched pixels (allowed {}), RMSE {:.3} (allowed {:.3}), \
+         largest channel difference {}. {outputs}",
+        path.display(),
+        comparison.mismatched,
+        tolerance.max_mismatched,
+        comparison.rmse,
+        tolerance.max_rmse,
+        comparison.max_channel_delta,
+    );
+}
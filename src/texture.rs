@@ -0,0 +1,362 @@
+use std::path::Path;
+
+use glam::{IVec2, Mat4, Vec2, Vec3};
+
+use crate::{
+    error::{Error, Result},
+    fb::Framebuffer,
+    math::{reflect, refract, unpack_color},
+    renderer::Renderer,
+};
+
+/// An RGB image that shaders can sample with texture coordinates
+///
+/// Texture coordinates run from (0, 0) at the bottom left of the image to (1, 1) at the top right, and
+/// repeat outside of that range. Texels are stored as colors in the 0-1 range.
+#[derive(Clone)]
+pub struct Texture {
+    width: u32,
+    height: u32,
+    texels: Vec<Vec3>,
+}
+
+impl Texture {
+    /// Creates a texture from texels stored row by row, starting from the bottom left
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of texels does not match the dimensions, or either dimension is zero.
+    pub fn new(width: u32, height: u32, texels: Vec<Vec3>) -> Self {
+        assert!(width > 0 && height > 0, "textures must not be empty");
+        assert_eq!(
+            texels.len(),
+            width as usize * height as usize,
+            "texel count does not match the texture dimensions"
+        );
+        Texture {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    /// Creates a texture of a single color
+    pub fn solid(color: Vec3) -> Self {
+        Texture::new(1, 1, vec![color])
+    }
+
+    /// Creates a texture from the packed colors of a framebuffer
+    pub fn from_framebuffer(fb: &Framebuffer<u32>) -> Self {
+        let (width, height) = (fb.get_width().max(1), fb.get_height().max(1));
+        let mut texels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let color = fb.try_get_pixel(x, y).unwrap_or_default();
+                texels.push(unpack_color(color).as_vec3() / 255.0);
+            }
+        }
+        Texture::new(width, height, texels)
+    }
+
+    /// Loads a texture from an image file, picking the format from its extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Texture::from_framebuffer(&Framebuffer::load_image(path)?))
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Reads a single texel, wrapping coordinates that lie outside the texture
+    pub fn texel(&self, x: i32, y: i32) -> Vec3 {
+        let x = x.rem_euclid(self.width as i32) as usize;
+        let y = y.rem_euclid(self.height as i32) as usize;
+        self.texels[y * self.width as usize + x]
+    }
+
+    /// Samples the texel nearest to the given texture coordinates
+    pub fn sample_nearest(&self, uv: Vec2) -> Vec3 {
+        let coords = uv * Vec2::new(self.width as f32, self.height as f32);
+        self.texel(coords.x.floor() as i32, coords.y.floor() as i32)
+    }
+
+    /// Samples the texture at the given coordinates, blending between the four nearest texels
+    pub fn sample_bilinear(&self, uv: Vec2) -> Vec3 {
+        // Texel centers lie half a texel in from their edges
+        let coords = uv * Vec2::new(self.width as f32, self.height as f32) - 0.5;
+        let base = coords.floor();
+        let frac = coords - base;
+        let (x, y) = (base.x as i32, base.y as i32);
+
+        let bottom = self.texel(x, y).lerp(self.texel(x + 1, y), frac.x);
+        let top = self.texel(x, y + 1).lerp(self.texel(x + 1, y + 1), frac.x);
+        bottom.lerp(top, frac.y)
+    }
+}
+
+/// A face of a cubemap, named after the axis that points through its center
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    POSITIVE_X,
+    NEGATIVE_X,
+    POSITIVE_Y,
+    NEGATIVE_Y,
+    POSITIVE_Z,
+    NEGATIVE_Z,
+}
+
+impl CubeFace {
+    /// Every face, in the order that `Cubemap` stores them
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::POSITIVE_X,
+        CubeFace::NEGATIVE_X,
+        CubeFace::POSITIVE_Y,
+        CubeFace::NEGATIVE_Y,
+        CubeFace::POSITIVE_Z,
+        CubeFace::NEGATIVE_Z,
+    ];
+
+    /// Picks the face that a direction points through, and the texture coordinates on that face
+    /// where it leaves the cube
+    pub fn from_direction(direction: Vec3) -> (CubeFace, Vec2) {
+        let abs = direction.abs();
+        // The face's coordinates along its u and v axes, and the distance to the face
+        let (face, u, v, major) = if abs.x >= abs.y && abs.x >= abs.z {
+            if direction.x > 0.0 {
+                (CubeFace::POSITIVE_X, -direction.z, direction.y, abs.x)
+            } else {
+                (CubeFace::NEGATIVE_X, direction.z, direction.y, abs.x)
+            }
+        } else if abs.y >= abs.z {
+            if direction.y > 0.0 {
+                (CubeFace::POSITIVE_Y, direction.x, -direction.z, abs.y)
+            } else {
+                (CubeFace::NEGATIVE_Y, direction.x, direction.z, abs.y)
+            }
+        } else if direction.z > 0.0 {
+            (CubeFace::POSITIVE_Z, direction.x, direction.y, abs.z)
+        } else {
+            (CubeFace::NEGATIVE_Z, -direction.x, direction.y, abs.z)
+        };
+        let major = major.max(f32::MIN_POSITIVE);
+        (face, (Vec2::new(u, v) / major + 1.0) / 2.0)
+    }
+
+    /// The direction through a point of the face, the inverse of `from_direction`. The direction is
+    /// not normalized, and reaches the face at a distance of one along its axis.
+    pub fn direction(self, uv: Vec2) -> Vec3 {
+        let st = uv * 2.0 - 1.0;
+        let (s, t) = (st.x, st.y);
+        match self {
+            CubeFace::POSITIVE_X => Vec3::new(1.0, t, -s),
+            CubeFace::NEGATIVE_X => Vec3::new(-1.0, t, s),
+            CubeFace::POSITIVE_Y => Vec3::new(s, 1.0, -t),
+            CubeFace::NEGATIVE_Y => Vec3::new(s, -1.0, t),
+            CubeFace::POSITIVE_Z => Vec3::new(s, t, 1.0),
+            CubeFace::NEGATIVE_Z => Vec3::new(-s, t, -1.0),
+        }
+    }
+
+    /// The axis pointing out through the center of the face
+    pub fn axis(self) -> Vec3 {
+        self.direction(Vec2::splat(0.5))
+    }
+
+    /// The direction that v runs along on the face
+    pub fn up(self) -> Vec3 {
+        self.direction(Vec2::new(0.5, 1.0)) - self.axis()
+    }
+
+    /// The view matrix of a camera at `position` looking out through the face, with v pointing up
+    ///
+    /// Cubemap faces are seen mirrored from inside the cube, so u points to the left of this view.
+    pub fn view_matrix(self, position: Vec3) -> Mat4 {
+        Mat4::look_to_rh(position, self.axis(), self.up())
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Six square textures forming the faces of a cube, sampled by direction rather than by texture
+/// coordinates
+///
+/// Faces follow the OpenGL cubemap layout. On the X faces, v runs along +Y and u along -Z for +X or +Z
+/// for -X. On the Y faces, u runs along +X and v along -Z for +Y or +Z for -Y. On the Z faces, v runs
+/// along +Y and u along +X for +Z or -X for -Z.
+#[derive(Clone)]
+pub struct Cubemap {
+    faces: [Texture; 6],
+}
+
+impl Cubemap {
+    /// Creates a cubemap from its faces, in the order of `CubeFace::ALL`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the faces are not square, or not all the same size.
+    pub fn new(faces: [Texture; 6]) -> Self {
+        let size = faces[0].get_width();
+        assert!(
+            faces
+                .iter()
+                .all(|face| face.get_width() == size && face.get_height() == size),
+            "cubemap faces must be square and all the same size"
+        );
+        Cubemap { faces }
+    }
+
+    /// Loads the faces of a cubemap from image files, in the order of `CubeFace::ALL`
+    pub fn load<P: AsRef<Path>>(paths: [P; 6]) -> Result<Self> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths {
+            faces.push(Texture::load(path)?);
+        }
+        let size = faces[0].get_width();
+        if let Some(face) = faces
+            .iter()
+            .find(|face| face.get_width() != size || face.get_height() != size)
+        {
+            return Err(Error::SizeMismatch {
+                expected: (size, size),
+                actual: (face.get_width(), face.get_height()),
+            });
+        }
+        Ok(Cubemap::new(faces.try_into().unwrap_or_else(|_| unreachable!())))
+    }
+
+    /// Renders a cubemap of the surroundings of `position`, calling `draw` for every face with a
+    /// renderer of `size` by `size` pixels and the view-projection matrix looking through that face
+    ///
+    /// The renderer is cleared to black before every face, and its color buffer then becomes the
+    /// face. Anything nearer than `near` or further than `far` from `position` is clipped.
+    pub fn render(
+        size: u32,
+        position: Vec3,
+        near: f32,
+        far: f32,
+        mut draw: impl FnMut(&mut Renderer, CubeFace, Mat4) -> Result<()>,
+    ) -> Result<Self> {
+        let size = size.max(1);
+        // The renderer maps the centers of its outermost pixels to the edges of clip space, so the
+        // field of view is narrowed by half a texel to line its pixels up with the texel centers
+        let fov = 2.0 * (1.0 - 1.0 / size as f32).max(0.5 / size as f32).atan();
+        let projection = Mat4::perspective_rh(fov, 1.0, near, far);
+        let mut renderer = Renderer::new(size, size);
+        let mut faces = Vec::with_capacity(6);
+        for face in CubeFace::ALL {
+            renderer.clear_framebuffer(0);
+            draw(&mut renderer, face, projection * face.view_matrix(position))?;
+            // Mirror the image, as u runs to the left of the face's view
+            let fb = renderer.get_color_buffer();
+            let mut texels = Vec::with_capacity(size as usize * size as usize);
+            for y in 0..size {
+                for x in (0..size).rev() {
+                    texels.push(unpack_color(fb.get_pixel(x, y)).as_vec3() / 255.0);
+                }
+            }
+            faces.push(Texture::new(size, size, texels));
+        }
+        Ok(Cubemap::new(faces.try_into().unwrap_or_else(|_| unreachable!())))
+    }
+
+    /// Creates a cubemap of a single color
+    pub fn solid(color: Vec3) -> Self {
+        Cubemap::new(std::array::from_fn(|_| Texture::solid(color)))
+    }
+
+    /// Creates a cubemap by evaluating a function for the direction through the center of every texel
+    ///
+    /// Faces are at least one texel in size, as with `render`.
+    pub fn from_fn(size: u32, mut f: impl FnMut(Vec3) -> Vec3) -> Self {
+        let size = size.max(1);
+        Cubemap::new(CubeFace::ALL.map(|face| {
+            let mut texels = Vec::with_capacity(size as usize * size as usize);
+            for y in 0..size {
+                for x in 0..size {
+                    let uv = (Vec2::new(x as f32, y as f32) + 0.5) / size as f32;
+                    texels.push(f(face.direction(uv).normalize()));
+                }
+            }
+            Texture::new(size, size, texels)
+        }))
+    }
+
+    /// The width and height of every face
+    pub fn get_size(&self) -> u32 {
+        self.faces[0].get_width()
+    }
+
+    pub fn get_face(&self, face: CubeFace) -> &Texture {
+        &self.faces[face.index()]
+    }
+
+    /// Reads a texel of a face, carrying on across the edge of the cube onto the neighbouring face
+    /// for coordinates that lie past the edge of this one
+    fn texel(&self, face: CubeFace, x: i32, y: i32) -> Vec3 {
+        let size = self.get_size() as i32;
+        if (0..size).contains(&x) && (0..size).contains(&y) {
+            return self.faces[face.index()].texel(x, y);
+        }
+        // Follow the direction through the texel's center onto the face it really belongs to. Texels
+        // past a corner of the face belong to no face, and land on the nearest corner texel instead.
+        let uv = (Vec2::new(x as f32, y as f32) + 0.5) / size as f32;
+        let (neighbour, uv) = CubeFace::from_direction(face.direction(uv));
+        let coords = (uv * size as f32)
+            .floor()
+            .as_ivec2()
+            .clamp(IVec2::ZERO, IVec2::splat(size - 1));
+        self.faces[neighbour.index()].texel(coords.x, coords.y)
+    }
+
+    /// Samples the texel nearest to a direction, which needs no normalizing
+    pub fn sample_nearest(&self, direction: Vec3) -> Vec3 {
+        let (face, uv) = CubeFace::from_direction(direction);
+        let coords = (uv * self.get_size() as f32).floor();
+        self.texel(face, coords.x as i32, coords.y as i32)
+    }
+
+    /// Samples the cubemap in a direction, which needs no normalizing, blending between the four
+    /// nearest texels
+    ///
+    /// Near the edge of a face, texels from the neighbouring face are blended in, so no seams show
+    /// between the faces.
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let (face, uv) = CubeFace::from_direction(direction);
+        let coords = uv * self.get_size() as f32 - 0.5;
+        let base = coords.floor();
+        let frac = coords - base;
+        let (x, y) = (base.x as i32, base.y as i32);
+
+        let bottom = self
+            .texel(face, x, y)
+            .lerp(self.texel(face, x + 1, y), frac.x);
+        let top = self
+            .texel(face, x, y + 1)
+            .lerp(self.texel(face, x + 1, y + 1), frac.x);
+        bottom.lerp(top, frac.y)
+    }
+
+    /// Samples the reflection of the surroundings in a surface, seen along `incident`, the direction
+    /// from the eye towards the surface
+    pub fn sample_reflection(&self, incident: Vec3, normal: Vec3) -> Vec3 {
+        self.sample(reflect(incident, normal))
+    }
+
+    /// Samples the surroundings as seen through a transparent surface, along `incident` bent by the
+    /// ratio of refractive indices `eta`, as in `math::refract`. Where the light is totally internally
+    /// reflected, the reflection is sampled instead.
+    pub fn sample_refraction(&self, incident: Vec3, normal: Vec3, eta: f32) -> Vec3 {
+        let incident = incident.normalize_or_zero();
+        match refract(incident, normal, eta) {
+            Some(refracted) => self.sample(refracted),
+            None => self.sample_reflection(incident, normal),
+        }
+    }
+}
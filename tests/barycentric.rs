@@ -0,0 +1,135 @@
+//! Tests for the Barycentric implementations of doubles, affine transforms, quaternions, integers,
+//! arrays and options.
+
+#[macro_use]
+extern crate softrender_derive;
+
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{Affine3A, DQuat, DVec3, Quat, Vec2, Vec3};
+use softrender::shader::Barycentric;
+
+const THIRDS: Vec3 = Vec3::new(1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0);
+const HALVES: Vec2 = Vec2::new(0.5, 0.5);
+/// Weights outside the 0-1 range, which only arise when extrapolating
+const EXTRAPOLATE: Vec2 = Vec2::new(-1.0, 2.0);
+
+#[test]
+fn doubles_keep_their_precision() {
+    // Far more digits than an f32 holds
+    let big = 1.0e12 + 0.5;
+    assert_eq!(big.interpolated(Vec3::X, &0.0, &0.0), big);
+    assert_eq!(big.line_interpolated(HALVES, &big), big);
+    let corners = [DVec3::splat(big), DVec3::ZERO, DVec3::splat(3.0)];
+    let center = corners[0].interpolated(Vec3::new(0.5, 0.25, 0.25), &corners[1], &corners[2]);
+    assert_eq!(center, DVec3::splat(5.0e11 + 1.0));
+}
+
+#[test]
+fn affine_transforms_interpolate_each_part() {
+    let first = Affine3A::from_translation(Vec3::ZERO);
+    let second = Affine3A::from_scale_rotation_translation(
+        Vec3::splat(3.0),
+        Quat::IDENTITY,
+        Vec3::new(4.0, 0.0, 0.0),
+    );
+    let halfway = first.line_interpolated(HALVES, &second);
+    let expected = Affine3A::from_scale_rotation_translation(
+        Vec3::splat(2.0),
+        Quat::IDENTITY,
+        Vec3::new(2.0, 0.0, 0.0),
+    );
+    assert!(halfway.abs_diff_eq(expected, 1e-6), "{halfway:?}");
+}
+
+#[test]
+fn quaternions_take_the_shortest_path() {
+    let start = Quat::IDENTITY;
+    let quarter_turn = Quat::from_rotation_z(FRAC_PI_2);
+    let eighth_turn = Quat::from_rotation_z(FRAC_PI_2 / 2.0);
+    // The negated quaternion is the same rotation, but lies in the opposite hemisphere. Interpolating
+    // it without flipping would turn the long way around.
+    for end in [quarter_turn, -quarter_turn] {
+        let halfway = start.line_interpolated(HALVES, &end);
+        assert!(halfway.is_normalized());
+        assert!(halfway.abs_diff_eq(eighth_turn, 1e-5), "{halfway}");
+    }
+    let center = start.interpolated(THIRDS, &-quarter_turn, &quarter_turn);
+    assert!(center.is_normalized());
+    let expected = start.interpolated(THIRDS, &quarter_turn, &quarter_turn);
+    assert!(center.abs_diff_eq(expected, 1e-6), "{center}");
+
+    let halfway = DQuat::IDENTITY.line_interpolated(HALVES, &-quarter_turn.as_f64());
+    assert!(halfway.abs_diff_eq(eighth_turn.as_f64(), 1e-5), "{halfway}");
+}
+
+#[test]
+fn integers_round_to_nearest() {
+    assert_eq!(10u8.line_interpolated(HALVES, &11), 11);
+    assert_eq!(10u8.line_interpolated(Vec2::new(0.6, 0.4), &11), 10);
+    // Halfway cases round away from zero
+    assert_eq!((-10i8).line_interpolated(HALVES, &-11), -11);
+    assert_eq!(1i32.interpolated(THIRDS, &2, &4), 2);
+    assert_eq!(
+        u64::MAX.interpolated(Vec3::X, &0, &0),
+        u64::MAX,
+        "values beyond the precision of f32"
+    );
+}
+
+#[test]
+fn integers_saturate_when_extrapolating() {
+    assert_eq!(200u8.line_interpolated(EXTRAPOLATE, &250), 255);
+    assert_eq!(250u8.line_interpolated(EXTRAPOLATE, &200), 150);
+    assert_eq!(100u8.line_interpolated(EXTRAPOLATE, &10), 0);
+    assert_eq!(100i8.line_interpolated(EXTRAPOLATE, &-100), -128);
+    assert_eq!((-100i8).line_interpolated(EXTRAPOLATE, &100), 127);
+}
+
+#[test]
+fn arrays_interpolate_element_wise() {
+    let weights = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]];
+    assert_eq!(
+        weights[0].line_interpolated(HALVES, &weights[1]),
+        [0.5, 0.5, 0.0, 0.0]
+    );
+    let colors: [[u8; 4]; 3] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 0]];
+    assert_eq!(
+        colors[0].interpolated(THIRDS, &colors[1], &colors[2]),
+        [85, 85, 85, 170]
+    );
+}
+
+#[derive(Clone, Debug, PartialEq, Barycentric)]
+struct Flat(#[flat] u32);
+
+#[test]
+fn options_need_every_vertex() {
+    let [a, b, c] = [Some(0.0f32), Some(3.0), Some(6.0)];
+    assert_eq!(a.interpolated(THIRDS, &b, &c), Some(3.0));
+    assert_eq!(a.line_interpolated(HALVES, &b), Some(1.5));
+    // A single vertex without the attribute leaves it out of the whole primitive, wherever it is
+    assert_eq!(a.interpolated(THIRDS, &b, &None), None);
+    assert_eq!(None.interpolated(THIRDS, &b, &c), None);
+    assert_eq!(a.line_interpolated(HALVES, &None), None);
+
+    // Qualifiers reach the inner value, with flat values taken from the provoking vertex if it has
+    // one, and from the first vertex otherwise
+    let [a, b, c] = [1, 2, 3].map(|value| Some(Flat(value)));
+    assert_eq!(
+        a.interpolated_qualified(THIRDS, THIRDS, &b, &c, &c),
+        Some(Flat(3))
+    );
+    assert_eq!(
+        a.interpolated_qualified(THIRDS, THIRDS, &b, &c, &None),
+        Some(Flat(1))
+    );
+    assert_eq!(
+        a.line_interpolated_qualified(HALVES, HALVES, &b, &b),
+        Some(Flat(2))
+    );
+    assert_eq!(
+        a.line_interpolated_qualified(HALVES, HALVES, &None, &b),
+        None
+    );
+}
@@ -0,0 +1,129 @@
+//! Tests for cameras, their controllers, and frustum extraction.
+
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{Mat4, Vec2, Vec3};
+use softrender::camera::{ArcballController, Camera, FlyController, OrbitController};
+
+const EPSILON: f32 = 1e-5;
+
+fn assert_near(actual: Vec3, expected: Vec3) {
+    assert!(
+        actual.abs_diff_eq(expected, EPSILON),
+        "expected {expected}, got {actual}"
+    );
+}
+
+#[test]
+fn look_at_matches_glam() {
+    let eye = Vec3::new(3.0, 2.0, 5.0);
+    let target = Vec3::new(-1.0, 0.5, 0.0);
+    let mut camera = Camera::perspective(1.0, 1.5, 0.1, 100.0, eye);
+    camera.look_at(target, Vec3::Y);
+
+    assert_near(camera.forward(), (target - eye).normalize());
+    assert!(camera
+        .view_matrix()
+        .abs_diff_eq(Mat4::look_at_rh(eye, target, Vec3::Y), EPSILON));
+    assert!(camera.view_projection_matrix().abs_diff_eq(
+        Mat4::perspective_rh(1.0, 1.5, 0.1, 100.0) * Mat4::look_at_rh(eye, target, Vec3::Y),
+        EPSILON
+    ));
+}
+
+#[test]
+fn resize_updates_aspect_ratio() {
+    let mut camera = Camera::orthographic(4.0, 1.0, 0.1, 10.0, Vec3::ZERO);
+    camera.resize(1920, 1080);
+    assert_eq!(camera.get_aspect_ratio(), 1920.0 / 1080.0);
+    // A minimized window keeps the last aspect ratio
+    camera.resize(0, 0);
+    assert_eq!(camera.get_aspect_ratio(), 1920.0 / 1080.0);
+}
+
+#[test]
+fn perspective_frustum_bounds_the_view() {
+    let mut camera = Camera::perspective(FRAC_PI_2, 1.0, 1.0, 10.0, Vec3::new(0.0, 0.0, 5.0));
+    camera.look_at(Vec3::ZERO, Vec3::Y);
+    let frustum = camera.frustum();
+
+    assert!(frustum.contains_point(Vec3::ZERO));
+    assert!(frustum.contains_point(Vec3::new(2.9, 0.0, 2.0)));
+    // Outside the 90 degree field of view, and beyond the far plane
+    assert!(!frustum.contains_point(Vec3::new(3.1, 0.0, 2.0)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -6.0)));
+    // Behind the camera
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 6.0)));
+
+    assert!(frustum.intersects_sphere(Vec3::new(3.5, 0.0, 2.0), 1.0));
+    assert!(!frustum.intersects_sphere(Vec3::new(0.0, 0.0, -8.0), 1.0));
+}
+
+#[test]
+fn orthographic_frustum_is_a_box() {
+    let camera = Camera::orthographic(4.0, 2.0, 0.0, 10.0, Vec3::ZERO);
+    let frustum = camera.frustum();
+    assert!(frustum.contains_point(Vec3::new(3.9, 1.9, -9.9)));
+    assert!(!frustum.contains_point(Vec3::new(4.1, 0.0, -5.0)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 2.1, -5.0)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -10.1)));
+}
+
+#[test]
+fn fly_controller_moves_relative_to_view() {
+    let mut camera = Camera::perspective(1.0, 1.0, 0.1, 100.0, Vec3::ZERO);
+    let mut fly = FlyController::new(Vec3::ZERO);
+    // Turn left by a quarter, to look down -X
+    fly.rotate(FRAC_PI_2, 0.0);
+    fly.translate(Vec3::new(0.0, 0.0, 2.0));
+    fly.update(&mut camera);
+    assert_near(camera.get_position(), Vec3::new(-2.0, 0.0, 0.0));
+    assert_near(camera.forward(), Vec3::NEG_X);
+
+    // Pitch is clamped short of straight up
+    fly.rotate(0.0, 10.0);
+    assert!(fly.pitch < FRAC_PI_2);
+
+    // Rolling a quarter turn to the right points the camera's up along the world's -Z
+    let mut fly = FlyController::new(Vec3::ZERO);
+    fly.roll(-FRAC_PI_2);
+    fly.update(&mut camera);
+    assert_near(camera.forward(), Vec3::NEG_Z);
+    assert_near(camera.up(), Vec3::X);
+}
+
+#[test]
+fn orbit_controller_faces_target() {
+    let mut camera = Camera::perspective(1.0, 1.0, 0.1, 100.0, Vec3::ZERO);
+    let target = Vec3::new(1.0, 2.0, 3.0);
+    let mut orbit = OrbitController::new(target, 4.0);
+    orbit.update(&mut camera);
+    assert_near(camera.get_position(), target + Vec3::new(0.0, 0.0, 4.0));
+
+    orbit.rotate(FRAC_PI_2, 0.0);
+    orbit.zoom(0.5);
+    orbit.update(&mut camera);
+    assert_near(camera.get_position(), target + Vec3::new(2.0, 0.0, 0.0));
+    assert_near(camera.forward(), Vec3::NEG_X);
+
+    // Pitching up raises the camera, which then looks down at the target
+    let mut orbit = OrbitController::new(target, 4.0);
+    orbit.rotate(0.0, 0.5);
+    orbit.update(&mut camera);
+    assert!(camera.get_position().y > target.y);
+    assert_near(
+        camera.forward(),
+        (target - camera.get_position()).normalize(),
+    );
+}
+
+#[test]
+fn arcball_drag_turns_around_target() {
+    let mut camera = Camera::perspective(1.0, 1.0, 0.1, 100.0, Vec3::ZERO);
+    let mut arcball = ArcballController::new(Vec3::ZERO, 3.0);
+    // Dragging the ball to the right spins the scene to the right, moving the camera to the left
+    arcball.drag(Vec2::ZERO, Vec2::new(1.0, 0.0));
+    arcball.update(&mut camera);
+    assert_near(camera.get_position(), Vec3::new(-3.0, 0.0, 0.0));
+    assert_near(camera.forward(), Vec3::X);
+}
@@ -0,0 +1,163 @@
+//! Tests for the command-line renderer's argument parsing and camera framing.
+
+#![cfg(feature = "cli")]
+
+use std::path::PathBuf;
+
+use glam::Vec3;
+use softrender::{
+    cli::{frame_camera, parse_args, Options, ShadingMode},
+    math::Aabb,
+};
+
+fn parse(args: &str) -> Result<Option<Options>, String> {
+    parse_args(args.split_whitespace().map(str::to_string))
+}
+
+fn options(args: &str) -> Options {
+    parse(args).unwrap().unwrap()
+}
+
+#[test]
+fn parses_options() {
+    let defaults = options("model.obj");
+    assert_eq!(defaults.model, PathBuf::from("model.obj"));
+    assert_eq!(defaults.output, PathBuf::from("out.png"));
+    assert_eq!(defaults.mode, ShadingMode::FLAT);
+    assert_eq!((defaults.width, defaults.height), (800, 800));
+    assert_eq!((defaults.eye, defaults.target), (None, None));
+
+    let options = options(
+        "-o teapot.ppm --mode textured -t bricks.png model.obj --width 320 --height 200 \
+         --eye 1,2,3 --target 0,0.5,0 --up 0,0,1 --fov 30 --background #FF8000",
+    );
+    assert_eq!(options.model, PathBuf::from("model.obj"));
+    assert_eq!(options.output, PathBuf::from("teapot.ppm"));
+    assert_eq!(options.mode, ShadingMode::TEXTURED);
+    assert_eq!(options.texture, Some(PathBuf::from("bricks.png")));
+    assert_eq!((options.width, options.height), (320, 200));
+    assert_eq!(options.eye, Some(Vec3::new(1.0, 2.0, 3.0)));
+    assert_eq!(options.target, Some(Vec3::new(0.0, 0.5, 0.0)));
+    assert_eq!(options.up, Vec3::Z);
+    assert_eq!(options.fov, 30.0);
+    assert_eq!(options.background, 0xFF8000);
+
+    // Asking for help stops parsing, so nothing after it is checked
+    assert_eq!(parse("--help --mode shiny"), Ok(None));
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    for (args, error) in [
+        ("", "no model given"),
+        ("a.obj b.obj", "unexpected argument \"b.obj\""),
+        ("model.obj --output", "missing value for --output"),
+        ("model.obj --mode shiny", "unknown mode \"shiny\""),
+        ("model.obj --colour red", "unknown option \"--colour\""),
+        (
+            "model.obj --width wide",
+            "invalid value \"wide\" for --width",
+        ),
+        ("model.obj --width -5", "invalid value \"-5\" for --width"),
+        (
+            "model.obj --eye 1,2",
+            "expected three components in \"1,2\"",
+        ),
+        ("model.obj --eye 1,two,3", "invalid vector \"1,two,3\""),
+        ("model.obj --eye 1,inf,3", "invalid vector \"1,inf,3\""),
+        (
+            "model.obj --background 1000000",
+            "invalid color \"1000000\"",
+        ),
+        ("model.obj --background red", "invalid color \"red\""),
+    ] {
+        assert_eq!(parse(args), Err(error.to_string()), "{args}");
+    }
+    for args in [
+        "model.obj --height 0",
+        "model.obj --fov 0",
+        "model.obj --fov 180",
+    ] {
+        assert!(parse(args).is_err(), "{args}");
+    }
+}
+
+/// Whether every corner of the bounds lies within the camera's view, between its near and far planes
+fn in_view(options: &Options, bounds: &Aabb) -> bool {
+    let view_proj = frame_camera(options, bounds)
+        .unwrap()
+        .view_projection_matrix();
+    (0..8).all(|corner| {
+        let point = Vec3::select(
+            glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+            bounds.max,
+            bounds.min,
+        );
+        let ndc = view_proj.project_point3(point);
+        ndc.is_finite() && ndc.abs().max_element() <= 1.0
+    })
+}
+
+#[test]
+fn frames_the_whole_model() {
+    let bounds = Aabb {
+        min: Vec3::new(-1.0, 0.0, -2.0),
+        max: Vec3::new(3.0, 1.0, 0.0),
+    };
+    for args in [
+        "model.obj",
+        "model.obj --width 200 --height 800",
+        "model.obj --width 800 --height 200 --fov 20",
+        "model.obj --target 1,0.5,-1 --up 1,0,0",
+    ] {
+        assert!(in_view(&options(args), &bounds), "{args}");
+    }
+    let camera = frame_camera(&options("model.obj"), &bounds).unwrap();
+    assert!(camera.forward().dot(Vec3::NEG_Z) > 0.9);
+
+    // An eye inside the model still gets a usable depth range
+    let camera = frame_camera(
+        &options("model.obj --eye 1,0.5,-1 --target 1,0.5,-5"),
+        &bounds,
+    );
+    assert!(camera.unwrap().view_projection_matrix().is_finite());
+}
+
+#[test]
+fn frames_degenerate_models() {
+    // A single point, at and far from the origin, and a flat model seen edge on
+    for bounds in [
+        Aabb {
+            min: Vec3::ZERO,
+            max: Vec3::ZERO,
+        },
+        Aabb {
+            min: Vec3::splat(1.0e4),
+            max: Vec3::splat(1.0e4),
+        },
+        Aabb {
+            min: Vec3::new(-1.0, -1.0, 0.0),
+            max: Vec3::new(1.0, 1.0, 0.0),
+        },
+    ] {
+        assert!(in_view(&options("model.obj"), &bounds), "{bounds:?}");
+    }
+}
+
+#[test]
+fn rejects_degenerate_cameras() {
+    let bounds = Aabb {
+        min: Vec3::splat(-1.0),
+        max: Vec3::splat(1.0),
+    };
+    // An eye placed on the target has no direction to look in
+    let result = frame_camera(&options("model.obj --eye 0,0,0"), &bounds);
+    assert!(result.is_err());
+    let result = frame_camera(&options("model.obj --eye 2,2,2 --target 2,2,2"), &bounds);
+    assert!(result.is_err());
+    // Looking straight along the up direction leaves the camera's roll undefined
+    let result = frame_camera(&options("model.obj --eye 0,5,0"), &bounds);
+    assert!(result.is_err());
+    let result = frame_camera(&options("model.obj --eye 0,5,0 --up 0,0,-1"), &bounds);
+    assert!(result.is_ok());
+}
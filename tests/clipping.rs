@@ -0,0 +1,207 @@
+//! Regression tests for attribute interpolation on clipped primitives.
+//!
+//! Each test renders a textured quad that is clipped by the viewing frustum, with a shader that writes
+//! the interpolated texture coordinates into the red and green channels. For every covered pixel, the
+//! texture coordinates are then recomputed analytically by intersecting the pixel's view ray with the
+//! plane of the quad, and compared against the rendered result.
+
+#[macro_use]
+extern crate softrender_derive;
+
+use glam::{Mat4, UVec3, Vec2, Vec3, Vec4};
+use softrender::{
+    fb::Framebuffer,
+    renderer::Renderer,
+    shader::{Barycentric, Shader},
+};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 128;
+const BACKGROUND: u32 = 0x0000FF;
+// Texture coordinates are quantized to 8 bits, and sampled at slightly different positions by the
+// rasterizer and the reference, so allow a couple of steps of error.
+const TOLERANCE: i32 = 2;
+
+#[derive(Clone, Barycentric)]
+struct VertexOut {
+    uv: Vec2,
+}
+
+struct Vertex {
+    pos: Vec3,
+    uv: Vec2,
+}
+
+struct UvShader {
+    view_proj: Mat4,
+}
+
+impl Shader<Vertex, VertexOut> for UvShader {
+    fn vertex(&self, vertex: &Vertex) -> (Vec4, VertexOut) {
+        (
+            self.view_proj * vertex.pos.extend(1.0),
+            VertexOut { uv: vertex.uv },
+        )
+    }
+
+    fn fragment(&self, inputs: VertexOut) -> UVec3 {
+        (inputs.uv.clamp(Vec2::ZERO, Vec2::ONE) * 255.0)
+            .round()
+            .extend(0.0)
+            .as_uvec3()
+    }
+}
+
+/// A quad spanning `origin` to `origin + u_axis + v_axis`, with texture coordinates running from
+/// (0, 0) at the origin to (1, 1) at the opposite corner
+struct Quad {
+    origin: Vec3,
+    u_axis: Vec3,
+    v_axis: Vec3,
+}
+
+impl Quad {
+    fn vertices(&self) -> Vec<Vertex> {
+        [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+            .into_iter()
+            .map(|(u, v)| Vertex {
+                pos: self.origin + self.u_axis * u + self.v_axis * v,
+                uv: Vec2::new(u, v),
+            })
+            .collect()
+    }
+
+    /// Analytically computes the texture coordinates seen through the given pixel, if the pixel's view
+    /// ray hits the quad
+    fn uv_at_pixel(&self, inv_view_proj: Mat4, x: u32, y: u32) -> Option<Vec2> {
+        let ndc = Vec2::new(
+            x as f32 / (WIDTH - 1) as f32 * 2.0 - 1.0,
+            y as f32 / (HEIGHT - 1) as f32 * 2.0 - 1.0,
+        );
+        let near = inv_view_proj.project_point3(ndc.extend(-1.0));
+        let far = inv_view_proj.project_point3(ndc.extend(1.0));
+        let dir = far - near;
+
+        let normal = self.u_axis.cross(self.v_axis);
+        let t = (self.origin - near).dot(normal) / dir.dot(normal);
+        if !(0.0..=1.0).contains(&t) {
+            return None;
+        }
+        let rel = near + dir * t - self.origin;
+        let uv = Vec2::new(
+            rel.dot(self.u_axis) / self.u_axis.length_squared(),
+            rel.dot(self.v_axis) / self.v_axis.length_squared(),
+        );
+        // Skip pixels right at the border of the quad, where coverage is ambiguous
+        let margin = 0.01;
+        if uv.cmplt(Vec2::splat(margin)).any() || uv.cmpgt(Vec2::splat(1.0 - margin)).any() {
+            return None;
+        }
+        Some(uv)
+    }
+}
+
+/// Renders the quad, and checks every pixel it covers against its analytic texture coordinates.
+/// Returns the number of pixels that were checked.
+fn render_and_compare(quad: &Quad, view_proj: Mat4) -> usize {
+    let mut renderer = Renderer::new(WIDTH, HEIGHT);
+    let mut shader = UvShader { view_proj };
+    renderer.clear_framebuffer(BACKGROUND);
+    // Draw both windings, so the test does not depend on which side of the quad faces the camera
+    let fb = renderer.draw(
+        &mut shader,
+        &quad.vertices(),
+        &[0, 1, 2, 2, 1, 3, 0, 2, 1, 1, 2, 3],
+    );
+
+    compare(fb, quad, view_proj.inverse())
+}
+
+fn compare(fb: &Framebuffer<u32>, quad: &Quad, inv_view_proj: Mat4) -> usize {
+    let mut checked = 0;
+    let mut failures = Vec::new();
+    // Pixels on the border of the screen lie exactly on the clipped edges, where coverage is ambiguous
+    for y in 1..HEIGHT - 1 {
+        for x in 1..WIDTH - 1 {
+            let Some(expected_uv) = quad.uv_at_pixel(inv_view_proj, x, y) else {
+                continue;
+            };
+            let pixel = fb.get_pixel(x, y);
+            assert_ne!(pixel, BACKGROUND, "pixel ({x}, {y}) should be covered");
+
+            let expected = (expected_uv * 255.0).round().as_ivec2();
+            let actual = glam::IVec2::new((pixel >> 16) as i32 & 0xFF, (pixel >> 8) as i32 & 0xFF);
+            if (expected - actual).abs().max_element() > TOLERANCE {
+                failures.push(((x, y), expected, actual));
+            }
+            checked += 1;
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} pixels have incorrect texture coordinates, first few: {:?}",
+        failures.len(),
+        checked,
+        &failures[..failures.len().min(5)]
+    );
+    checked
+}
+
+fn perspective(eye: Vec3, target: Vec3) -> Mat4 {
+    Mat4::perspective_rh(60.0f32.to_radians(), 1.0, 0.5, 50.0)
+        * Mat4::look_at_rh(eye, target, Vec3::Y)
+}
+
+#[test]
+fn floor_clipped_by_near_plane() {
+    // A floor extending from behind the camera far into the distance, so that both of its triangles
+    // cross the near plane.
+    let quad = Quad {
+        origin: Vec3::new(-4.0, -1.0, 6.0),
+        u_axis: Vec3::new(8.0, 0.0, 0.0),
+        v_axis: Vec3::new(0.0, 0.0, -30.0),
+    };
+    let checked = render_and_compare(&quad, perspective(Vec3::ZERO, Vec3::new(0.0, -0.3, -1.0)));
+    assert!(checked > 1000);
+}
+
+#[test]
+fn edges_parallel_to_view_direction() {
+    // Looking straight down the length of a floor, its long edges have the same clip-space x and y at
+    // both ends, even though they cross the near plane.
+    let quad = Quad {
+        origin: Vec3::new(-4.0, -1.0, 6.0),
+        u_axis: Vec3::new(8.0, 0.0, 0.0),
+        v_axis: Vec3::new(0.0, 0.0, -30.0),
+    };
+    let checked = render_and_compare(&quad, perspective(Vec3::ZERO, Vec3::NEG_Z));
+    assert!(checked > 1000);
+}
+
+#[test]
+fn wall_clipped_by_side_planes() {
+    // A wall seen at a grazing angle, much wider than the screen, so that it is clipped by the left and
+    // right planes while its depth varies strongly across the screen.
+    let quad = Quad {
+        origin: Vec3::new(-40.0, -3.0, -1.0),
+        u_axis: Vec3::new(80.0, 0.0, -20.0),
+        v_axis: Vec3::new(0.0, 6.0, 0.0),
+    };
+    let checked = render_and_compare(&quad, perspective(Vec3::ZERO, Vec3::new(0.3, 0.0, -1.0)));
+    assert!(checked > 1000);
+}
+
+#[test]
+fn quad_clipped_by_far_plane() {
+    // A floor extending well past the far plane
+    let quad = Quad {
+        origin: Vec3::new(-20.0, -2.0, -1.0),
+        u_axis: Vec3::new(40.0, 0.0, 0.0),
+        v_axis: Vec3::new(0.0, 0.0, -200.0),
+    };
+    let view_proj = Mat4::perspective_rh(60.0f32.to_radians(), 1.0, 0.5, 30.0)
+        * Mat4::look_at_rh(Vec3::ZERO, Vec3::new(0.0, -0.2, -1.0), Vec3::Y);
+    let checked = render_and_compare(&quad, view_proj);
+    assert!(checked > 1000);
+}
@@ -0,0 +1,79 @@
+//! Geometry and shaders shared between the integration tests.
+
+// Each test crate compiles this module separately, and uses only part of it
+#![allow(dead_code)]
+
+use glam::{Mat4, UVec3, Vec2, Vec3, Vec4};
+use softrender::shader::Shader;
+
+/// Indices of the two counter-clockwise triangles making up a rectangle from `rect_xy` or `rect_xz`
+pub const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+/// A rectangle in the XY plane at `z`, facing +Z, with corners at `min` and `max`
+pub fn rect_xy(min: Vec2, max: Vec2, z: f32) -> Vec<Vec3> {
+    [
+        (min.x, min.y),
+        (max.x, min.y),
+        (max.x, max.y),
+        (min.x, max.y),
+    ]
+    .map(|(x, y)| Vec3::new(x, y, z))
+    .to_vec()
+}
+
+/// A rectangle in the XZ plane at `y`, facing +Y, with corners at `min` and `max`
+pub fn rect_xz(min: Vec2, max: Vec2, y: f32) -> Vec<Vec3> {
+    [
+        (min.x, max.y),
+        (max.x, max.y),
+        (max.x, min.y),
+        (min.x, min.y),
+    ]
+    .map(|(x, z)| Vec3::new(x, y, z))
+    .to_vec()
+}
+
+/// Transforms positions by `mvp`, and panics if any fragment is shaded
+pub struct NoFragmentShader {
+    pub mvp: Mat4,
+}
+
+impl Shader<Vec3, ()> for NoFragmentShader {
+    fn vertex(&self, vertex: &Vec3) -> (Vec4, ()) {
+        (self.mvp * vertex.extend(1.0), ())
+    }
+
+    fn fragment(&self, _inputs: ()) -> UVec3 {
+        panic!("depth-only draws must not run the fragment shader")
+    }
+}
+
+/// A vertex placed directly in clip space, with a 0-1 color
+#[derive(Debug, Clone, Copy)]
+pub struct ClipVertex {
+    pub position: Vec4,
+    pub color: Vec3,
+}
+
+impl ClipVertex {
+    /// A vertex at the given normalized device coordinates, scaled by `w` into clip space
+    pub fn new(ndc: Vec3, w: f32, color: Vec3) -> Self {
+        ClipVertex {
+            position: (ndc * w).extend(w),
+            color,
+        }
+    }
+}
+
+/// Passes clip-space positions through, and shades fragments with their interpolated color
+pub struct ColorShader;
+
+impl Shader<ClipVertex, Vec3> for ColorShader {
+    fn vertex(&self, vertex: &ClipVertex) -> (Vec4, Vec3) {
+        (vertex.position, vertex.color)
+    }
+
+    fn fragment(&self, color: Vec3) -> UVec3 {
+        (color * 255.0).round().as_uvec3()
+    }
+}
@@ -0,0 +1,171 @@
+//! Tests for sampling cubemaps across their seams, drawing skyboxes and rendering into cubemaps.
+
+use glam::{Mat4, Vec2, Vec3};
+use softrender::{
+    camera::Camera,
+    math::{reflect, refract},
+    renderer::Renderer,
+    shaders::UnlitShader,
+    texture::{CubeFace, Cubemap, Texture},
+};
+
+const EPSILON: f32 = 1e-5;
+
+/// A cubemap with every face a solid color: red for +X, blue for +Z and black elsewhere
+fn colored_faces(size: u32) -> Cubemap {
+    Cubemap::new(CubeFace::ALL.map(|face| {
+        let color = match face {
+            CubeFace::POSITIVE_X => Vec3::X,
+            CubeFace::POSITIVE_Z => Vec3::Z,
+            _ => Vec3::ZERO,
+        };
+        Texture::new(size, size, vec![color; (size * size) as usize])
+    }))
+}
+
+#[test]
+fn cube_faces_round_trip() {
+    for face in CubeFace::ALL {
+        for uv in [
+            Vec2::new(0.5, 0.5),
+            Vec2::new(0.1, 0.8),
+            Vec2::new(0.9, 0.3),
+        ] {
+            let (found, found_uv) = CubeFace::from_direction(face.direction(uv) * 3.0);
+            assert_eq!(found, face);
+            assert!(
+                found_uv.abs_diff_eq(uv, EPSILON),
+                "{face:?} {uv} {found_uv}"
+            );
+        }
+    }
+    let (face, uv) = CubeFace::from_direction(Vec3::new(0.2, -5.0, 0.1));
+    assert_eq!(face, CubeFace::NEGATIVE_Y);
+    assert!(uv.abs_diff_eq(Vec2::new(0.52, 0.51), EPSILON));
+}
+
+#[test]
+fn sampling_blends_across_seams() {
+    let cubemap = colored_faces(2);
+    assert_eq!(cubemap.sample_nearest(Vec3::new(1.0, 0.2, 0.3)), Vec3::X);
+
+    // On the edge between +Z and +X, both faces are blended evenly, whichever face is sampled
+    let from_z = cubemap.sample(Vec3::new(0.999, 0.0, 1.0));
+    let from_x = cubemap.sample(Vec3::new(1.0, 0.0, 0.999));
+    for color in [from_z, from_x] {
+        assert!(
+            color.abs_diff_eq(Vec3::new(0.5, 0.0, 0.5), 0.01),
+            "{color}"
+        );
+    }
+    // Away from the edges, only the one face is seen
+    assert_eq!(cubemap.sample(Vec3::Z), Vec3::Z);
+}
+
+#[test]
+fn reflection_and_refraction() {
+    let incident = Vec3::new(1.0, -1.0, 0.0).normalize();
+    assert!(reflect(incident, Vec3::Y).abs_diff_eq(Vec3::new(1.0, 1.0, 0.0).normalize(), EPSILON));
+    // Equal refractive indices don't bend the ray at all
+    assert!(refract(incident, Vec3::Y, 1.0)
+        .unwrap()
+        .abs_diff_eq(incident, EPSILON));
+    // Entering a denser medium bends the ray towards the normal, following Snell's law
+    let refracted = refract(incident, Vec3::Y, 1.0 / 1.5).unwrap();
+    assert!((refracted.x - incident.x / 1.5).abs() < EPSILON);
+    assert!((refracted.length() - 1.0).abs() < EPSILON);
+    // Leaving it at a grazing angle reflects the ray entirely
+    assert!(refract(incident, Vec3::Y, 1.5).is_none());
+
+    let cubemap = colored_faces(4);
+    let looking_down_z = Vec3::new(0.1, 0.0, -1.0).normalize();
+    assert_eq!(
+        cubemap.sample_reflection(looking_down_z, Vec3::Z),
+        Vec3::Z
+    );
+    assert_eq!(
+        cubemap.sample_refraction(looking_down_z, Vec3::Z, 1.0),
+        Vec3::ZERO
+    );
+    let grazing = Vec3::new(1.0, 0.0, -0.2);
+    assert_eq!(cubemap.sample_refraction(grazing, Vec3::Z, 1.5), Vec3::X);
+}
+
+#[test]
+fn skybox_fills_the_background() {
+    let skybox = colored_faces(4);
+    let mut renderer = Renderer::new(32, 32);
+    renderer.clear_framebuffer(0);
+    // A green quad covering the left half of the view
+    let vertices = [(-1.0, -1.0), (0.0, -1.0), (0.0, 1.0), (-1.0, 1.0)]
+        .map(|(x, y)| Vec3::new(x, y, 0.5))
+        .to_vec();
+    let mut shader = UnlitShader {
+        mvp: Mat4::IDENTITY,
+        color: Vec3::Y,
+    };
+    renderer.draw(&mut shader, &vertices, &[0, 1, 2, 0, 2, 3]);
+
+    // Looking down +X, with the camera moved away from the origin, which makes no difference
+    let mut camera = Camera::perspective(1.0, 1.0, 0.1, 10.0, Vec3::new(5.0, 2.0, 1.0));
+    camera.look_at(Vec3::new(6.0, 2.0, 1.0), Vec3::Y);
+    let fb = renderer.draw_skybox(&skybox, camera.view_projection_matrix());
+    assert_eq!(fb.get_pixel(8, 16), 0x00FF00);
+    assert_eq!(fb.get_pixel(24, 16), 0xFF0000);
+    assert_eq!(renderer.get_depth_buffer().get_pixel(24, 16), 1.0);
+}
+
+#[test]
+fn empty_cubemaps_get_one_texel_per_face() {
+    let cubemap = Cubemap::from_fn(0, |direction| direction.abs());
+    assert_eq!(cubemap.get_size(), 1);
+    // The single texel of each face is evaluated along the face's axis
+    assert!(cubemap.sample(Vec3::X).abs_diff_eq(Vec3::X, EPSILON));
+    assert!(cubemap.sample(Vec3::NEG_Y).abs_diff_eq(Vec3::Y, EPSILON));
+}
+
+#[test]
+fn rendered_cubemaps_line_up_with_their_faces() {
+    // Rendering a skybox into a cubemap reproduces it texel for texel
+    let source = Cubemap::from_fn(8, |direction| direction * 0.5 + 0.5);
+    let rendered = Cubemap::render(8, Vec3::ZERO, 0.1, 10.0, |renderer, _, view_proj| {
+        renderer.draw_skybox(&source, view_proj);
+        Ok(())
+    })
+    .unwrap();
+    for face in CubeFace::ALL {
+        for y in 0..8 {
+            for x in 0..8 {
+                let expected = source.get_face(face).texel(x, y);
+                let actual = rendered.get_face(face).texel(x, y);
+                assert!(
+                    actual.abs_diff_eq(expected, 1.0 / 255.0),
+                    "{face:?} ({x}, {y}): expected {expected}, got {actual}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn geometry_is_rendered_into_the_facing_face() {
+    // A red square two units along +X, facing the origin
+    let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .map(|(z, y)| Vec3::new(2.0, y, z))
+        .to_vec();
+    let mut faces_drawn = Vec::new();
+    let cubemap = Cubemap::render(16, Vec3::ZERO, 0.1, 10.0, |renderer, face, view_proj| {
+        faces_drawn.push(face);
+        let mut shader = UnlitShader {
+            mvp: view_proj,
+            color: Vec3::X,
+        };
+        renderer.try_draw(&mut shader, &vertices, &[0, 1, 2, 0, 2, 3])?;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(faces_drawn, CubeFace::ALL);
+    assert_eq!(cubemap.sample(Vec3::X), Vec3::X);
+    assert_eq!(cubemap.sample(Vec3::NEG_X), Vec3::ZERO);
+    assert_eq!(cubemap.sample(Vec3::Z), Vec3::ZERO);
+}
@@ -0,0 +1,167 @@
+//! Tests for bounding volumes and frustum-culled draws.
+
+#[macro_use]
+extern crate softrender_derive;
+
+use glam::{Mat4, UVec3, Vec3, Vec4};
+use softrender::{
+    math::{Aabb, BoundingSphere, BoundingVolume, Frustum},
+    renderer::{CullStats, Renderer},
+    shader::{Barycentric, Shader},
+};
+
+const BACKGROUND: u32 = 0x000000;
+
+#[derive(Clone, Barycentric)]
+struct VertexOut {
+    unused: f32,
+}
+
+/// Fills every fragment with white, and counts how many vertices were shaded
+struct CountingShader {
+    mvp: Mat4,
+    vertices_shaded: std::cell::Cell<usize>,
+}
+
+impl Shader<Vec3, VertexOut> for CountingShader {
+    fn vertex(&self, vertex: &Vec3) -> (Vec4, VertexOut) {
+        self.vertices_shaded.set(self.vertices_shaded.get() + 1);
+        (self.mvp * vertex.extend(1.0), VertexOut { unused: 0.0 })
+    }
+
+    fn fragment(&self, _inputs: VertexOut) -> UVec3 {
+        UVec3::splat(255)
+    }
+}
+
+fn view_proj() -> Mat4 {
+    Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0)
+        * Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y)
+}
+
+fn triangle() -> ([Vec3; 3], [u32; 3]) {
+    (
+        [
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ],
+        [0, 1, 2],
+    )
+}
+
+#[test]
+fn aabb_from_points() {
+    let aabb = Aabb::from_points([
+        Vec3::new(1.0, -2.0, 0.5),
+        Vec3::new(-1.0, 3.0, 0.0),
+        Vec3::new(0.0, 0.0, -4.0),
+    ])
+    .unwrap();
+    assert_eq!(aabb.min, Vec3::new(-1.0, -2.0, -4.0));
+    assert_eq!(aabb.max, Vec3::new(1.0, 3.0, 0.5));
+    assert_eq!(aabb.center(), Vec3::new(0.0, 0.5, -1.75));
+    assert!(aabb.contains_point(Vec3::ZERO));
+    assert!(!aabb.contains_point(Vec3::new(0.0, 0.0, 1.0)));
+    assert!(Aabb::from_points([]).is_none());
+}
+
+#[test]
+fn transformed_aabb_contains_transformed_corners() {
+    let aabb = Aabb::new(Vec3::new(-1.0, -2.0, -3.0), Vec3::new(2.0, 1.0, 0.5));
+    let transform = Mat4::from_translation(Vec3::new(4.0, -1.0, 2.0))
+        * Mat4::from_rotation_y(0.7)
+        * Mat4::from_scale(Vec3::new(2.0, 1.0, 0.5));
+    let transformed = aabb.transformed(transform);
+    let corners = (0..8).map(|i| {
+        Vec3::select(
+            glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+            aabb.max,
+            aabb.min,
+        )
+    });
+    let exact = Aabb::from_points(corners.map(|c| transform.transform_point3(c))).unwrap();
+    assert!(transformed.min.abs_diff_eq(exact.min, 1e-5));
+    assert!(transformed.max.abs_diff_eq(exact.max, 1e-5));
+}
+
+#[test]
+fn bounding_sphere_contains_points() {
+    let points = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(-3.0, 2.0, 0.0),
+        Vec3::new(0.0, 0.0, 4.0),
+    ];
+    let sphere = BoundingSphere::from_points(points).unwrap();
+    for point in points {
+        assert!(point.distance(sphere.center) <= sphere.radius + 1e-5);
+    }
+
+    let scaled = sphere.transformed(Mat4::from_scale(Vec3::new(1.0, 3.0, 2.0)));
+    assert!((scaled.radius - sphere.radius * 3.0).abs() < 1e-5);
+}
+
+#[test]
+fn volumes_are_tested_against_frustum() {
+    let frustum = Frustum::from_matrix(view_proj());
+    let visible = Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5));
+    let behind = Aabb::new(Vec3::new(-0.5, -0.5, 6.0), Vec3::new(0.5, 0.5, 7.0));
+    let beside = Aabb::new(Vec3::new(10.0, -0.5, -0.5), Vec3::new(11.0, 0.5, 0.5));
+    // Straddles the left plane, so must be kept even though its center is outside
+    let straddling = Aabb::new(Vec3::new(-5.0, -0.5, -0.5), Vec3::new(-2.0, 0.5, 0.5));
+
+    assert!(visible.intersects_frustum(&frustum));
+    assert!(!behind.intersects_frustum(&frustum));
+    assert!(!beside.intersects_frustum(&frustum));
+    assert!(straddling.intersects_frustum(&frustum));
+    assert!(BoundingSphere::from_aabb(&visible).intersects_frustum(&frustum));
+    assert!(!BoundingSphere::from_aabb(&beside).intersects_frustum(&frustum));
+}
+
+#[test]
+fn culled_draws_skip_shading_and_are_counted() {
+    let mut renderer = Renderer::new(64, 64);
+    renderer.clear_framebuffer(BACKGROUND);
+    let (vertices, indices) = triangle();
+    let bounds = Aabb::from_points(vertices).unwrap();
+
+    let visible = Mat4::IDENTITY;
+    let offscreen = Mat4::from_translation(Vec3::new(50.0, 0.0, 0.0));
+    for model in [visible, offscreen, offscreen] {
+        let mvp = view_proj() * model;
+        let mut shader = CountingShader {
+            mvp,
+            vertices_shaded: Default::default(),
+        };
+        renderer.draw_culled(&mut shader, &vertices, &indices, &bounds, mvp);
+        let expected = if model == visible { 3 } else { 0 };
+        assert_eq!(shader.vertices_shaded.get(), expected);
+    }
+
+    assert_eq!(
+        renderer.get_cull_stats(),
+        CullStats {
+            drawn: 1,
+            culled: 2
+        }
+    );
+    assert_eq!(renderer.get_color_buffer().get_pixel(32, 32), 0xFFFFFF);
+
+    renderer.clear_framebuffer(BACKGROUND);
+    assert_eq!(renderer.get_cull_stats(), CullStats::default());
+}
+
+#[test]
+fn culled_draws_still_validate_indices() {
+    let mut renderer = Renderer::new(64, 64);
+    let (vertices, _) = triangle();
+    let bounds = Aabb::from_points(vertices).unwrap();
+    let mvp = view_proj() * Mat4::from_translation(Vec3::new(50.0, 0.0, 0.0));
+    let mut shader = CountingShader {
+        mvp,
+        vertices_shaded: Default::default(),
+    };
+    assert!(renderer
+        .try_draw_culled(&mut shader, &vertices, &[0, 1, 7], &bounds, mvp)
+        .is_err());
+}
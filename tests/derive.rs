@@ -0,0 +1,167 @@
+//! Tests for `#[derive(Barycentric)]` on every shape of struct, its interpolation qualifiers, and the
+//! errors it reports.
+
+#[macro_use]
+extern crate softrender_derive;
+
+use std::marker::PhantomData;
+
+use glam::{UVec3, Vec2, Vec3, Vec4};
+use softrender::{
+    math::unpack_color,
+    renderer::{Renderer, Topology},
+    shader::{Barycentric, Shader},
+};
+
+/// Pixel centers sit at whole coordinates, so an odd size puts one on the center of the view
+const SIZE: u32 = 65;
+const CENTER: u32 = SIZE / 2;
+
+#[derive(Clone, Barycentric)]
+struct Qualified {
+    smooth: f32,
+    #[noperspective]
+    linear: f32,
+    #[flat]
+    flat: f32,
+}
+
+struct QualifiedVertex {
+    position: Vec4,
+    value: f32,
+    flat: f32,
+}
+
+/// Shades the smooth, noperspective and flat attributes into red, green and blue
+struct QualifiedShader;
+
+impl Shader<QualifiedVertex, Qualified> for QualifiedShader {
+    fn vertex(&self, vertex: &QualifiedVertex) -> (Vec4, Qualified) {
+        let attributes = Qualified {
+            smooth: vertex.value,
+            linear: vertex.value,
+            flat: vertex.flat,
+        };
+        (vertex.position, attributes)
+    }
+
+    fn fragment(&self, inputs: Qualified) -> UVec3 {
+        (Vec3::new(inputs.smooth, inputs.linear, inputs.flat) * 255.0)
+            .round()
+            .as_uvec3()
+    }
+}
+
+/// A triangle whose bottom edge runs across the middle row of the view. Along that edge, the value
+/// rises from zero to one, and its far end is three times as far from the camera.
+fn triangle() -> [QualifiedVertex; 3] {
+    [
+        (Vec3::new(-0.9, 0.0, 0.5), 1.0, 0.0, 0.2),
+        (Vec3::new(0.9, 0.0, 0.5), 3.0, 1.0, 0.6),
+        (Vec3::new(0.0, 0.8, 0.5), 1.0, 0.0, 1.0),
+    ]
+    .map(|(ndc, w, value, flat)| QualifiedVertex {
+        position: (ndc * w).extend(w),
+        value,
+        flat,
+    })
+}
+
+#[test]
+fn qualifiers_pick_how_fields_are_interpolated() {
+    for (topology, indices) in [
+        (Topology::TRIANGLES, &[0, 1, 2][..]),
+        (Topology::LINES, &[0, 1][..]),
+    ] {
+        let mut renderer = Renderer::new(SIZE, SIZE);
+        renderer.set_topology(topology);
+        renderer.clear_framebuffer(0);
+        let fb = renderer.draw(&mut QualifiedShader, &triangle(), indices);
+
+        // Halfway along the edge on screen, the noperspective field is halfway between its ends,
+        // while the smooth one is a quarter of the way: (0.5 / 3) / (0.5 / 1 + 0.5 / 3) = 0.25.
+        // The flat field keeps the value of the first, provoking, vertex.
+        let color = unpack_color(fb.get_pixel(CENTER, CENTER));
+        assert!(color.x.abs_diff(64) <= 1, "{color}");
+        assert!(color.y.abs_diff(128) <= 1, "{color}");
+        assert_eq!(color.z, 51);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Barycentric)]
+struct Pair(f32, #[flat] Vec2);
+
+#[derive(Clone, Debug, PartialEq, Barycentric)]
+struct Unit;
+
+#[derive(Clone, Debug, PartialEq, Barycentric)]
+struct Labelled<T> {
+    value: T,
+    #[barycentric(skip)]
+    label: String,
+}
+
+/// Barycentric whatever `Tag` is, since the only field using it is skipped, and `PhantomData` is
+/// `Clone` for any type
+#[derive(Clone, Barycentric)]
+struct Tagged<T, Tag> {
+    value: T,
+    #[barycentric(skip)]
+    tag: PhantomData<Tag>,
+}
+
+/// Neither `Clone` nor `Barycentric`
+struct NotClone;
+
+const WEIGHTS: Vec3 = Vec3::new(0.5, 0.25, 0.25);
+const LINE_WEIGHTS: Vec2 = Vec2::new(0.75, 0.25);
+
+#[test]
+fn tuple_structs_interpolate_by_index() {
+    let [a, b, c] = [0.0, 4.0, 8.0].map(|value| Pair(value, Vec2::splat(value)));
+    assert_eq!(a.interpolated(WEIGHTS, &b, &c), Pair(3.0, Vec2::ZERO));
+    assert_eq!(a.line_interpolated(LINE_WEIGHTS, &b), Pair(1.0, Vec2::ZERO));
+    // Qualifiers take flat fields from the provoking vertex instead
+    let qualified = a.interpolated_qualified(WEIGHTS, WEIGHTS, &b, &c, &c);
+    assert_eq!(qualified, Pair(3.0, Vec2::splat(8.0)));
+}
+
+#[test]
+fn unit_structs_interpolate_to_themselves() {
+    assert_eq!(Unit.interpolated(WEIGHTS, &Unit, &Unit), Unit);
+    assert_eq!(
+        Unit.line_interpolated_qualified(LINE_WEIGHTS, LINE_WEIGHTS, &Unit, &Unit),
+        Unit
+    );
+}
+
+#[test]
+fn generic_structs_skip_fields() {
+    let [a, b, c] = [("a", 0.0), ("b", 4.0), ("c", 8.0)].map(|(label, value)| Labelled {
+        value: Vec3::splat(value),
+        label: label.to_string(),
+    });
+    let interpolated = a.interpolated(WEIGHTS, &b, &c);
+    assert_eq!(interpolated.value, Vec3::splat(3.0));
+    assert_eq!(interpolated.label, "a");
+    assert_eq!(a.line_interpolated(LINE_WEIGHTS, &b).label, "a");
+    let qualified = a.line_interpolated_qualified(LINE_WEIGHTS, LINE_WEIGHTS, &b, &b);
+    assert_eq!(qualified.value, Vec3::splat(1.0));
+    assert_eq!(qualified.label, "b");
+}
+
+#[test]
+fn bounds_follow_field_types() {
+    // Type parameters only used through fields that are Clone for any type need no bounds at all
+    let [a, b, c] = [0.0, 4.0, 8.0].map(|value| Tagged::<f32, NotClone> {
+        value,
+        tag: PhantomData,
+    });
+    assert_eq!(a.interpolated(WEIGHTS, &b, &c).value, 3.0);
+}
+
+#[test]
+fn invalid_derives_fail_to_compile() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,94 @@
+//! Tests for the shaded wireframe and hidden-line draw modes.
+
+mod common;
+
+use common::{rect_xy, ClipVertex, ColorShader, QUAD_INDICES};
+use glam::{Vec2, Vec3};
+use softrender::renderer::{DrawMode, Renderer};
+
+/// Pixel centers sit at whole coordinates, so ndc multiples of 1/32 land exactly on them
+const SIZE: u32 = 65;
+const CLEAR: u32 = 0x000040;
+
+fn renderer(mode: DrawMode) -> Renderer {
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.clear_framebuffer(CLEAR);
+    renderer.set_draw_mode(mode);
+    renderer
+}
+
+/// A right triangle with its corner at the bottom left, whose two short edges run along pixel
+/// centers at row and column 8
+fn corner_triangle(depth: f32, color: Vec3) -> Vec<ClipVertex> {
+    [(-0.75, -0.75), (0.75, -0.75), (-0.75, 0.75)]
+        .map(|(x, y)| ClipVertex::new(Vec3::new(x, y, depth), 1.0, color))
+        .to_vec()
+}
+
+fn square(min: Vec2, max: Vec2, depth: f32, color: Vec3) -> Vec<ClipVertex> {
+    rect_xy(min, max, depth)
+        .into_iter()
+        .map(|position| ClipVertex::new(position, 1.0, color))
+        .collect()
+}
+
+#[test]
+fn shaded_wireframe_outlines_edges() {
+    let mut renderer = renderer(DrawMode::SHADED_WIREFRAME);
+    renderer.set_wireframe_color(0xFFFF00);
+    let fb = renderer.draw(&mut ColorShader, &corner_triangle(0.5, Vec3::X), &[0, 1, 2]);
+    // Both short edges, and the long one
+    assert_eq!(fb.get_pixel(20, 8), 0xFFFF00);
+    assert_eq!(fb.get_pixel(8, 20), 0xFFFF00);
+    assert_eq!(fb.get_pixel(32, 32), 0xFFFF00);
+    // The interior is shaded as usual, and the outside untouched
+    assert_eq!(fb.get_pixel(20, 20), 0xFF0000);
+    assert_eq!(fb.get_pixel(20, 9), 0xFF0000);
+    assert_eq!(fb.get_pixel(20, 7), CLEAR);
+
+    // Wider outlines reach further into the triangle
+    renderer.clear_framebuffer(CLEAR);
+    renderer.set_line_width(5.0);
+    let fb = renderer.draw(&mut ColorShader, &corner_triangle(0.5, Vec3::X), &[0, 1, 2]);
+    assert_eq!(fb.get_pixel(20, 10), 0xFFFF00);
+    assert_eq!(fb.get_pixel(20, 11), 0xFF0000);
+}
+
+#[test]
+fn clipped_triangles_only_outline_their_own_edges() {
+    let mut renderer = renderer(DrawMode::SHADED_WIREFRAME);
+    // A triangle reaching far past the sides and top of the view, whose only edge on screen runs
+    // along row 16. Clipping turns what is left into a fan, whose inner edges must not be outlined.
+    let triangle = [(-3.0, -0.5), (3.0, -0.5), (0.0, 4.0)]
+        .map(|(x, y)| ClipVertex::new(Vec3::new(x, y, 0.5), 1.0, Vec3::X))
+        .to_vec();
+    let fb = renderer.draw(&mut ColorShader, &triangle, &[0, 1, 2]);
+    assert_eq!(fb.get_pixel(32, 16), 0xFFFFFF);
+    for y in 17..SIZE - 1 {
+        for x in 1..SIZE - 1 {
+            assert_eq!(fb.get_pixel(x, y), 0xFF0000, "({x}, {y})");
+        }
+    }
+}
+
+#[test]
+fn hidden_line_hides_occluded_edges() {
+    let mut renderer = renderer(DrawMode::HIDDEN_LINE);
+    // A red triangle behind, then a green square in front covering part of its bottom edge
+    renderer.draw(&mut ColorShader, &corner_triangle(0.6, Vec3::X), &[0, 1, 2]);
+    let front = square(Vec2::new(0.0, -0.875), Vec2::new(0.875, 0.5), 0.3, Vec3::Y);
+    let fb = renderer.draw(&mut ColorShader, &front, &QUAD_INDICES);
+
+    // Edges are colored by the shader
+    assert_eq!(fb.get_pixel(16, 8), 0xFF0000);
+    assert_eq!(fb.get_pixel(32, 20), 0x00FF00);
+    // Interiors are filled with the clear color, hiding the edge behind the square
+    assert_eq!(fb.get_pixel(20, 20), CLEAR);
+    assert_eq!(fb.get_pixel(40, 8), CLEAR);
+    assert_eq!(fb.get_pixel(50, 12), CLEAR);
+    // and still write their depth, so edges drawn later behind them are hidden too
+    assert_eq!(renderer.get_depth_buffer().get_pixel(50, 12), 0.3);
+    let fb = renderer.draw(&mut ColorShader, &corner_triangle(0.8, Vec3::Z), &[0, 1, 2]);
+    assert_eq!(fb.get_pixel(40, 8), CLEAR);
+    assert_eq!(fb.get_pixel(8, 20), 0xFF0000);
+}
@@ -0,0 +1,98 @@
+//! Tests for the errors returned by the fallible drawing and framebuffer access methods.
+
+mod common;
+
+use common::{rect_xy, ClipVertex, ColorShader, QUAD_INDICES};
+use glam::{Vec2, Vec3};
+use softrender::{
+    error::Error,
+    fb::Framebuffer,
+    renderer::{Renderer, Topology},
+};
+
+fn quad() -> Vec<ClipVertex> {
+    rect_xy(Vec2::splat(-0.5), Vec2::splat(0.5), 0.5)
+        .into_iter()
+        .map(|position| ClipVertex::new(position, 1.0, Vec3::X))
+        .collect()
+}
+
+fn untouched(renderer: &Renderer) -> bool {
+    renderer
+        .get_color_buffer()
+        .get_raw()
+        .iter()
+        .all(|&color| color == 0)
+        && renderer
+            .get_depth_buffer()
+            .get_raw()
+            .iter()
+            .all(|&depth| depth == 1.0)
+}
+
+#[test]
+fn draws_reject_indices_past_the_vertex_buffer() {
+    let mut renderer = Renderer::new(16, 16);
+    renderer.clear_framebuffer(0);
+    // The first triangle is fine, but nothing is drawn when any index is out of bounds
+    let result = renderer.try_draw(&mut ColorShader, &quad(), &[0, 1, 2, 0, 2, 4]);
+    assert!(matches!(
+        result,
+        Err(Error::IndexOutOfBounds {
+            position: 5,
+            index: 4,
+            vertex_count: 4
+        })
+    ));
+    assert!(untouched(&renderer));
+
+    assert!(renderer
+        .try_draw(&mut ColorShader, &quad(), &QUAD_INDICES)
+        .is_ok());
+    assert!(!untouched(&renderer));
+}
+
+#[test]
+fn draws_reject_incomplete_primitives() {
+    let mut renderer = Renderer::new(16, 16);
+    renderer.clear_framebuffer(0);
+    let result = renderer.try_draw(&mut ColorShader, &quad(), &QUAD_INDICES[..5]);
+    assert!(matches!(
+        result,
+        Err(Error::IncompletePrimitive {
+            index_count: 5,
+            vertices_per_primitive: 3
+        })
+    ));
+    assert!(untouched(&renderer));
+
+    // Any number of indices makes whole points
+    renderer.set_topology(Topology::POINTS);
+    assert!(renderer
+        .try_draw(&mut ColorShader, &quad(), &QUAD_INDICES[..5])
+        .is_ok());
+}
+
+#[test]
+fn pixels_outside_the_framebuffer_are_errors() {
+    let mut fb = Framebuffer::<u32>::new(4, 3);
+    assert!(fb.try_plot_pixel(3, 2, 7).is_ok());
+    assert_eq!(fb.try_get_pixel(3, 2).unwrap(), 7);
+
+    for (x, y) in [(4, 0), (0, 3), (u32::MAX, u32::MAX)] {
+        assert!(matches!(
+            fb.try_plot_pixel(x, y, 1),
+            Err(Error::PixelOutOfBounds {
+                width: 4,
+                height: 3,
+                ..
+            })
+        ));
+        assert!(matches!(
+            fb.try_get_pixel(x, y),
+            Err(Error::PixelOutOfBounds { x: ex, y: ey, .. }) if (ex, ey) == (x, y)
+        ));
+    }
+    // Failed writes leave the framebuffer untouched
+    assert_eq!(fb.get_raw().iter().filter(|&&value| value != 0).count(), 1);
+}
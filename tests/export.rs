@@ -0,0 +1,131 @@
+//! Tests that exported images decode correctly, with pixel (0, 0) at the bottom left.
+
+use std::io::Cursor;
+
+use image::ImageFormat as DecodeFormat;
+use softrender::{
+    error::Error,
+    export::{DepthFormat, ImageFormat},
+    fb::Framebuffer,
+};
+
+const WIDTH: u32 = 5;
+const HEIGHT: u32 = 3;
+
+/// A color buffer with a distinct color in three of its corners
+fn color_buffer() -> Framebuffer<u32> {
+    let mut fb = Framebuffer::new(WIDTH, HEIGHT);
+    fb.fill(0x202020);
+    fb.plot_pixel(0, 0, 0xFF0000);
+    fb.plot_pixel(WIDTH - 1, 0, 0x00FF00);
+    fb.plot_pixel(0, HEIGHT - 1, 0x0000FF);
+    fb
+}
+
+fn decode(fb: &Framebuffer<u32>, format: ImageFormat, decode_as: DecodeFormat) -> image::RgbImage {
+    let mut bytes = Vec::new();
+    fb.write_image(&mut bytes, format).unwrap();
+    image::load(Cursor::new(bytes), decode_as)
+        .unwrap()
+        .into_rgb8()
+}
+
+fn check_orientation(format: ImageFormat, decode_as: DecodeFormat) {
+    let image = decode(&color_buffer(), format, decode_as);
+    assert_eq!(image.dimensions(), (WIDTH, HEIGHT));
+    // Decoded images have their origin at the top left
+    assert_eq!(image.get_pixel(0, HEIGHT - 1).0, [255, 0, 0], "{format:?}");
+    assert_eq!(
+        image.get_pixel(WIDTH - 1, HEIGHT - 1).0,
+        [0, 255, 0],
+        "{format:?}"
+    );
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 255], "{format:?}");
+    assert_eq!(image.get_pixel(WIDTH - 1, 0).0, [32, 32, 32], "{format:?}");
+}
+
+#[test]
+fn ppm_orientation() {
+    check_orientation(ImageFormat::PPM, DecodeFormat::Pnm);
+}
+
+#[test]
+fn pam_orientation() {
+    check_orientation(ImageFormat::PAM, DecodeFormat::Pnm);
+}
+
+#[test]
+fn bmp_orientation() {
+    check_orientation(ImageFormat::BMP, DecodeFormat::Bmp);
+}
+
+#[test]
+fn tga_orientation() {
+    check_orientation(ImageFormat::TGA, DecodeFormat::Tga);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn png_orientation() {
+    check_orientation(ImageFormat::PNG, DecodeFormat::Png);
+}
+
+#[test]
+fn view_exports_only_its_region() {
+    let fb = color_buffer();
+    let mut bytes = Vec::new();
+    fb.view(0, 0, 2, 1)
+        .unwrap()
+        .write_image(&mut bytes, ImageFormat::PPM)
+        .unwrap();
+    assert_eq!(bytes, b"P6\n2 1\n255\n\xFF\x00\x00\x20\x20\x20");
+}
+
+#[test]
+fn depth_pgm_is_normalized() {
+    let mut fb = Framebuffer::<f32>::new(2, 2);
+    fb.fill(1.0);
+    fb.plot_pixel(0, 0, 0.5);
+
+    let mut bytes = Vec::new();
+    fb.write_depth_image(&mut bytes, DepthFormat::PGM).unwrap();
+    let image = image::load(Cursor::new(bytes), DecodeFormat::Pnm)
+        .unwrap()
+        .into_luma16();
+    assert_eq!(image.get_pixel(0, 1).0, [0]);
+    assert_eq!(image.get_pixel(0, 0).0, [65535]);
+    assert_eq!(image.get_pixel(1, 1).0, [65535]);
+}
+
+#[test]
+fn depth_pfm_stores_raw_floats_bottom_up() {
+    let mut fb = Framebuffer::<f32>::new(2, 2);
+    fb.fill(1.0);
+    fb.plot_pixel(1, 0, 0.25);
+
+    let mut bytes = Vec::new();
+    fb.write_depth_image(&mut bytes, DepthFormat::PFM).unwrap();
+    let header = b"Pf\n2 2\n-1.0\n";
+    assert_eq!(&bytes[..header.len()], header);
+    let floats: Vec<f32> = bytes[header.len()..]
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    assert_eq!(floats, [1.0, 0.25, 1.0, 1.0]);
+}
+
+#[test]
+fn format_from_extension() {
+    assert_eq!(
+        ImageFormat::from_path("out/frame.PPM").unwrap(),
+        ImageFormat::PPM
+    );
+    assert_eq!(
+        DepthFormat::from_path("depth.pfm").unwrap(),
+        DepthFormat::PFM
+    );
+    assert!(matches!(
+        ImageFormat::from_path("frame.gif"),
+        Err(Error::UnsupportedFormat { .. })
+    ));
+}
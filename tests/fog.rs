@@ -0,0 +1,226 @@
+//! Tests for fog modes, and for the renderer blending fog into shaded fragments.
+
+mod common;
+
+use common::{rect_xy, rect_xz, NoFragmentShader, QUAD_INDICES};
+use glam::{Mat4, UVec3, Vec2, Vec3};
+use softrender::{
+    fog::{Fog, FogMode},
+    math::unpack_color,
+    renderer::{DrawMode, Renderer, Topology},
+    shaders::UnlitShader,
+};
+
+const EPSILON: f32 = 1e-4;
+
+/// A square facing the camera, `depth` units in front of it, covering the whole view
+fn wall(depth: f32) -> Vec<Vec3> {
+    rect_xy(Vec2::splat(-depth), Vec2::splat(depth), -depth)
+}
+
+const FOV_Y: f32 = 1.0;
+
+fn projection() -> Mat4 {
+    Mat4::perspective_rh(FOV_Y, 1.0, 0.1, 100.0)
+}
+
+/// Draws red geometry, returning the color at the given pixel
+fn draw_red_at(
+    renderer: &mut Renderer,
+    view_proj: Mat4,
+    vertices: &[Vec3],
+    indices: &[u32],
+    (x, y): (u32, u32),
+) -> UVec3 {
+    let mut shader = UnlitShader {
+        mvp: view_proj,
+        color: Vec3::X,
+    };
+    unpack_color(
+        renderer
+            .draw(&mut shader, vertices, indices)
+            .get_pixel(x, y),
+    )
+}
+
+fn draw_red(renderer: &mut Renderer, view_proj: Mat4, vertices: &[Vec3], indices: &[u32]) -> UVec3 {
+    draw_red_at(renderer, view_proj, vertices, indices, (8, 8))
+}
+
+#[test]
+fn fog_thickens_with_depth() {
+    let camera = Vec3::ZERO;
+    let linear = Fog::new(
+        FogMode::LINEAR {
+            start: 2.0,
+            end: 6.0,
+        },
+        Vec3::ONE,
+    );
+    assert_eq!(linear.amount(1.0, camera, Vec3::ZERO), 0.0);
+    assert!((linear.amount(3.0, camera, Vec3::ZERO) - 0.25).abs() < EPSILON);
+    assert_eq!(linear.amount(10.0, camera, Vec3::ZERO), 1.0);
+
+    let exponential = Fog::new(FogMode::EXPONENTIAL { density: 0.5 }, Vec3::ONE);
+    let squared = Fog::new(FogMode::EXPONENTIAL_SQUARED { density: 0.5 }, Vec3::ONE);
+    assert!(
+        (exponential.amount(2.0, camera, Vec3::ZERO) - (1.0 - (-1.0f32).exp())).abs() < EPSILON
+    );
+    assert!((squared.amount(2.0, camera, Vec3::ZERO) - (1.0 - (-1.0f32).exp())).abs() < EPSILON);
+    // Squaring keeps nearby geometry clearer, and distant geometry more hidden
+    assert!(squared.amount(1.0, camera, Vec3::ZERO) < exponential.amount(1.0, camera, Vec3::ZERO));
+    assert!(squared.amount(4.0, camera, Vec3::ZERO) > exponential.amount(4.0, camera, Vec3::ZERO));
+
+    let color = linear.apply(Vec3::ZERO, 0.25);
+    assert!(color.abs_diff_eq(Vec3::splat(0.25), EPSILON));
+}
+
+#[test]
+fn height_fog_gathers_along_the_ray() {
+    let fog = Fog::new(
+        FogMode::HEIGHT {
+            density: 0.5,
+            height: 0.0,
+            falloff: 1.0,
+        },
+        Vec3::ONE,
+    );
+    // Level with the fog's height, the density is constant along the ray
+    let level = fog.amount(0.0, Vec3::ZERO, Vec3::new(2.0, 0.0, 0.0));
+    assert!((level - (1.0 - (-1.0f32).exp())).abs() < EPSILON);
+    // The same distance further up passes through thinner fog, and further down through thicker
+    let above = fog.amount(0.0, Vec3::new(0.0, 2.0, 0.0), Vec3::new(2.0, 2.0, 0.0));
+    let below = fog.amount(0.0, Vec3::new(0.0, -2.0, 0.0), Vec3::new(2.0, -2.0, 0.0));
+    assert!(above < level && level < below);
+    // Looking down into the fog matches looking up out of it
+    let down = fog.amount(0.0, Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+    let up = fog.amount(0.0, Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+    assert!((down - up).abs() < EPSILON);
+    assert!(down > above);
+}
+
+#[test]
+fn renderer_blends_shaded_fragments_into_fog() {
+    let projection = projection();
+    let mut renderer = Renderer::new(16, 16);
+    renderer.set_fog(
+        Some(Fog::new(
+            FogMode::LINEAR {
+                start: 2.0,
+                end: 6.0,
+            },
+            Vec3::Z,
+        )),
+        Mat4::IDENTITY,
+        projection,
+    );
+
+    // In front of the fog, past it, and a quarter of the way in
+    for (depth, expected) in [(1.5, [255, 0, 0]), (8.0, [0, 0, 255]), (3.0, [191, 0, 64])] {
+        renderer.clear_framebuffer(0);
+        let color = draw_red(&mut renderer, projection, &wall(depth), &QUAD_INDICES);
+        assert_eq!(color, UVec3::from(expected), "{depth}");
+    }
+
+    // Lines and points are fogged too
+    renderer.clear_framebuffer(0);
+    renderer.set_topology(Topology::POINTS);
+    renderer.set_point_size(4.0);
+    let color = draw_red(
+        &mut renderer,
+        projection,
+        &[Vec3::new(0.0, 0.0, -8.0)],
+        &[0],
+    );
+    assert_eq!(color, UVec3::new(0, 0, 255));
+    renderer.clear_framebuffer(0);
+    renderer.set_topology(Topology::LINES);
+    renderer.set_line_width(3.0);
+    let line = [Vec3::new(-1.0, 0.0, -8.0), Vec3::new(1.0, 0.0, -8.0)];
+    assert_eq!(
+        draw_red(&mut renderer, projection, &line, &[0, 1]),
+        UVec3::new(0, 0, 255)
+    );
+
+    // Without fog, the shader's color is written as is
+    renderer.set_fog(None, Mat4::IDENTITY, projection);
+    assert!(renderer.get_fog().is_none());
+    renderer.clear_framebuffer(0);
+    renderer.set_topology(Topology::TRIANGLES);
+    let color = draw_red(&mut renderer, projection, &wall(8.0), &QUAD_INDICES);
+    assert_eq!(color, UVec3::new(255, 0, 0));
+}
+
+#[test]
+fn view_depth_is_interpolated_perspective_correctly() {
+    // Looking across a floor one unit below the eye, which recedes from one to forty units away
+    let floor = rect_xz(Vec2::new(-40.0, -40.0), Vec2::new(40.0, -1.0), -1.0);
+    let fog = Fog::new(
+        FogMode::LINEAR {
+            start: 0.0,
+            end: 8.0,
+        },
+        Vec3::Z,
+    );
+    // An odd size puts a row of pixel centers halfway down the lower half of the view
+    let mut renderer = Renderer::new(17, 17);
+    renderer.set_fog(Some(fog), Mat4::IDENTITY, projection());
+    renderer.clear_framebuffer(0);
+    let color = draw_red_at(&mut renderer, projection(), &floor, &QUAD_INDICES, (8, 4));
+
+    // The ray through that row drops one unit below the eye at twice the distance where the bottom
+    // of the view does
+    let depth = 2.0 / (FOV_Y / 2.0).tan();
+    let expected = fog.amount(depth, Vec3::ZERO, Vec3::ZERO);
+    assert!(
+        (color.z as f32 / 255.0 - expected).abs() < 0.01,
+        "{color} {expected}"
+    );
+}
+
+#[test]
+fn height_fog_in_the_renderer_follows_world_position() {
+    // Looking down at a floor from above, with fog lying on the floor
+    let eye = Vec3::new(0.0, 10.0, 0.0);
+    let view = Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::NEG_Z);
+    let view_proj = projection() * view;
+    let fog = Fog::new(
+        FogMode::HEIGHT {
+            density: 0.3,
+            height: 0.0,
+            falloff: 1.0,
+        },
+        Vec3::Z,
+    );
+    let mut renderer = Renderer::new(16, 16);
+    renderer.set_fog(Some(fog), view, projection());
+
+    let floor = |height: f32| rect_xz(Vec2::splat(-20.0), Vec2::splat(20.0), height);
+    let mut blue = |height: f32| {
+        renderer.clear_framebuffer(0);
+        let color = draw_red(&mut renderer, view_proj, &floor(height), &QUAD_INDICES);
+        let expected = fog.amount(10.0 - height, eye, Vec3::new(0.0, height, 0.0));
+        assert!(
+            (color.z as f32 / 255.0 - expected).abs() < 0.01,
+            "{height}: {color} {expected}"
+        );
+        color.z
+    };
+    // The lower floor sits deeper in the fog, and is hidden more
+    assert!(blue(-1.0) > blue(4.0));
+}
+
+#[test]
+fn depth_only_draws_are_not_fogged() {
+    let mut renderer = Renderer::new(16, 16);
+    renderer.set_fog(
+        Some(Fog::new(FogMode::EXPONENTIAL { density: 1.0 }, Vec3::ONE)),
+        Mat4::IDENTITY,
+        projection(),
+    );
+    renderer.clear_framebuffer(0x123456);
+    renderer.set_draw_mode(DrawMode::DEPTH_ONLY);
+    let mut shader = NoFragmentShader { mvp: projection() };
+    let cb = renderer.draw(&mut shader, &wall(4.0), &QUAD_INDICES);
+    assert!(cb.get_raw().iter().all(|&color| color == 0x123456));
+}
@@ -0,0 +1,420 @@
+//! Tests for the glTF loader, using small scenes built in memory.
+
+#![cfg(feature = "gltf")]
+
+use std::io::Cursor;
+
+use glam::{Affine3A, Mat4, Quat, UVec3, Vec2, Vec3, Vec4};
+use softrender::{
+    error::Error,
+    gltf::{AlphaMode, GltfScene},
+    math::unpack_color,
+    renderer::Renderer,
+};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// A 2x2 PNG with a red top left pixel, and the others white
+fn png_image() -> Vec<u8> {
+    let mut image = image::RgbImage::from_pixel(2, 2, image::Rgb([255, 255, 255]));
+    image.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+/// Buffer data for one triangle: three positions, three texture coordinates and three u16 indices
+fn triangle_buffer() -> Vec<u8> {
+    let mut data = Vec::new();
+    for value in [-1.0f32, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0] {
+        data.extend(value.to_le_bytes());
+    }
+    for value in [0.0f32, 1.0, 1.0, 1.0, 0.5, 0.0] {
+        data.extend(value.to_le_bytes());
+    }
+    for index in [0u16, 1, 2, 0] {
+        data.extend(index.to_le_bytes());
+    }
+    data
+}
+
+/// The JSON of a scene whose root is translated, with a scaled child holding a textured triangle
+fn scene_json(buffer_uri: Option<String>, buffer_len: usize, image_uri: String) -> String {
+    let uri = buffer_uri.map_or(String::new(), |uri| format!(r#""uri": "{uri}", "#));
+    format!(
+        r#"{{
+        "asset": {{ "version": "2.0" }},
+        "scene": 0,
+        "scenes": [{{ "nodes": [0] }}],
+        "nodes": [
+            {{ "name": "root", "translation": [1, 0, 0], "children": [1] }},
+            {{ "name": "child", "scale": [2, 2, 2], "mesh": 0 }}
+        ],
+        "meshes": [{{ "primitives": [{{
+            "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }},
+            "indices": 2,
+            "material": 0
+        }}] }}],
+        "materials": [{{
+            "name": "painted",
+            "pbrMetallicRoughness": {{
+                "baseColorFactor": [1, 1, 1, 1],
+                "baseColorTexture": {{ "index": 0 }},
+                "metallicFactor": 0.25,
+                "roughnessFactor": 0.75
+            }},
+            "alphaMode": "MASK",
+            "alphaCutoff": 0.3
+        }}],
+        "textures": [{{ "source": 0 }}],
+        "images": [{{ "uri": "{image_uri}" }}],
+        "buffers": [{{ {uri}"byteLength": {buffer_len} }}],
+        "bufferViews": [
+            {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+            {{ "buffer": 0, "byteOffset": 36, "byteLength": 24 }},
+            {{ "buffer": 0, "byteOffset": 60, "byteLength": 6 }}
+        ],
+        "accessors": [
+            {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+               "min": [-1, -1, 0], "max": [1, 1, 0] }},
+            {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }},
+            {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+        ]
+    }}"#
+    )
+}
+
+fn check_scene(scene: &GltfScene) {
+    assert_eq!(scene.roots, [0]);
+    let child = &scene.nodes[1];
+    assert_eq!(child.name.as_deref(), Some("child"));
+    assert_eq!(child.parent, Some(0));
+    assert_eq!(scene.nodes[0].children, [1]);
+    let expected =
+        Affine3A::from_scale_rotation_translation(Vec3::splat(2.0), Quat::IDENTITY, Vec3::X);
+    assert!(child.world_transform.abs_diff_eq(expected, 1e-6));
+
+    let mesh = &scene.meshes[child.mesh.unwrap()];
+    assert_eq!(mesh.indices, [0, 1, 2]);
+    assert_eq!(mesh.groups.len(), 1);
+    // Texture coordinates are flipped to run upwards, and missing normals face the front
+    assert_eq!(mesh.vertices[0].uv, Vec2::new(0.0, 0.0));
+    assert_eq!(mesh.vertices[2].uv, Vec2::new(0.5, 1.0));
+    assert_eq!(mesh.vertices[0].normal, Vec3::Z);
+    assert_eq!(mesh.vertices[0].tangent, Vec4::new(1.0, 0.0, 0.0, 1.0));
+
+    let material = scene.group_material(&mesh.groups[0]).unwrap();
+    assert_eq!(material.name.as_deref(), Some("painted"));
+    assert_eq!(material.metallic_factor, 0.25);
+    assert_eq!(material.roughness_factor, 0.75);
+    assert_eq!(material.alpha_mode, AlphaMode::MASK(0.3));
+
+    // The top left pixel of the image is the top left texel of the texture
+    let texture = &scene.textures[material.base_color_texture.unwrap()];
+    assert_eq!(texture.texel(0, 1), Vec3::X);
+    assert_eq!(texture.texel(0, 0), Vec3::ONE);
+
+    let pbr = scene.group_pbr_material(&mesh.groups[0]);
+    assert_eq!((pbr.metallic, pbr.roughness), (0.25, 0.75));
+    assert!(std::ptr::eq(pbr.base_color_texture.unwrap(), texture));
+    assert!(pbr.normal_texture.is_none());
+}
+
+#[test]
+fn loads_gltf_with_embedded_buffers() {
+    let buffer = triangle_buffer();
+    let json = scene_json(
+        Some(format!(
+            "data:application/octet-stream;base64,{}",
+            base64(&buffer)
+        )),
+        buffer.len(),
+        format!("data:image/png;base64,{}", base64(&png_image())),
+    );
+    check_scene(&GltfScene::from_slice(json.as_bytes()).unwrap());
+}
+
+#[test]
+fn loads_glb() {
+    let mut buffer = triangle_buffer();
+    let buffer_len = buffer.len();
+    let json = scene_json(
+        None,
+        buffer_len,
+        format!("data:image/png;base64,{}", base64(&png_image())),
+    );
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+    let mut glb = Vec::new();
+    glb.extend(b"glTF");
+    glb.extend(2u32.to_le_bytes());
+    glb.extend(((12 + 8 + json.len() + 8 + buffer.len()) as u32).to_le_bytes());
+    glb.extend((json.len() as u32).to_le_bytes());
+    glb.extend(b"JSON");
+    glb.extend(&json);
+    glb.extend((buffer.len() as u32).to_le_bytes());
+    glb.extend(b"BIN\0");
+    glb.extend(&buffer);
+
+    check_scene(&GltfScene::from_slice(&glb).unwrap());
+}
+
+#[test]
+fn draws_scene_with_textures() {
+    let buffer = triangle_buffer();
+    let json = scene_json(
+        Some(format!(
+            "data:application/octet-stream;base64,{}",
+            base64(&buffer)
+        )),
+        buffer.len(),
+        format!("data:image/png;base64,{}", base64(&png_image())),
+    );
+    let scene = GltfScene::from_slice(json.as_bytes()).unwrap();
+
+    // Look at the triangle, which spans x from -1 to 3 and y from -2 to 2 after its transforms
+    let view_proj = Mat4::orthographic_rh(-1.0, 3.0, -2.0, 2.0, -1.0, 1.0);
+    let mut renderer = Renderer::new(64, 64);
+    renderer.clear_framebuffer(0);
+    scene.draw(&mut renderer, view_proj, Vec3::NEG_Z).unwrap();
+
+    let fb = renderer.get_color_buffer();
+    // The top of the triangle is tinted by the red texel, while the center of the bottom right texel
+    // is white
+    let top = unpack_color(fb.get_pixel(32, 56));
+    assert!(top.x == 255 && top.y < 192 && top.y == top.z, "{top}");
+    let white = unpack_color(fb.get_pixel(47, 16));
+    assert!(white.min_element() >= 240, "{white}");
+    assert_eq!(fb.get_pixel(2, 60), 0);
+}
+
+#[test]
+fn draw_discards_masked_materials_below_their_cutoff() {
+    let buffer = triangle_buffer();
+    let json = scene_json(
+        Some(format!(
+            "data:application/octet-stream;base64,{}",
+            base64(&buffer)
+        )),
+        buffer.len(),
+        format!("data:image/png;base64,{}", base64(&png_image())),
+    );
+    let mut scene = GltfScene::from_slice(json.as_bytes()).unwrap();
+    let view_proj = Mat4::orthographic_rh(-1.0, 3.0, -2.0, 2.0, -1.0, 1.0);
+    let mut renderer = Renderer::new(64, 64);
+
+    // The material's cutoff is 0.3
+    for (alpha, drawn) in [(0.25, false), (0.3, true), (0.5, true)] {
+        scene.materials[0].base_color_factor.w = alpha;
+        renderer.clear_framebuffer(0);
+        scene.draw(&mut renderer, view_proj, Vec3::NEG_Z).unwrap();
+        assert_eq!(
+            renderer.get_color_buffer().get_pixel(47, 16) != 0,
+            drawn,
+            "{alpha}"
+        );
+        assert_eq!(
+            renderer.get_depth_buffer().get_pixel(47, 16) < 1.0,
+            drawn,
+            "{alpha}"
+        );
+    }
+}
+
+#[test]
+fn base_color_textures_are_decoded_from_srgb() {
+    let buffer = triangle_buffer();
+    // Every texel is an sRGB mid gray
+    let mut bytes = Vec::new();
+    image::RgbImage::from_pixel(2, 2, image::Rgb([128, 128, 128]))
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+    let json = scene_json(
+        Some(format!(
+            "data:application/octet-stream;base64,{}",
+            base64(&buffer)
+        )),
+        buffer.len(),
+        format!("data:image/png;base64,{}", base64(&bytes)),
+    );
+    let scene = GltfScene::from_slice(json.as_bytes()).unwrap();
+    let view_proj = Mat4::orthographic_rh(-1.0, 3.0, -2.0, 2.0, -1.0, 1.0);
+    let mut renderer = Renderer::new(64, 64);
+    renderer.clear_framebuffer(0);
+    // Light the triangle head on, so the texel is only decoded and encoded again
+    scene.draw(&mut renderer, view_proj, Vec3::NEG_Z).unwrap();
+    assert_eq!(
+        unpack_color(renderer.get_color_buffer().get_pixel(47, 16)),
+        UVec3::splat(128)
+    );
+}
+
+/// Loads a scene with the given nodes and no meshes, returning the reason it was rejected
+fn hierarchy_error(scenes: &str, nodes: &str) -> String {
+    let json =
+        format!(r#"{{ "asset": {{ "version": "2.0" }}, "scenes": {scenes}, "nodes": {nodes} }}"#);
+    match GltfScene::from_slice(json.as_bytes()) {
+        Err(Error::MalformedScene { reason }) => reason,
+        Err(err) => panic!("unexpected error: {err}"),
+        Ok(_) => panic!("loaded a malformed hierarchy"),
+    }
+}
+
+#[test]
+fn rejects_malformed_hierarchies() {
+    // A cycle below a root gives the node where it is entered a second parent
+    let cycle = r#"[{ "children": [1] }, { "children": [2] }, { "children": [1] }]"#;
+    assert_eq!(
+        hierarchy_error(r#"[{ "nodes": [0] }]"#, cycle),
+        "node 1 is a child of both node 0 and node 2"
+    );
+    // Cycles are found even when no scene reaches them
+    let cycle = r#"[{ "children": [1] }, { "children": [0] }, {}]"#;
+    assert!(hierarchy_error(r#"[{ "nodes": [2] }]"#, cycle).contains("cycle"));
+    assert!(hierarchy_error("[]", r#"[{ "children": [0] }]"#).contains("cycle"));
+    let shared = r#"[{ "children": [2] }, { "children": [2] }, {}]"#;
+    assert_eq!(
+        hierarchy_error(r#"[{ "nodes": [0, 1] }]"#, shared),
+        "node 2 is a child of both node 0 and node 1"
+    );
+    let nested_root = r#"[{ "children": [1] }, {}]"#;
+    assert_eq!(
+        hierarchy_error(r#"[{ "nodes": [0, 1] }]"#, nested_root),
+        "scene root 1 is also a child"
+    );
+}
+
+#[test]
+fn draw_rejects_edited_cycles() {
+    let json = r#"{ "asset": { "version": "2.0" }, "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "children": [1] }, {}] }"#;
+    let mut scene = GltfScene::from_slice(json.as_bytes()).unwrap();
+    scene.nodes[1].children.push(0);
+    let mut renderer = Renderer::new(4, 4);
+    assert!(matches!(
+        scene.draw(&mut renderer, Mat4::IDENTITY, Vec3::NEG_Z),
+        Err(Error::MalformedScene { .. })
+    ));
+
+    // Indices past the end of the nodes are errors too, whether roots or children
+    scene.nodes[1].children = vec![2];
+    let result = scene.draw(&mut renderer, Mat4::IDENTITY, Vec3::NEG_Z);
+    assert!(
+        matches!(result, Err(Error::MalformedScene { reason }) if reason == "node 2 does not exist")
+    );
+    scene.nodes[1].children.clear();
+    scene.roots.push(7);
+    assert!(scene
+        .draw(&mut renderer, Mat4::IDENTITY, Vec3::NEG_Z)
+        .is_err());
+}
+
+/// Buffer data for a quad folded along its diagonal: four positions, texture coordinates, normals
+/// and tangents, and six u16 indices
+fn folded_quad_buffer() -> Vec<u8> {
+    let mut data = Vec::new();
+    let floats = [
+        // Positions, with the last corner lifted out of the plane of the first triangle
+        [
+            0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0,
+        ]
+        .as_slice(),
+        &[0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0],
+        &[0.0, 0.0, 1.0].repeat(4),
+        &[0.0, 1.0, 0.0, 1.0].repeat(4),
+    ];
+    for value in floats.concat() {
+        data.extend(value.to_le_bytes());
+    }
+    for index in [0u16, 1, 2, 0, 2, 3] {
+        data.extend(index.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn generates_missing_attributes_per_primitive() {
+    let buffer = folded_quad_buffer();
+    let json = format!(
+        r#"{{
+        "asset": {{ "version": "2.0" }},
+        "meshes": [{{ "primitives": [
+            {{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1, "NORMAL": 2, "TANGENT": 3 }},
+               "indices": 4 }},
+            {{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }}, "indices": 4 }}
+        ] }}],
+        "buffers": [{{ "uri": "data:application/octet-stream;base64,{}", "byteLength": {} }}],
+        "bufferViews": [
+            {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+            {{ "buffer": 0, "byteOffset": 48, "byteLength": 32 }},
+            {{ "buffer": 0, "byteOffset": 80, "byteLength": 48 }},
+            {{ "buffer": 0, "byteOffset": 128, "byteLength": 64 }},
+            {{ "buffer": 0, "byteOffset": 192, "byteLength": 12 }}
+        ],
+        "accessors": [
+            {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+               "min": [0, 0, 0], "max": [1, 1, 1] }},
+            {{ "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC2" }},
+            {{ "bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC3" }},
+            {{ "bufferView": 3, "componentType": 5126, "count": 4, "type": "VEC4" }},
+            {{ "bufferView": 4, "componentType": 5123, "count": 6, "type": "SCALAR" }}
+        ]
+    }}"#,
+        base64(&buffer),
+        buffer.len()
+    );
+    let scene = GltfScene::from_slice(json.as_bytes()).unwrap();
+    let mesh = &scene.meshes[0];
+
+    // The first primitive keeps its vertices shared, along with the tangents it supplies, with the
+    // bitangent flipped to match the flipped texture coordinates
+    assert_eq!(mesh.group_indices(&mesh.groups[0]), [0, 1, 2, 0, 2, 3]);
+    for vertex in &mesh.vertices[..4] {
+        assert_eq!(vertex.normal, Vec3::Z);
+        assert_eq!(vertex.tangent, Vec4::new(0.0, 1.0, 0.0, -1.0));
+    }
+
+    // The second has no normals, so each of its triangles gets its own vertices, with the normal of
+    // its face, and tangents generated from its texture coordinates
+    assert_eq!(mesh.vertices.len(), 4 + 6);
+    assert_eq!(mesh.group_indices(&mesh.groups[1]), [4, 5, 6, 7, 8, 9]);
+    let lifted = Vec3::new(1.0, -1.0, 1.0).normalize();
+    for (i, vertex) in mesh.vertices[4..].iter().enumerate() {
+        let normal = if i < 3 { Vec3::Z } else { lifted };
+        assert!(
+            vertex.normal.abs_diff_eq(normal, 1e-6),
+            "{i}: {}",
+            vertex.normal
+        );
+        assert!(vertex.tangent.w.abs() == 1.0);
+        assert!(vertex.tangent.truncate().dot(vertex.normal).abs() < 1e-6);
+    }
+    assert!(mesh.vertices[4]
+        .tangent
+        .truncate()
+        .abs_diff_eq(Vec3::X, 1e-6));
+}
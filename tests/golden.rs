@@ -0,0 +1,157 @@
+//! Golden-image regression tests, rendering the scenes of the examples headlessly and comparing them
+//! against the references in `tests/golden`.
+//!
+//! Run with `SOFTRENDER_BLESS=1` to regenerate the references after an intentional change. Failing
+//! tests write `<name>.actual.ppm` and `<name>.diff.ppm` next to the reference for inspection.
+
+#[macro_use]
+extern crate softrender_derive;
+
+use std::path::PathBuf;
+
+use glam::{Affine3A, Mat4, UVec3, Vec2, Vec3, Vec4};
+use image::RgbImage;
+use softrender::{
+    camera::Camera,
+    mesh::{Material, Mesh},
+    scene::{Light, NodeContent, Scene},
+    shader::{Barycentric, Shader},
+    shaders::{BlinnPhongShader, ColorVertex, VertexColorShader},
+    testing::{assert_matches_reference, render_headless, Tolerance},
+};
+
+const WIDTH: u32 = 200;
+const HEIGHT: u32 = 200;
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.ppm"))
+}
+
+fn resource_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("res")
+        .join(name)
+}
+
+mod hello_triangle {
+    use super::*;
+
+    #[test]
+    fn matches_reference() {
+        let vertices = [
+            (Vec3::new(0.5, -0.5, 0.0), Vec3::X),
+            (Vec3::new(0.0, 0.5, 0.0), Vec3::Y),
+            (Vec3::new(-0.5, -0.5, 0.0), Vec3::Z),
+        ]
+        .map(|(position, color)| ColorVertex { position, color });
+        let mut shader = VertexColorShader {
+            mvp: Mat4::IDENTITY,
+        };
+        let fb = render_headless(WIDTH, HEIGHT, 95 | 95 << 8 | 95 << 16, |renderer| {
+            renderer.draw(&mut shader, &vertices, &[0, 1, 2]);
+        });
+        assert_matches_reference(&fb, reference_path("hello_triangle"), &Tolerance::default());
+    }
+}
+
+mod texture {
+    use super::*;
+
+    #[derive(Clone, Barycentric)]
+    struct VertexOut {
+        uv: Vec2,
+    }
+
+    struct Vertex {
+        pos: Vec3,
+        uv: Vec2,
+    }
+
+    struct TextureShader {
+        texture: RgbImage,
+    }
+
+    impl Shader<Vertex, VertexOut> for TextureShader {
+        fn vertex(&self, vertex: &Vertex) -> (Vec4, VertexOut) {
+            (vertex.pos.extend(1.0), VertexOut { uv: vertex.uv })
+        }
+
+        fn fragment(&self, inputs: VertexOut) -> UVec3 {
+            let pix = self.texture.get_pixel(
+                (inputs.uv.x * (self.texture.width() - 1) as f32) as u32,
+                ((1.0 - inputs.uv.y) * (self.texture.height() - 1) as f32) as u32,
+            );
+            UVec3::new(pix[0] as u32, pix[1] as u32, pix[2] as u32)
+        }
+    }
+
+    #[test]
+    fn matches_reference() {
+        let mut shader = TextureShader {
+            texture: image::open(resource_path("texture.png"))
+                .expect("Failed to load texture")
+                .into_rgb8(),
+        };
+        let vertices = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(u, v)| Vertex {
+            pos: Vec3::new(u - 0.5, v - 0.5, 0.0),
+            uv: Vec2::new(u, v),
+        });
+        let fb = render_headless(WIDTH, HEIGHT, 0, |renderer| {
+            renderer.draw(&mut shader, &vertices, &[0, 1, 2, 2, 1, 3]);
+        });
+        assert_matches_reference(&fb, reference_path("texture"), &Tolerance::default());
+    }
+}
+
+mod model_load {
+    use super::*;
+
+    #[test]
+    fn matches_reference() {
+        let mut scene = Scene::new();
+        let mesh = scene
+            .add_mesh(Mesh::load_obj(resource_path("teapot.obj")).expect("Could not load model."));
+        let clay = scene.add_material(Material {
+            diffuse: Vec3::splat(200.0 / 255.0),
+            ..Default::default()
+        });
+        let pivot = scene.add_node(
+            None,
+            Affine3A::from_rotation_x(25.0f32.to_radians())
+                * Affine3A::from_rotation_y(10.0f32.to_radians()),
+            NodeContent::EMPTY,
+        );
+        scene.add_node(
+            Some(pivot),
+            Affine3A::from_scale(Vec3::splat(0.4)),
+            NodeContent::MESH {
+                mesh,
+                material: Some(clay),
+            },
+        );
+        scene.add_node(
+            None,
+            Affine3A::IDENTITY,
+            NodeContent::LIGHT(Light::directional(Vec3::ONE, 1.0)),
+        );
+
+        let camera = Camera::perspective(
+            50.0f32.to_radians(),
+            WIDTH as f32 / HEIGHT as f32,
+            0.1,
+            50.0,
+            Vec3::new(0.0, 0.2, 3.5),
+        );
+        let fb = render_headless(WIDTH, HEIGHT, 0, |renderer| {
+            renderer
+                .render_scene(&scene, &camera, |context| BlinnPhongShader {
+                    ambient_light: Vec3::splat(0.2),
+                    ..BlinnPhongShader::from_context(context)
+                })
+                .unwrap();
+        });
+        assert_matches_reference(&fb, reference_path("model_load"), &Tolerance::default());
+    }
+}
@@ -0,0 +1,45 @@
+//! Tests for loading images into framebuffers, and rejecting malformed ones.
+
+use softrender::{error::Error, export::ImageFormat, fb::Framebuffer};
+
+fn read_ppm(bytes: &[u8]) -> Result<Framebuffer<u32>, Error> {
+    Framebuffer::read_image(bytes, ImageFormat::PPM)
+}
+
+fn reason(result: Result<Framebuffer<u32>, Error>) -> String {
+    match result {
+        Err(Error::MalformedImage { reason }) => reason,
+        Err(err) => panic!("unexpected error: {err}"),
+        Ok(_) => panic!("malformed image was read"),
+    }
+}
+
+#[test]
+fn ppm_round_trips() {
+    let mut fb = Framebuffer::new(3, 2);
+    fb.fill(0x102030);
+    fb.plot_pixel(0, 0, 0xFF0000);
+    fb.plot_pixel(2, 1, 0x0000FF);
+    let mut bytes = Vec::new();
+    fb.write_image(&mut bytes, ImageFormat::PPM).unwrap();
+    assert_eq!(read_ppm(&bytes).unwrap().get_raw(), fb.get_raw());
+}
+
+#[test]
+fn ppm_rejects_truncated_pixel_data() {
+    let mut bytes = b"P6\n# two by two\n2 2\n255\n".to_vec();
+    bytes.extend([0; 11]);
+    assert_eq!(reason(read_ppm(&bytes)), "pixel data is truncated");
+}
+
+#[test]
+fn ppm_headers_cannot_claim_more_data_than_the_input_holds() {
+    // Far more pixels than could ever be allocated, backed by a single byte
+    let huge = format!("P6 {0} {0} 255\n\0", u32::MAX).into_bytes();
+    assert!(matches!(
+        reason(read_ppm(&huge)).as_str(),
+        "image dimensions are too large" | "pixel data is truncated"
+    ));
+    let large = b"P6 100000 100000 255\n\0\0\0";
+    assert_eq!(reason(read_ppm(large)), "pixel data is truncated");
+}
@@ -0,0 +1,161 @@
+//! Tests for rasterizing lines, both as the edges of wireframe triangles and as line lists.
+
+mod common;
+
+use common::{rect_xy, ClipVertex, ColorShader, QUAD_INDICES};
+use glam::{Vec2, Vec3};
+use softrender::{
+    error::Error,
+    math::unpack_color,
+    renderer::{DrawMode, Renderer, Topology},
+};
+
+/// Pixel centers sit at whole coordinates, so an odd size puts one on the center of the view
+const SIZE: u32 = 65;
+const CENTER: u32 = SIZE / 2;
+
+/// A triangle whose bottom edge runs across the middle row of the view, from left to right, at the
+/// given depth. The far end of the edge is `far_w` times further from the camera than the rest.
+fn edge_triangle(depth: f32, far_w: f32, near: Vec3, far: Vec3) -> Vec<ClipVertex> {
+    vec![
+        ClipVertex::new(Vec3::new(-0.9, 0.0, depth), 1.0, near),
+        ClipVertex::new(Vec3::new(0.9, 0.0, depth), far_w, far),
+        ClipVertex::new(Vec3::new(0.0, 0.8, depth), 1.0, near),
+    ]
+}
+
+#[test]
+fn wireframe_lines_are_depth_tested() {
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.clear_framebuffer(0);
+    // A green square covering the right of the middle row
+    let square: Vec<_> = rect_xy(Vec2::new(0.2, -0.5), Vec2::new(0.95, 0.5), 0.2)
+        .into_iter()
+        .map(|position| ClipVertex::new(position, 1.0, Vec3::Y))
+        .collect();
+    renderer.draw(&mut ColorShader, &square, &QUAD_INDICES);
+
+    // A red edge behind the square is hidden by it, but still writes its depth where it is seen
+    renderer.set_draw_mode(DrawMode::WIREFRAME);
+    let behind = edge_triangle(0.5, 1.0, Vec3::X, Vec3::X);
+    let fb = renderer.draw(&mut ColorShader, &behind, &[0, 1, 2]);
+    assert_eq!(fb.get_pixel(10, CENTER), 0xFF0000);
+    assert_eq!(fb.get_pixel(50, CENTER), 0x00FF00);
+    let depth = renderer.get_depth_buffer();
+    assert!((depth.get_pixel(10, CENTER) - 0.5).abs() < 1e-5);
+    assert!((depth.get_pixel(50, CENTER) - 0.2).abs() < 1e-5);
+
+    // A blue edge in front of it is drawn over the square
+    let in_front = edge_triangle(0.1, 1.0, Vec3::Z, Vec3::Z);
+    let fb = renderer.draw(&mut ColorShader, &in_front, &[0, 1, 2]);
+    assert_eq!(fb.get_pixel(50, CENTER), 0x0000FF);
+}
+
+#[test]
+fn wireframe_lines_interpolate_perspective_correctly() {
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.clear_framebuffer(0);
+    renderer.set_draw_mode(DrawMode::WIREFRAME);
+    // The edge fades from black to red, and its red end is three times as far away
+    let triangle = edge_triangle(0.5, 3.0, Vec3::ZERO, Vec3::X);
+    let fb = renderer.draw(&mut ColorShader, &triangle, &[0, 1, 2]);
+
+    // Halfway along the edge on screen, only a quarter of the way along it in the world:
+    // (0.5 / 3) / (0.5 / 1 + 0.5 / 3) = 0.25
+    let red = unpack_color(fb.get_pixel(CENTER, CENTER)).x;
+    assert!(red.abs_diff(64) <= 1, "{red}");
+    // Near the ends, the colors approach those of the vertices
+    assert!(unpack_color(fb.get_pixel(4, CENTER)).x <= 2);
+    assert!(unpack_color(fb.get_pixel(60, CENTER)).x >= 240);
+}
+
+/// A red line list segment across the view at the given ndc height
+fn horizontal_line(y: f32) -> [ClipVertex; 2] {
+    [-0.5, 0.5].map(|x| ClipVertex::new(Vec3::new(x, y, 0.5), 1.0, Vec3::X))
+}
+
+/// The rows of the center column that a line colors
+fn covered_rows(renderer: &Renderer) -> Vec<u32> {
+    let fb = renderer.get_color_buffer();
+    (0..SIZE)
+        .filter(|&y| fb.get_pixel(CENTER, y) != 0)
+        .collect()
+}
+
+#[test]
+fn line_width_covers_pixels_within_half_width() {
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.set_topology(Topology::LINES);
+    renderer.clear_framebuffer(0);
+    renderer.draw(&mut ColorShader, &horizontal_line(0.0), &[0, 1]);
+    assert_eq!(covered_rows(&renderer), [CENTER]);
+
+    // Pixel centers up to two and a half pixels away are covered
+    renderer.set_line_width(5.0);
+    renderer.clear_framebuffer(0);
+    renderer.draw(&mut ColorShader, &horizontal_line(0.0), &[0, 1]);
+    assert_eq!(
+        covered_rows(&renderer),
+        (CENTER - 2..=CENTER + 2).collect::<Vec<_>>()
+    );
+    // The ends of wide lines are rounded, reaching half the width past the endpoints
+    let fb = renderer.get_color_buffer();
+    assert_eq!(fb.get_pixel(14, CENTER), 0xFF0000);
+    assert_eq!(fb.get_pixel(13, CENTER), 0);
+
+    // Widths below a pixel are clamped to one
+    renderer.set_line_width(0.1);
+    renderer.clear_framebuffer(0);
+    renderer.draw(&mut ColorShader, &horizontal_line(0.0), &[0, 1]);
+    assert_eq!(covered_rows(&renderer), [CENTER]);
+}
+
+#[test]
+fn antialiased_lines_blend_by_coverage() {
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.set_topology(Topology::LINES);
+    renderer.set_line_antialiasing(true);
+    renderer.clear_framebuffer(0);
+    // Halfway between two rows, a one pixel wide line covers half of each
+    let half_pixel = 1.0 / (SIZE - 1) as f32;
+    renderer.draw(&mut ColorShader, &horizontal_line(half_pixel), &[0, 1]);
+    assert_eq!(covered_rows(&renderer), [CENTER, CENTER + 1]);
+    let fb = renderer.get_color_buffer();
+    for y in [CENTER, CENTER + 1] {
+        let color = unpack_color(fb.get_pixel(CENTER, y));
+        assert!(color.x.abs_diff(128) <= 1 && color.y == 0, "{color}");
+    }
+    // Fragments covering at least half a pixel also write their depth
+    assert_eq!(renderer.get_depth_buffer().get_pixel(CENTER, CENTER), 0.5);
+
+    // Centered on a row, the line covers it fully, and a quarter of the rows either side
+    renderer.set_line_width(1.5);
+    renderer.clear_framebuffer(0);
+    renderer.draw(&mut ColorShader, &horizontal_line(0.0), &[0, 1]);
+    let fb = renderer.get_color_buffer();
+    assert_eq!(fb.get_pixel(CENTER, CENTER), 0xFF0000);
+    let edge = unpack_color(fb.get_pixel(CENTER, CENTER + 1)).x;
+    assert!(edge.abs_diff(64) <= 1, "{edge}");
+    assert_eq!(fb.get_pixel(CENTER, CENTER + 2), 0);
+    // Faint fragments leave the depth buffer alone, so they don't hide what is drawn later
+    assert_eq!(
+        renderer.get_depth_buffer().get_pixel(CENTER, CENTER + 1),
+        1.0
+    );
+}
+
+#[test]
+fn line_lists_reject_odd_index_counts() {
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.set_topology(Topology::LINES);
+    renderer.clear_framebuffer(0);
+    let result = renderer.try_draw(&mut ColorShader, &horizontal_line(0.0), &[0, 1, 0]);
+    assert!(matches!(
+        result,
+        Err(Error::IncompletePrimitive {
+            index_count: 3,
+            vertices_per_primitive: 2
+        })
+    ));
+    assert!(covered_rows(&renderer).is_empty());
+}
@@ -0,0 +1,181 @@
+//! Tests for the OBJ and MTL loaders.
+
+use std::path::Path;
+
+use glam::{Vec2, Vec3};
+use softrender::{
+    error::Error,
+    mesh::{Material, Mesh},
+};
+
+const QUAD: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+#[test]
+fn polygons_are_triangulated_as_fans() {
+    let mesh = Mesh::parse_obj(QUAD, Vec::new()).unwrap();
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+    assert_eq!(mesh.vertices[2].position, Vec3::new(1.0, 1.0, 0.0));
+    assert_eq!(mesh.vertices[2].uv, Vec2::new(1.0, 1.0));
+    assert_eq!(mesh.vertices[2].normal, Vec3::Z);
+}
+
+#[test]
+fn vertices_are_shared_only_when_every_index_matches() {
+    // Both triangles share positions 1 and 3, but only position 1 also shares its texture coordinate
+    let source = "
+        v 0 0 0
+        v 1 0 0
+        v 0 1 0
+        v 1 1 0
+        vt 0 0
+        vt 0.5 0.5
+        f 1/1 2/1 3/1
+        f 3/2 2/1 4/1
+    ";
+    let mesh = Mesh::parse_obj(source, Vec::new()).unwrap();
+    assert_eq!(mesh.vertices.len(), 5);
+    assert_eq!(mesh.indices, [0, 1, 2, 3, 1, 4]);
+    assert_eq!(mesh.vertices[3].position, mesh.vertices[2].position);
+    assert_eq!(mesh.vertices[3].uv, Vec2::splat(0.5));
+}
+
+#[test]
+fn negative_indices_count_from_the_end() {
+    let source = "
+        v 0 0 0
+        v 1 0 0
+        v 0 1 0
+        f -3 -2 -1
+    ";
+    let mesh = Mesh::parse_obj(source, Vec::new()).unwrap();
+    assert_eq!(mesh.indices, [0, 1, 2]);
+    assert_eq!(mesh.vertices[1].position, Vec3::X);
+}
+
+#[test]
+fn missing_normals_are_smoothed_from_faces() {
+    // Two faces folded along the shared edge between positions 1 and 2
+    let source = "
+        v 0 0 0
+        v 1 0 0
+        v 0 1 0
+        v 0 0 1
+        f 1 2 3
+        f 2 1 4
+    ";
+    let mesh = Mesh::parse_obj(source, Vec::new()).unwrap();
+    assert_eq!(mesh.vertices[2].normal, Vec3::Z);
+    assert_eq!(mesh.vertices[3].normal, Vec3::Y);
+    let shared = mesh.vertices[0].normal;
+    assert!((shared - Vec3::new(0.0, 1.0, 1.0).normalize()).length() < 1e-6);
+}
+
+#[test]
+fn faces_are_grouped_by_material() {
+    let materials = Material::parse_mtl(
+        "
+        newmtl red
+        Kd 1 0 0
+        map_Kd -bm 1.0 textures/red.png
+        newmtl blue
+        Kd 0 0 1
+        d 0.5
+        ",
+        Path::new("assets"),
+    )
+    .unwrap();
+    assert_eq!(materials.len(), 2);
+    assert_eq!(materials[0].diffuse, Vec3::X);
+    assert_eq!(
+        materials[0].diffuse_texture.as_deref(),
+        Some(Path::new("assets/textures/red.png"))
+    );
+    assert_eq!(materials[1].opacity, 0.5);
+
+    let source = "
+        v 0 0 0
+        v 1 0 0
+        v 0 1 0
+        f 1 2 3
+        usemtl blue
+        f 1 2 3
+        f 1 2 3
+        usemtl missing
+        f 1 2 3
+        usemtl red
+        f 1 2 3 1
+    ";
+    let mesh = Mesh::parse_obj(source, materials).unwrap();
+    let groups: Vec<_> = mesh
+        .groups
+        .iter()
+        .map(|group| (group.material, group.indices.clone()))
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            (None, 0..3),
+            (Some(1), 3..9),
+            (None, 9..12),
+            (Some(0), 12..18)
+        ]
+    );
+    assert_eq!(mesh.group_material(&mesh.groups[1]).unwrap().name, "blue");
+    assert_eq!(mesh.group_indices(&mesh.groups[2]).len(), 3);
+}
+
+#[test]
+fn errors_report_their_line() {
+    let source = "v 0 0 0\nv 1 0 0\n\nf 1 2 3\n";
+    assert!(matches!(
+        Mesh::parse_obj(source, Vec::new()),
+        Err(Error::MalformedModel { line: 4, .. })
+    ));
+    assert!(matches!(
+        Mesh::parse_obj("v 0 zero 0", Vec::new()),
+        Err(Error::MalformedModel { line: 1, .. })
+    ));
+}
+
+#[test]
+fn loads_teapot() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/teapot.obj");
+    let mesh = Mesh::load_obj(path).unwrap();
+    assert_eq!(mesh.indices.len() % 3, 0);
+    assert!(!mesh.vertices.is_empty());
+    // The teapot's material library is not shipped, so its faces have no material
+    assert_eq!(mesh.groups.len(), 1);
+    assert_eq!(mesh.groups[0].indices, 0..mesh.indices.len());
+}
+
+#[test]
+fn mtllib_loads_every_library_it_lists() {
+    let dir = std::env::temp_dir().join(format!("softrender-mtllib-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("red.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+    std::fs::write(dir.join("blue.mtl"), "newmtl blue\nKd 0 0 1\n").unwrap();
+    let source = format!("mtllib red.mtl missing.mtl blue.mtl\nusemtl blue\n{QUAD}");
+    std::fs::write(dir.join("quad.obj"), source).unwrap();
+    let mesh = Mesh::load_obj(dir.join("quad.obj"));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mesh = mesh.unwrap();
+    let names: Vec<_> = mesh.materials.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["red", "blue"]);
+    assert_eq!(
+        mesh.group_material(&mesh.groups[0]).unwrap().diffuse,
+        Vec3::Z
+    );
+}
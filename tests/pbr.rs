@@ -0,0 +1,237 @@
+//! Tests for physically based shading, checking the BRDF against values worked out by hand from the
+//! formulas of the glTF specification, and the spherical harmonics against analytic irradiance.
+
+use std::f32::consts::PI;
+
+use glam::{Affine3A, Mat3, Mat4, Vec2, Vec3, Vec4};
+use softrender::{
+    math::ToneMapping,
+    mesh::MeshVertex,
+    pbr::{
+        cook_torrance, distribution_ggx, environment_brdf, fresnel_schlick, visibility_smith_ggx,
+        Irradiance, PbrMaterial, PbrShader, PbrVertexOut, ShIrradiance,
+    },
+    renderer::Renderer,
+    scene::{Light, LightInstance},
+    texture::Cubemap,
+};
+
+const EPSILON: f32 = 1e-4;
+
+fn assert_near(actual: f32, expected: f32, epsilon: f32) {
+    assert!(
+        (actual - expected).abs() <= epsilon,
+        "expected {expected}, got {actual}"
+    );
+}
+
+fn assert_near_vec(actual: Vec3, expected: Vec3, epsilon: f32) {
+    assert!(
+        actual.abs_diff_eq(expected, epsilon),
+        "expected {expected}, got {actual}"
+    );
+}
+
+#[test]
+fn ggx_distribution_reference_values() {
+    // At the peak, D = 1 / (π α²), with α = roughness²
+    assert_near(distribution_ggx(1.0, 1.0), 1.0 / PI, EPSILON);
+    assert_near(distribution_ggx(1.0, 0.5), 1.0 / (PI * 0.0625), EPSILON);
+    // A fully rough surface is uniform over the hemisphere
+    assert_near(distribution_ggx(0.3, 1.0), 1.0 / PI, EPSILON);
+    // α = 0.25, so D = α² / (π (0.25 (α² - 1) + 1)²) = 0.0625 / (π 0.765625²)
+    assert_near(distribution_ggx(0.5, 0.5), 0.03394, EPSILON);
+}
+
+#[test]
+fn smith_visibility_reference_values() {
+    // Head on, V = 0.5 / (n·l + n·v) for any roughness
+    assert_near(visibility_smith_ggx(1.0, 1.0, 0.2), 0.25, EPSILON);
+    assert_near(visibility_smith_ggx(1.0, 1.0, 1.0), 0.25, EPSILON);
+    // With α = 1, V = 0.5 / (n·l + n·v)
+    assert_near(visibility_smith_ggx(0.5, 0.8, 1.0), 0.5 / 1.3, EPSILON);
+    assert_eq!(visibility_smith_ggx(0.0, 0.0, 0.5), 0.0);
+}
+
+#[test]
+fn fresnel_reference_values() {
+    let f0 = Vec3::new(0.04, 0.5, 1.0);
+    assert_near_vec(fresnel_schlick(f0, 1.0), f0, EPSILON);
+    assert_near_vec(fresnel_schlick(f0, 0.0), Vec3::ONE, EPSILON);
+    // (1 - 0.5)^5 = 1/32
+    assert_near_vec(
+        fresnel_schlick(Vec3::splat(0.04), 0.5),
+        Vec3::splat(0.04 + 0.96 / 32.0),
+        EPSILON,
+    );
+}
+
+#[test]
+fn cook_torrance_reference_values() {
+    let n = Vec3::Z;
+    // A rough white dielectric lit and viewed head on: F = 0.04, D = 1/π, V = 1/4
+    let expected = 0.96 / PI + 0.04 / (4.0 * PI);
+    assert_near_vec(
+        cook_torrance(Vec3::ONE, 0.0, 1.0, n, n, n),
+        Vec3::splat(expected),
+        EPSILON,
+    );
+
+    // A metal has no diffuse term, and reflects with its base color
+    let gold = Vec3::new(1.0, 0.78, 0.34);
+    let to_light = Vec3::new(0.6, 0.0, 0.8);
+    let to_view = Vec3::new(-0.6, 0.0, 0.8);
+    // The halfway vector is the normal, so with α = 0.25: D = 1 / (π α²), and V = 0.5 / (2 · 0.8 ·
+    // sqrt(0.64 (1 - α²) + α²))
+    let d = 1.0 / (PI * 0.0625);
+    let v = 0.5 / (1.6 * (0.64 * (1.0 - 0.0625) + 0.0625f32).sqrt());
+    assert_near_vec(
+        cook_torrance(gold, 1.0, 0.5, n, to_view, to_light),
+        gold * d * v * 0.8,
+        1e-3,
+    );
+
+    // Light from below the surface is ignored
+    assert_eq!(cook_torrance(Vec3::ONE, 0.0, 0.5, n, n, -n), Vec3::ZERO);
+}
+
+#[test]
+fn environment_brdf_stays_in_range() {
+    for roughness in [0.0, 0.25, 0.5, 1.0] {
+        for n_dot_v in [0.05, 0.5, 1.0] {
+            let scale = environment_brdf(Vec3::splat(0.04), n_dot_v, roughness).x;
+            assert!((0.0..=1.0).contains(&scale), "{scale}");
+        }
+    }
+    // Smooth surfaces reflect almost exactly F0 head on
+    assert_near(environment_brdf(Vec3::ONE, 1.0, 0.0).x, 1.0, 0.05);
+}
+
+#[test]
+fn constant_environment_irradiance_is_pi_times_radiance() {
+    let radiance = Vec3::new(0.5, 1.0, 2.0);
+    let from_cubemap = ShIrradiance::from_cubemap(&Cubemap::solid(radiance));
+    let constant = ShIrradiance::constant(radiance);
+    for normal in [Vec3::X, Vec3::NEG_Y, Vec3::new(1.0, 2.0, -3.0)] {
+        assert_near_vec(constant.irradiance(normal), radiance * PI, 1e-3);
+        assert_near_vec(from_cubemap.irradiance(normal), radiance * PI, 1e-3);
+    }
+}
+
+#[test]
+fn sky_irradiance_matches_analytic_value() {
+    // Radiance of one from the upper hemisphere only gives an irradiance of π facing up, zero facing
+    // down and π/2 facing sideways. Nine harmonics ring a little, so allow a few percent.
+    let sky = Cubemap::from_fn(32, |direction| {
+        Vec3::splat(if direction.y > 0.0 { 1.0 } else { 0.0 })
+    });
+    let harmonics = ShIrradiance::from_cubemap(&sky);
+    assert_near(harmonics.irradiance(Vec3::Y).x, PI, 0.1);
+    assert_near(harmonics.irradiance(Vec3::NEG_Y).x, 0.0, 0.1);
+    assert_near(harmonics.irradiance(Vec3::X).x, PI / 2.0, 0.05);
+}
+
+fn shader<'a>(lights: &'a [LightInstance], material: PbrMaterial<'a>) -> PbrShader<'a> {
+    PbrShader {
+        view_proj: Mat4::IDENTITY,
+        model: Affine3A::IDENTITY,
+        normal_matrix: Mat3::IDENTITY,
+        camera_position: Vec3::new(0.0, 0.0, 5.0),
+        lights,
+        material,
+        irradiance: Irradiance::CONSTANT(Vec3::ZERO),
+        environment: None,
+        exposure: 1.0,
+        tone_mapping: ToneMapping::NONE,
+        srgb_output: false,
+    }
+}
+
+fn facing_fragment() -> PbrVertexOut {
+    PbrVertexOut {
+        world_position: Vec3::ZERO,
+        normal: Vec3::Z,
+        uv: Vec2::ZERO,
+        tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
+    }
+}
+
+#[test]
+fn shading_with_lights_and_ambient() {
+    let lights = [LightInstance {
+        light: Light::directional(Vec3::ONE, PI),
+        position: Vec3::ZERO,
+        direction: Vec3::NEG_Z,
+    }];
+    let dielectric = PbrMaterial {
+        metallic: 0.0,
+        ..Default::default()
+    };
+    let mut shader = shader(&lights, dielectric);
+    let expected = PI * (0.96 / PI + 0.04 / (4.0 * PI));
+    assert_near_vec(
+        shader.shade(&facing_fragment()),
+        Vec3::splat(expected),
+        EPSILON,
+    );
+
+    // In a uniformly lit environment, a rough white dielectric reflects almost all of it back
+    shader.lights = &[];
+    let harmonics = ShIrradiance::constant(Vec3::ONE);
+    shader.irradiance = Irradiance::SPHERICAL_HARMONICS(&harmonics);
+    let ambient = shader.shade(&facing_fragment());
+    assert!(ambient.abs_diff_eq(Vec3::ONE, 0.05), "{ambient}");
+
+    // Emission is added on top
+    shader.material.emissive = Vec3::new(1.0, 0.0, 0.0);
+    assert_near(shader.shade(&facing_fragment()).x, ambient.x + 1.0, EPSILON);
+}
+
+#[test]
+fn smooth_metal_reflects_environment() {
+    let environment = Cubemap::from_fn(8, |direction| {
+        if direction.z > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::ZERO
+        }
+    });
+    let material = PbrMaterial {
+        roughness: 0.0,
+        ..Default::default()
+    };
+    let mut shader = shader(&[], material);
+    shader.environment = Some(&environment);
+    // Viewed head on, the surface reflects what lies straight behind the camera
+    let color = shader.shade(&facing_fragment());
+    assert!(color.y > 0.9 && color.x == 0.0, "{color}");
+}
+
+#[test]
+fn renders_through_the_pipeline() {
+    let lights = [LightInstance {
+        light: Light::directional(Vec3::ONE, PI),
+        position: Vec3::ZERO,
+        direction: Vec3::NEG_Z,
+    }];
+    let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| MeshVertex {
+        position: Vec3::new(x, y, 0.0),
+        normal: Vec3::Z,
+        uv: Vec2::ZERO,
+        tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
+    });
+    let mut shader = shader(
+        &lights,
+        PbrMaterial {
+            metallic: 0.0,
+            ..Default::default()
+        },
+    );
+    shader.srgb_output = true;
+    let mut renderer = Renderer::new(16, 16);
+    renderer.clear_framebuffer(0);
+    let fb = renderer.draw(&mut shader, &vertices, &[0, 1, 2, 0, 2, 3]);
+    // A linear radiance of 0.97 is encoded to 0.987 by the sRGB curve
+    let blue = fb.get_pixel(8, 8) & 0xFF;
+    assert!(blue.abs_diff(252) <= 1, "{blue}");
+}
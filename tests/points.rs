@@ -0,0 +1,131 @@
+//! Tests for point lists, with per-vertex sizes and sprite shading through `point_fragment`.
+
+use glam::{UVec3, Vec2, Vec3, Vec4};
+use softrender::{
+    math::unpack_color,
+    renderer::{DrawMode, Renderer, Topology},
+    shader::Shader,
+};
+
+/// Pixel centers sit at whole coordinates, so an odd size puts one on the center of the view
+const SIZE: u32 = 33;
+const CENTER: u32 = SIZE / 2;
+
+#[derive(Clone, Copy)]
+struct PointVertex {
+    /// Position in normalized device coordinates
+    position: Vec3,
+    /// Overrides the renderer's point size, if set
+    size: Option<f32>,
+}
+
+fn point(x: f32, y: f32, size: Option<f32>) -> PointVertex {
+    PointVertex {
+        position: Vec3::new(x, y, 0.5),
+        size,
+    }
+}
+
+/// Shades points white, as sprites with red and green following `point_coord`, or as discs
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy)]
+enum PointShader {
+    SOLID,
+    COORDS,
+    ROUND,
+}
+
+impl Shader<PointVertex, ()> for PointShader {
+    fn vertex(&self, vertex: &PointVertex) -> (Vec4, ()) {
+        (vertex.position.extend(1.0), ())
+    }
+
+    fn fragment(&self, _inputs: ()) -> UVec3 {
+        UVec3::splat(255)
+    }
+
+    fn point_size(&self, vertex: &PointVertex) -> Option<f32> {
+        vertex.size
+    }
+
+    fn point_fragment(&self, inputs: (), point_coord: Vec2) -> Option<UVec3> {
+        match self {
+            PointShader::SOLID => Some(self.fragment(inputs)),
+            PointShader::COORDS => Some((point_coord * 255.0).round().as_uvec2().extend(0)),
+            PointShader::ROUND => {
+                (point_coord.distance(Vec2::splat(0.5)) <= 0.5).then(|| self.fragment(inputs))
+            }
+        }
+    }
+}
+
+fn renderer() -> Renderer {
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.set_topology(Topology::POINTS);
+    renderer.clear_framebuffer(0);
+    renderer
+}
+
+/// The number of pixels whose depth was written
+fn covered(renderer: &Renderer) -> usize {
+    renderer
+        .get_depth_buffer()
+        .get_raw()
+        .iter()
+        .filter(|&&depth| depth < 1.0)
+        .count()
+}
+
+#[test]
+fn vertices_override_the_point_size() {
+    let mut renderer = renderer();
+    renderer.set_point_size(3.0);
+    renderer.draw(&mut PointShader::SOLID, &[point(-0.5, 0.0, None)], &[0]);
+    assert_eq!(covered(&renderer), 9);
+    renderer.draw(&mut PointShader::SOLID, &[point(0.5, 0.0, Some(5.0))], &[0]);
+    assert_eq!(covered(&renderer), 9 + 25);
+    // Sizes below a pixel are clamped to one
+    renderer.clear_framebuffer(0);
+    renderer.draw(&mut PointShader::SOLID, &[point(0.0, 0.0, Some(0.2))], &[0]);
+    assert_eq!(covered(&renderer), 1);
+    assert_eq!(
+        renderer.get_color_buffer().get_pixel(CENTER, CENTER),
+        0xFFFFFF
+    );
+}
+
+#[test]
+fn point_coord_runs_across_the_sprite() {
+    let mut renderer = renderer();
+    renderer.set_point_size(4.0);
+    renderer.draw(&mut PointShader::COORDS, &[point(0.0, 0.0, None)], &[0]);
+    // An even sized point covers the two pixels either side of its center on each axis
+    assert_eq!(covered(&renderer), 16);
+    let fb = renderer.get_color_buffer();
+    let coords = |x, y| unpack_color(fb.get_pixel(x, y));
+    assert_eq!(coords(CENTER - 2, CENTER - 2), UVec3::new(0, 0, 0));
+    assert_eq!(coords(CENTER + 1, CENTER - 2), UVec3::new(191, 0, 0));
+    assert_eq!(coords(CENTER - 2, CENTER + 1), UVec3::new(0, 191, 0));
+    assert_eq!(coords(CENTER, CENTER), UVec3::new(128, 128, 0));
+}
+
+#[test]
+fn discarded_fragments_are_not_written() {
+    let mut renderer = renderer();
+    renderer.set_point_size(9.0);
+    renderer.draw(&mut PointShader::ROUND, &[point(0.0, 0.0, None)], &[0]);
+    let (corner, middle) = ((CENTER - 4, CENTER - 4), (CENTER, CENTER + 4));
+    let fb = renderer.get_color_buffer();
+    assert_eq!(fb.get_pixel(corner.0, corner.1), 0);
+    assert_eq!(fb.get_pixel(middle.0, middle.1), 0xFFFFFF);
+    let depth = renderer.get_depth_buffer();
+    assert_eq!(depth.get_pixel(corner.0, corner.1), 1.0);
+    assert_eq!(depth.get_pixel(middle.0, middle.1), 0.5);
+
+    // Depth-only draws never shade, so the whole square writes its depth
+    renderer.clear_framebuffer(0);
+    renderer.set_draw_mode(DrawMode::DEPTH_ONLY);
+    let fb = renderer.draw(&mut PointShader::ROUND, &[point(0.0, 0.0, None)], &[0]);
+    assert!(fb.get_raw().iter().all(|&color| color == 0));
+    assert_eq!(covered(&renderer), 81);
+}
@@ -0,0 +1,185 @@
+//! Tests for post-processing pipelines and the built-in passes.
+
+use glam::Vec3;
+use softrender::{
+    fb::Framebuffer,
+    math::{pack_color, unpack_color},
+    post::{ColorLut, Fxaa, GaussianBlur, PostInputs, PostPipeline, Sharpen},
+    renderer::Renderer,
+    texture::Texture,
+};
+
+const EPSILON: f32 = 1e-4;
+
+/// An image filled by a function of each pixel's coordinates, returning 0-255 colors
+fn image(width: u32, height: u32, f: impl Fn(u32, u32) -> Vec3) -> Framebuffer<u32> {
+    let mut fb = Framebuffer::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            fb.plot_pixel(x, y, pack_color(f(x, y).as_uvec3()));
+        }
+    }
+    fb
+}
+
+fn run(pipeline: &PostPipeline, color: &Framebuffer<u32>) -> Framebuffer<u32> {
+    let depth = Framebuffer::new(color.get_width(), color.get_height());
+    pipeline.run(color, &depth, &[])
+}
+
+fn red(fb: &Framebuffer<u32>, x: u32, y: u32) -> u32 {
+    unpack_color(fb.get_pixel(x, y)).x
+}
+
+#[test]
+fn passes_run_in_order_over_every_pixel() {
+    let mut pipeline = PostPipeline::new();
+    // Rows of the image are split between threads, so an odd count leaves a short band at the end
+    pipeline.set_threads(3);
+    pipeline
+        .add(|_: &PostInputs, _x, y| Vec3::splat(y as f32 / 9.0))
+        .add(|inputs: &PostInputs, x, y| Vec3::ONE - inputs.color(x as i32, y as i32));
+    let result = run(&pipeline, &Framebuffer::new(4, 10));
+    assert_eq!(result.get_pixel(0, 0), 0xFFFFFF);
+    assert_eq!(result.get_pixel(3, 9), 0x000000);
+    assert_eq!(red(&result, 2, 3), 170);
+    assert_eq!(pipeline.len(), 2);
+}
+
+#[test]
+fn threads_do_not_change_the_result() {
+    let source = image(17, 13, |x, y| {
+        Vec3::new(((x * 37 + y * 11) % 256) as f32, (x * 15) as f32, (y * 19) as f32)
+    });
+    let mut pipeline = PostPipeline::new();
+    for pass in GaussianBlur::both_axes(1.5) {
+        pipeline.add(pass);
+    }
+    pipeline.add(Fxaa::default());
+    pipeline.set_threads(1);
+    let single = run(&pipeline, &source);
+    pipeline.set_threads(5);
+    assert_eq!(run(&pipeline, &source).get_raw(), single.get_raw());
+}
+
+#[test]
+fn gaussian_blur_spreads_light_evenly() {
+    let source = image(15, 15, |x, y| {
+        if (x, y) == (7, 7) {
+            Vec3::splat(255.0)
+        } else {
+            Vec3::ZERO
+        }
+    });
+    let mut pipeline = PostPipeline::new();
+    for pass in GaussianBlur::both_axes(0.8) {
+        pipeline.add(pass);
+    }
+    let result = run(&pipeline, &source);
+    let center = red(&result, 7, 7);
+    assert!(center < 255 && center > red(&result, 8, 7));
+    // The kernel is symmetric, and spreads the same way along both axes
+    assert_eq!(red(&result, 8, 7), red(&result, 6, 7));
+    assert_eq!(red(&result, 8, 7), red(&result, 7, 8));
+    let total: u32 = (0..15)
+        .flat_map(|y| (0..15).map(move |x| (x, y)))
+        .map(|(x, y)| red(&result, x, y))
+        .sum();
+    assert!(total.abs_diff(255) <= 20, "{total}");
+
+    // A flat image stays as it is
+    let flat = image(8, 8, |_, _| Vec3::splat(100.0));
+    assert_eq!(run(&pipeline, &flat).get_raw(), flat.get_raw());
+}
+
+#[test]
+fn fxaa_smooths_stairs_and_leaves_flat_areas() {
+    // A shallow staircase edge, rising one pixel every four
+    let source = image(32, 16, |x, y| {
+        if y * 4 > x + 16 {
+            Vec3::splat(255.0)
+        } else {
+            Vec3::ZERO
+        }
+    });
+    let mut pipeline = PostPipeline::new();
+    pipeline.add(Fxaa::default());
+    let result = run(&pipeline, &source);
+
+    let blended = (0..32)
+        .flat_map(|x| (0..16).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            let value = red(&result, x, y);
+            value > 0 && value < 255
+        })
+        .count();
+    assert!(blended >= 16, "{blended}");
+    assert_eq!(red(&result, 2, 0), 0);
+    assert_eq!(red(&result, 2, 15), 255);
+}
+
+#[test]
+fn sharpen_exaggerates_contrast() {
+    let source = image(8, 8, |x, _| Vec3::splat(if x < 4 { 100.0 } else { 150.0 }));
+    let mut pipeline = PostPipeline::new();
+    pipeline.add(Sharpen { strength: 0.5 });
+    let result = run(&pipeline, &source);
+    assert_eq!(red(&result, 3, 4), 75);
+    assert_eq!(red(&result, 4, 4), 175);
+    assert_eq!(red(&result, 0, 4), 100);
+}
+
+#[test]
+fn color_luts_map_colors() {
+    let identity = ColorLut::identity(4);
+    let color = Vec3::new(0.1, 0.55, 0.9);
+    assert!(identity.lookup(color).abs_diff_eq(color, EPSILON));
+
+    let invert = ColorLut::from_fn(2, |color| Vec3::ONE - color);
+    assert!(invert
+        .lookup(color)
+        .abs_diff_eq(Vec3::ONE - color, EPSILON));
+
+    // A strip with red along each slice, green down it, and blue across the slices
+    let size = 4;
+    let mut texels = Vec::new();
+    for row in 0..size {
+        for column in 0..size * size {
+            let (r, g, b) = (column % size, size - 1 - row, column / size);
+            texels.push(Vec3::new(r as f32, g as f32, b as f32) / (size - 1) as f32);
+        }
+    }
+    let strip = ColorLut::from_texture(&Texture::new(size * size, size, texels)).unwrap();
+    assert!(strip.lookup(color).abs_diff_eq(color, EPSILON));
+    assert!(ColorLut::from_texture(&Texture::new(8, 4, vec![Vec3::ZERO; 32])).is_err());
+}
+
+#[test]
+#[should_panic(expected = "entry count does not match")]
+fn color_lut_sizes_past_u32_cubes_are_checked() {
+    // 1626 cubed no longer fits in a u32
+    ColorLut::new(1626, vec![Vec3::ZERO; 8]);
+}
+
+#[test]
+#[should_panic(expected = "too many entries")]
+fn color_lut_sizes_past_usize_cubes_are_checked() {
+    ColorLut::from_fn(u32::MAX, |color| color);
+}
+
+#[test]
+fn color_lut_strips_too_wide_to_describe_are_rejected() {
+    let size = 65536;
+    let strip = Texture::new(1, size, vec![Vec3::ZERO; size as usize]);
+    assert!(ColorLut::from_texture(&strip).is_err());
+}
+
+#[test]
+fn renderer_post_processes_its_color_buffer() {
+    let mut renderer = Renderer::new(4, 4);
+    renderer.clear_framebuffer(0x204080);
+    let mut pipeline = PostPipeline::new();
+    pipeline.add(ColorLut::from_fn(2, |color| Vec3::ONE - color));
+    let fb = renderer.post_process(&pipeline);
+    assert_eq!(fb.get_pixel(1, 2), 0xDFBF7F);
+}
@@ -0,0 +1,228 @@
+//! Tests for the scene graph, and for drawing scenes with `Renderer::render_scene`.
+
+#[macro_use]
+extern crate softrender_derive;
+
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{Affine3A, Mat4, UVec3, Vec2, Vec3, Vec4};
+use softrender::{
+    camera::Camera,
+    mesh::{Material, MaterialGroup, Mesh, MeshVertex},
+    renderer::{CullStats, Renderer},
+    scene::{DrawContext, Light, NodeContent, Scene},
+    shader::{Barycentric, Shader},
+};
+
+const EPSILON: f32 = 1e-5;
+
+#[derive(Clone, Barycentric)]
+struct VertexOut {
+    unused: f32,
+}
+
+struct SolidShader {
+    mvp: Mat4,
+    color: UVec3,
+}
+
+impl Shader<MeshVertex, VertexOut> for SolidShader {
+    fn vertex(&self, vertex: &MeshVertex) -> (Vec4, VertexOut) {
+        (
+            self.mvp * vertex.position.extend(1.0),
+            VertexOut { unused: 0.0 },
+        )
+    }
+
+    fn fragment(&self, _inputs: VertexOut) -> UVec3 {
+        self.color
+    }
+}
+
+/// A unit quad in the XY plane, facing +Z
+fn quad() -> Mesh {
+    let vertex = |x, y| MeshVertex {
+        position: Vec3::new(x, y, 0.0),
+        normal: Vec3::Z,
+        uv: Vec2::ZERO,
+        tangent: Vec4::ZERO,
+    };
+    Mesh {
+        vertices: vec![
+            vertex(-0.5, -0.5),
+            vertex(0.5, -0.5),
+            vertex(0.5, 0.5),
+            vertex(-0.5, 0.5),
+        ],
+        indices: vec![0, 1, 2, 0, 2, 3],
+        ..Default::default()
+    }
+}
+
+fn mesh_node(mesh: usize, material: Option<usize>) -> NodeContent {
+    NodeContent::MESH { mesh, material }
+}
+
+#[test]
+fn world_transforms_combine_ancestors() {
+    let mut scene = Scene::new();
+    let root = scene.add_node(
+        None,
+        Affine3A::from_translation(Vec3::new(1.0, 0.0, 0.0)),
+        NodeContent::EMPTY,
+    );
+    let child = scene.add_node(
+        Some(root),
+        Affine3A::from_rotation_z(FRAC_PI_2),
+        NodeContent::EMPTY,
+    );
+    let grandchild = scene.add_node(
+        Some(child),
+        Affine3A::from_translation(Vec3::new(2.0, 0.0, 0.0)),
+        NodeContent::EMPTY,
+    );
+    let position = |scene: &Scene| Vec3::from(scene.node(grandchild).world_transform.translation);
+    assert!(position(&scene).abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), EPSILON));
+
+    scene.node_mut(root).local_transform = Affine3A::from_translation(Vec3::new(0.0, 0.0, 3.0));
+    scene.update_transforms();
+    assert!(position(&scene).abs_diff_eq(Vec3::new(0.0, 2.0, 3.0), EPSILON));
+
+    scene.set_parent(grandchild, None);
+    scene.update_transforms();
+    assert!(position(&scene).abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), EPSILON));
+    assert!(scene.node(child).get_children().is_empty());
+    assert_eq!(scene.roots().collect::<Vec<_>>(), vec![root, grandchild]);
+}
+
+#[test]
+#[should_panic]
+fn parenting_to_a_descendant_panics() {
+    let mut scene = Scene::new();
+    let root = scene.add_node(None, Affine3A::IDENTITY, NodeContent::EMPTY);
+    let child = scene.add_node(Some(root), Affine3A::IDENTITY, NodeContent::EMPTY);
+    scene.set_parent(root, Some(child));
+}
+
+#[test]
+fn cameras_and_lights_are_placed_by_nodes() {
+    let mut scene = Scene::new();
+    let rig = scene.add_node(
+        None,
+        Affine3A::from_translation(Vec3::new(0.0, 0.0, 5.0)),
+        NodeContent::EMPTY,
+    );
+    let camera = scene.add_node(
+        Some(rig),
+        Affine3A::from_rotation_y(FRAC_PI_2),
+        NodeContent::CAMERA(Camera::perspective(1.0, 1.0, 0.1, 10.0, Vec3::ZERO)),
+    );
+    scene.add_node(
+        Some(rig),
+        Affine3A::from_rotation_x(-FRAC_PI_2),
+        NodeContent::LIGHT(Light::directional(Vec3::ONE, 2.0)),
+    );
+    scene.node_mut(camera).name = Some("main".to_string());
+
+    assert_eq!(scene.find_node("main"), Some(camera));
+    let camera = scene.camera(camera).unwrap();
+    assert!(camera
+        .get_position()
+        .abs_diff_eq(Vec3::new(0.0, 0.0, 5.0), EPSILON));
+    assert!(camera.forward().abs_diff_eq(Vec3::NEG_X, EPSILON));
+    assert!(scene.camera(rig).is_none());
+
+    let lights = scene.lights();
+    assert_eq!(lights.len(), 1);
+    assert!(lights[0].direction.abs_diff_eq(Vec3::NEG_Y, EPSILON));
+    assert_eq!(lights[0].light.intensity, 2.0);
+}
+
+#[test]
+fn draws_are_sorted_and_culled() {
+    let mut scene = Scene::new();
+    let mesh = scene.add_mesh(quad());
+    let glass = scene.add_material(Material {
+        opacity: 0.5,
+        ..Default::default()
+    });
+    let at = |z| Affine3A::from_translation(Vec3::new(0.0, 0.0, z));
+    let far_opaque = scene.add_node(None, at(-4.0), mesh_node(mesh, None));
+    let near_opaque = scene.add_node(None, at(-1.0), mesh_node(mesh, None));
+    let far_glass = scene.add_node(None, at(-3.0), mesh_node(mesh, Some(glass)));
+    let near_glass = scene.add_node(None, at(-2.0), mesh_node(mesh, Some(glass)));
+    // Behind the camera, so never drawn
+    scene.add_node(None, at(5.0), mesh_node(mesh, None));
+
+    let camera = Camera::perspective(1.0, 1.0, 0.1, 100.0, Vec3::new(0.0, 0.0, 1.0));
+    let mut renderer = Renderer::new(32, 32);
+    renderer.clear_framebuffer(0);
+    let mut order = Vec::new();
+    renderer
+        .render_scene(&scene, &camera, |context: &DrawContext| {
+            order.push(context.node);
+            SolidShader {
+                mvp: context.view_proj * Mat4::from(context.model),
+                color: UVec3::splat(255),
+            }
+        })
+        .unwrap();
+
+    // Without blending, materials with an opacity below one are sorted as if they were opaque
+    assert_eq!(order, &[near_opaque, near_glass, far_glass, far_opaque]);
+    assert_eq!(
+        renderer.get_cull_stats(),
+        CullStats {
+            drawn: 4,
+            culled: 1
+        }
+    );
+    assert_eq!(renderer.get_color_buffer().get_pixel(16, 16), 0xFFFFFF);
+}
+
+#[test]
+fn groups_are_drawn_with_their_materials() {
+    let mut mesh = quad();
+    mesh.materials = vec![
+        Material {
+            diffuse: Vec3::X,
+            ..Default::default()
+        },
+        Material {
+            diffuse: Vec3::Y,
+            ..Default::default()
+        },
+    ];
+    mesh.groups = vec![
+        MaterialGroup {
+            material: Some(0),
+            indices: 0..3,
+        },
+        MaterialGroup {
+            material: Some(1),
+            indices: 3..6,
+        },
+    ];
+    let mut scene = Scene::new();
+    let mesh = scene.add_mesh(mesh);
+    scene.add_node(
+        None,
+        Affine3A::from_translation(Vec3::new(0.0, 0.0, -1.0)),
+        mesh_node(mesh, None),
+    );
+
+    let camera = Camera::orthographic(1.0, 1.0, 0.1, 10.0, Vec3::ZERO);
+    let mut renderer = Renderer::new(64, 64);
+    renderer.clear_framebuffer(0);
+    renderer
+        .render_scene(&scene, &camera, |context: &DrawContext| SolidShader {
+            mvp: context.view_proj * Mat4::from(context.model),
+            color: (context.material.unwrap().diffuse * 255.0).as_uvec3(),
+        })
+        .unwrap();
+
+    let fb = renderer.get_color_buffer();
+    // The first triangle covers the bottom right half of the quad, and the second the top left
+    assert_eq!(fb.get_pixel(48, 16), 0xFF0000);
+    assert_eq!(fb.get_pixel(16, 48), 0x00FF00);
+}
@@ -0,0 +1,263 @@
+//! Tests for the built-in shaders, drawing a quad that fills the framebuffer and checking the color
+//! at its center.
+
+use glam::{Affine3A, Mat3, Mat4, Vec2, Vec3, Vec4};
+use softrender::{
+    mesh::{Material, MeshVertex},
+    renderer::Renderer,
+    scene::{Light, LightInstance},
+    shader::{Barycentric, Shader},
+    shaders::{
+        BlinnPhongShader, ColorVertex, Filtering, GouraudShader, TexturedShader, TexturedVertex,
+        UnlitShader, VertexColorShader,
+    },
+    texture::Texture,
+};
+
+const SIZE: u32 = 16;
+const CENTER: u32 = SIZE / 2;
+
+/// A quad covering the whole of clip space at z = 0, facing the camera along +Z, with its tangent
+/// along +X
+fn quad() -> Vec<MeshVertex> {
+    [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+        .map(|(u, v)| MeshVertex {
+            position: Vec3::new(u * 2.0 - 1.0, v * 2.0 - 1.0, 0.0),
+            normal: Vec3::Z,
+            uv: Vec2::new(u, v),
+            tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
+        })
+        .to_vec()
+}
+
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+fn render<S, V, VO>(shader: &mut S, vertices: &[V]) -> u32
+where
+    S: Shader<V, VO>,
+    VO: Barycentric + Clone,
+{
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.clear_framebuffer(0);
+    renderer
+        .draw(shader, vertices, &QUAD_INDICES)
+        .get_pixel(CENTER, CENTER)
+}
+
+fn directional(direction: Vec3, intensity: f32) -> LightInstance {
+    LightInstance {
+        light: Light::directional(Vec3::ONE, intensity),
+        position: Vec3::ZERO,
+        direction: direction.normalize(),
+    }
+}
+
+fn blinn_phong<'a>(lights: &'a [LightInstance], material: &'a Material) -> BlinnPhongShader<'a> {
+    BlinnPhongShader {
+        view_proj: Mat4::IDENTITY,
+        model: Affine3A::IDENTITY,
+        normal_matrix: Mat3::IDENTITY,
+        camera_position: Vec3::new(0.0, 0.0, 5.0),
+        lights,
+        ambient_light: Vec3::ZERO,
+        material,
+        diffuse_texture: None,
+        specular_texture: None,
+        normal_texture: None,
+        normal_scale: 1.0,
+        // Linear output keeps the expected colors simple
+        srgb_output: false,
+    }
+}
+
+#[test]
+fn unlit_fills_with_color() {
+    let mut shader = UnlitShader {
+        mvp: Mat4::IDENTITY,
+        color: Vec3::new(1.0, 0.5, 0.0),
+    };
+    assert_eq!(render(&mut shader, &quad()), 0xFF8000);
+}
+
+#[test]
+fn vertex_colors_are_interpolated() {
+    let vertices = quad()
+        .iter()
+        .map(|vertex| ColorVertex {
+            position: vertex.position,
+            color: Vec3::new(vertex.uv.x, 0.0, 1.0),
+        })
+        .collect::<Vec<_>>();
+    let color = render(
+        &mut VertexColorShader {
+            mvp: Mat4::IDENTITY,
+        },
+        &vertices,
+    );
+    assert!((color >> 16).abs_diff(0x80) <= 10, "{color:06x}");
+    assert_eq!(color & 0xFFFF, 0x00FF);
+}
+
+#[test]
+fn textures_are_sampled() {
+    let texture = Texture::new(2, 2, vec![Vec3::X, Vec3::Y, Vec3::Z, Vec3::ONE]);
+    let vertices = quad()
+        .iter()
+        .map(|vertex| TexturedVertex {
+            position: vertex.position,
+            uv: vertex.uv * 0.5,
+        })
+        .collect::<Vec<_>>();
+    let mut shader = TexturedShader::new(Mat4::IDENTITY, &texture);
+    shader.filtering = Filtering::NEAREST;
+    shader.tint = Vec3::new(1.0, 1.0, 0.0);
+    assert_eq!(render(&mut shader, &vertices), 0xFF0000);
+}
+
+#[test]
+fn color_textures_are_decoded_from_srgb() {
+    // An sRGB mid gray, which is about 0.214 in linear light
+    let texture = Texture::new(1, 1, vec![Vec3::splat(0.5)]);
+    let vertices = quad()
+        .iter()
+        .map(|vertex| TexturedVertex {
+            position: vertex.position,
+            uv: vertex.uv,
+        })
+        .collect::<Vec<_>>();
+    let mut shader = TexturedShader::new(Mat4::IDENTITY, &texture);
+    assert_eq!(render(&mut shader, &vertices), 0x808080);
+    // The tint applies in linear light
+    shader.tint = Vec3::splat(0.5);
+    assert_eq!(render(&mut shader, &vertices) & 0xFF, 0x5C);
+    shader.srgb_output = false;
+    assert_eq!(render(&mut shader, &vertices) & 0xFF, 0x1B);
+
+    let lights = [directional(Vec3::NEG_Z, 1.0)];
+    let material = Material::default();
+    let mut shader = blinn_phong(&lights, &material);
+    shader.diffuse_texture = Some(&texture);
+    assert_eq!(render(&mut shader, &quad()) & 0xFF, 0x37);
+    shader.srgb_output = true;
+    assert_eq!(render(&mut shader, &quad()) & 0xFF, 0x80);
+}
+
+#[test]
+fn blinn_phong_diffuse_and_ambient() {
+    let lights = [directional(Vec3::NEG_Z, 1.0)];
+    let material = Material {
+        diffuse: Vec3::splat(0.5),
+        ..Default::default()
+    };
+    let mut shader = blinn_phong(&lights, &material);
+    assert_eq!(render(&mut shader, &quad()), 0x808080);
+
+    shader.ambient_light = Vec3::new(1.0, 0.0, 0.0);
+    assert_eq!(render(&mut shader, &quad()), 0xFF8080);
+
+    // Lights from behind the surface contribute nothing
+    let lights = [directional(Vec3::Z, 1.0)];
+    let mut shader = blinn_phong(&lights, &material);
+    assert_eq!(render(&mut shader, &quad()), 0x000000);
+}
+
+#[test]
+fn blinn_phong_specular_highlight() {
+    let lights = [directional(Vec3::NEG_Z, 1.0)];
+    let material = Material {
+        diffuse: Vec3::ZERO,
+        specular: Vec3::ONE,
+        shininess: 32.0,
+        ..Default::default()
+    };
+    // The camera looks straight down the normal, so the highlight is at its brightest
+    assert_eq!(
+        render(&mut blinn_phong(&lights, &material), &quad()),
+        0xFFFFFF
+    );
+}
+
+#[test]
+fn point_and_spot_lights_attenuate() {
+    let material = Material::default();
+    let point = LightInstance {
+        light: Light::point(Vec3::ONE, 2.0, 100.0),
+        position: Vec3::new(0.0, 0.0, 2.0),
+        direction: Vec3::NEG_Z,
+    };
+    // Intensity falls off with the square of the distance
+    let color = render(&mut blinn_phong(&[point], &material), &quad());
+    assert!((color & 0xFF).abs_diff(128) <= 1, "{color:06x}");
+
+    // Out of range
+    let mut short = point;
+    short.light = Light::point(Vec3::ONE, 2.0, 1.0);
+    assert_eq!(render(&mut blinn_phong(&[short], &material), &quad()), 0);
+
+    let spot = |direction: Vec3| LightInstance {
+        light: Light::spot(Vec3::ONE, 8.0, 100.0, 0.2, 0.3),
+        position: Vec3::new(0.0, 0.0, 2.0),
+        direction,
+    };
+    assert_eq!(
+        render(&mut blinn_phong(&[spot(Vec3::NEG_Z)], &material), &quad()),
+        0xFFFFFF
+    );
+    assert_eq!(
+        render(&mut blinn_phong(&[spot(Vec3::X)], &material), &quad()),
+        0
+    );
+}
+
+#[test]
+fn normal_maps_tilt_the_surface() {
+    // A normal tilted 45 degrees towards +X, encoded into the 0-1 range
+    let tilted = Vec3::new(1.0, 0.0, 1.0).normalize();
+    let normal_map = Texture::solid(tilted * 0.5 + 0.5);
+    // Grazing light along -X, which barely lights the flat surface
+    let lights = [directional(Vec3::new(-1.0, 0.0, -0.01), 1.0)];
+    let material = Material::default();
+
+    let mut shader = blinn_phong(&lights, &material);
+    assert!(render(&mut shader, &quad()) & 0xFF < 8);
+
+    shader.normal_texture = Some(&normal_map);
+    let color = render(&mut shader, &quad()) & 0xFF;
+    assert!(color.abs_diff(182) <= 2, "{color}");
+
+    // Flipping the handedness of the tangent frame doesn't change a normal with no bitangent part
+    let flipped = quad()
+        .into_iter()
+        .map(|vertex| MeshVertex {
+            tangent: Vec4::new(1.0, 0.0, 0.0, -1.0),
+            ..vertex
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(render(&mut shader, &flipped) & 0xFF, color);
+}
+
+#[test]
+fn gouraud_matches_per_pixel_lighting_on_flat_surfaces() {
+    let lights = [directional(Vec3::new(0.3, -0.2, -1.0), 1.0)];
+    let material = Material {
+        diffuse: Vec3::new(0.8, 0.4, 0.2),
+        ..Default::default()
+    };
+    let mut gouraud = GouraudShader {
+        view_proj: Mat4::IDENTITY,
+        model: Affine3A::IDENTITY,
+        normal_matrix: Mat3::IDENTITY,
+        camera_position: Vec3::new(0.0, 0.0, 5.0),
+        lights: &lights,
+        ambient_light: Vec3::splat(0.1),
+        material: &material,
+        diffuse_texture: None,
+        srgb_output: false,
+    };
+    let mut per_pixel = blinn_phong(&lights, &material);
+    per_pixel.ambient_light = Vec3::splat(0.1);
+    assert_eq!(
+        render(&mut gouraud, &quad()),
+        render(&mut per_pixel, &quad())
+    );
+}
@@ -0,0 +1,117 @@
+//! Tests for depth-only drawing, and for two pass shadow mapping with `ShadowSampler`.
+
+mod common;
+
+use common::{rect_xy, rect_xz, NoFragmentShader, QUAD_INDICES};
+use glam::{Mat4, Vec2, Vec3};
+use softrender::{
+    fb::Framebuffer,
+    math::BoundingSphere,
+    renderer::{DrawMode, Renderer},
+    scene::{Light, LightInstance},
+    shaders::UnlitShader,
+    shadow::{light_view_proj, ShadowSampler},
+};
+
+/// A square in the XZ plane at `height`, facing +Y
+fn square(height: f32, half_size: f32) -> Vec<Vec3> {
+    rect_xz(Vec2::splat(-half_size), Vec2::splat(half_size), height)
+}
+
+#[test]
+fn depth_only_draws_skip_color() {
+    let mut renderer = Renderer::new(8, 8);
+    renderer.clear_framebuffer(0x123456);
+    renderer.set_draw_mode(DrawMode::DEPTH_ONLY);
+    let mut shader = NoFragmentShader {
+        mvp: Mat4::IDENTITY,
+    };
+    let cb = renderer.draw(
+        &mut shader,
+        &rect_xy(-Vec2::ONE, Vec2::ONE, 0.5),
+        &QUAD_INDICES,
+    );
+    assert!(cb.get_raw().iter().all(|&color| color == 0x123456));
+    assert_eq!(renderer.get_depth_buffer().get_pixel(4, 4), 0.5);
+}
+
+/// Draws a ground square with a smaller square floating above it into a shadow map
+fn shadow_pass(light_view_proj: Mat4) -> Renderer {
+    let mut renderer = Renderer::new(64, 64);
+    renderer.clear_framebuffer(0);
+    renderer.set_draw_mode(DrawMode::DEPTH_ONLY);
+    let mut shader = UnlitShader {
+        mvp: light_view_proj,
+        color: Vec3::ONE,
+    };
+    renderer.draw(&mut shader, &square(0.0, 2.0), &QUAD_INDICES);
+    renderer.draw(&mut shader, &square(1.0, 0.5), &QUAD_INDICES);
+    renderer
+}
+
+#[test]
+fn directional_shadows() {
+    let sun = LightInstance {
+        light: Light::directional(Vec3::ONE, 1.0),
+        position: Vec3::ZERO,
+        direction: Vec3::NEG_Y,
+    };
+    let bounds = BoundingSphere::new(Vec3::new(0.0, 0.5, 0.0), 3.0);
+    let view_proj = light_view_proj(&sun, &bounds).unwrap();
+    let renderer = shadow_pass(view_proj);
+    let sampler = ShadowSampler::new(renderer.get_depth_buffer());
+
+    let visibility = |x: f32, y: f32| sampler.visibility(view_proj, Vec3::new(x, y, 0.0));
+    // Under the floating square, and beside it
+    assert_eq!(visibility(0.0, 0.0), 0.0);
+    assert_eq!(visibility(1.5, 0.0), 1.0);
+    // Surfaces don't shadow themselves
+    assert_eq!(visibility(0.0, 1.0), 1.0);
+    // Filtering softens the edge of the shadow
+    let edge = visibility(0.5, 0.0);
+    assert!(edge > 0.0 && edge < 1.0, "{edge}");
+
+    // Without filtering, the edge is as sharp as the map allows
+    let mut sharp = ShadowSampler::new(renderer.get_depth_buffer());
+    sharp.pcf_radius = 0;
+    assert_eq!(sharp.visibility(view_proj, Vec3::new(0.4, 0.0, 0.0)), 0.0);
+    assert_eq!(sharp.visibility(view_proj, Vec3::new(0.6, 0.0, 0.0)), 1.0);
+
+    // Points outside of the map are lit
+    assert_eq!(visibility(10.0, 0.0), 1.0);
+}
+
+#[test]
+fn empty_shadow_maps_shadow_nothing() {
+    let depth = Framebuffer::new(0, 0);
+    let sampler = ShadowSampler::new(&depth);
+    assert_eq!(sampler.compare(Vec2::ZERO, 0.5), 1.0);
+    assert_eq!(sampler.compare_ndc(Vec3::new(0.0, 0.0, 0.5)), 1.0);
+    assert_eq!(sampler.visibility(Mat4::IDENTITY, Vec3::ZERO), 1.0);
+}
+
+#[test]
+fn spot_light_shadows() {
+    let spot = LightInstance {
+        light: Light::spot(Vec3::ONE, 1.0, 100.0, 0.6, 0.8),
+        position: Vec3::new(0.0, 3.0, 0.0),
+        direction: Vec3::NEG_Y,
+    };
+    let bounds = BoundingSphere::new(Vec3::new(0.0, 0.5, 0.0), 2.5);
+    let view_proj = light_view_proj(&spot, &bounds).unwrap();
+    let renderer = shadow_pass(view_proj);
+    let sampler = ShadowSampler::new(renderer.get_depth_buffer());
+
+    // The shadow spreads out from the light, reaching 0.75 from the center on the ground
+    let visibility = |x: f32| sampler.visibility(view_proj, Vec3::new(x, 0.0, 0.0));
+    assert_eq!(visibility(0.0), 0.0);
+    assert_eq!(visibility(0.5), 0.0);
+    assert_eq!(visibility(1.0), 1.0);
+
+    // Point lights would need a map for every direction
+    let bulb = LightInstance {
+        light: Light::point(Vec3::ONE, 1.0, 10.0),
+        ..spot
+    };
+    assert!(light_view_proj(&bulb, &bounds).is_none());
+}
@@ -0,0 +1,75 @@
+//! Tests for screen-space ambient occlusion, on a floor meeting a wall.
+
+mod common;
+
+use common::{rect_xy, rect_xz, QUAD_INDICES};
+use glam::{Mat4, Vec2, Vec3, Vec4Swizzles};
+use softrender::{camera::Camera, renderer::Renderer, shaders::UnlitShader, ssao::Ssao};
+
+const SIZE: u32 = 128;
+
+/// A floor at y = 0 facing up, running back to a wall at z = -5 facing the camera
+fn floor_and_wall() -> [Vec<Vec3>; 2] {
+    let floor = rect_xz(Vec2::new(-5.0, -5.0), Vec2::new(5.0, 0.0), 0.0);
+    let wall = rect_xy(Vec2::new(-5.0, 0.0), Vec2::new(5.0, 5.0), -5.0);
+    [floor, wall]
+}
+
+fn camera() -> Camera {
+    let mut camera = Camera::perspective(1.2, 1.0, 0.1, 20.0, Vec3::new(0.0, 2.0, 0.0));
+    camera.look_at(Vec3::new(0.0, 0.5, -5.0), Vec3::Y);
+    camera
+}
+
+fn render(meshes: &[Vec<Vec3>]) -> Renderer {
+    let camera = camera();
+    let mut renderer = Renderer::new(SIZE, SIZE);
+    renderer.clear_framebuffer(0x0000FF);
+    let mut shader = UnlitShader {
+        mvp: camera.view_projection_matrix(),
+        color: Vec3::ONE,
+    };
+    for vertices in meshes {
+        renderer.draw(&mut shader, vertices, &QUAD_INDICES);
+    }
+    renderer
+}
+
+/// The pixel that a point in the world is drawn at
+fn pixel(point: Vec3) -> (u32, u32) {
+    let clip = camera().view_projection_matrix() * point.extend(1.0);
+    let screen = (clip.xy() / clip.w + 1.0) / 2.0 * (SIZE - 1) as f32;
+    (screen.x.round() as u32, screen.y.round() as u32)
+}
+
+#[test]
+fn flat_surfaces_are_not_occluded() {
+    let [floor, _] = floor_and_wall();
+    let renderer = render(&[floor]);
+    let occlusion =
+        Ssao::new(16, 0.5).compute(renderer.get_depth_buffer(), camera().projection_matrix());
+    let least = occlusion.get_raw().iter().copied().fold(1.0, f32::min);
+    assert!(least > 0.95, "{least}");
+}
+
+#[test]
+fn corners_are_darkened() {
+    let mut renderer = render(&floor_and_wall());
+    let projection: Mat4 = camera().projection_matrix();
+    let ssao = Ssao::new(16, 0.5);
+    let occlusion = ssao.compute(renderer.get_depth_buffer(), projection);
+
+    let (corner_x, corner_y) = pixel(Vec3::new(0.0, 0.0, -5.0));
+    let (open_x, open_y) = pixel(Vec3::new(0.0, 0.0, -2.0));
+    let corner = occlusion.get_pixel(corner_x, corner_y);
+    let open = occlusion.get_pixel(open_x, open_y);
+    assert!(corner < 0.85, "{corner}");
+    assert!(open > 0.95, "{open}");
+
+    let fb = ssao.apply(&mut renderer, projection);
+    let corner_color = fb.get_pixel(corner_x, corner_y);
+    assert!(corner_color & 0xFF < 0xE0, "{corner_color:06x}");
+    assert_eq!(corner_color >> 16, corner_color & 0xFF);
+    // The background is left alone
+    assert_eq!(fb.get_pixel(0, SIZE - 1), 0x0000FF);
+}
@@ -0,0 +1,92 @@
+//! Tests for the image comparison used by the golden-image harness.
+
+use softrender::{
+    error::Error,
+    export::ImageFormat,
+    fb::Framebuffer,
+    testing::{compare, load_reference, perceptual_delta, Tolerance},
+};
+
+fn solid(width: u32, height: u32, color: u32) -> Framebuffer<u32> {
+    let mut fb = Framebuffer::new(width, height);
+    fb.fill(color);
+    fb
+}
+
+#[test]
+fn identical_images_pass_exactly() {
+    let fb = solid(4, 4, 0x336699);
+    let comparison = compare(&fb, &fb, &Tolerance::exact()).unwrap();
+    assert_eq!(comparison.mismatched, 0);
+    assert_eq!(comparison.rmse, 0.0);
+    assert!(comparison.passed(&Tolerance::exact()));
+}
+
+#[test]
+fn counts_pixels_beyond_tolerance() {
+    let reference = solid(4, 4, 0x808080);
+    let mut actual = solid(4, 4, 0x808080);
+    actual.plot_pixel(0, 0, 0x818080);
+    actual.plot_pixel(3, 3, 0xFF8080);
+
+    let tolerance = Tolerance {
+        per_pixel: 1,
+        perceptual: None,
+        max_mismatched: 0,
+        max_rmse: f64::INFINITY,
+    };
+    let comparison = compare(&actual, &reference, &tolerance).unwrap();
+    assert_eq!(comparison.mismatched, 1);
+    assert_eq!(comparison.max_channel_delta, 127);
+    assert!(!comparison.passed(&tolerance));
+
+    // Both differing pixels are marked in the diff, in the framebuffer's orientation
+    assert_eq!(comparison.diff.get_pixel(0, 0), 0xFFFF00);
+    assert_eq!(comparison.diff.get_pixel(3, 3), 0xFF0000);
+
+    let expected_rmse = ((1.0f64 + 127.0 * 127.0) / 48.0).sqrt();
+    assert!((comparison.rmse - expected_rmse).abs() < 1e-9);
+}
+
+#[test]
+fn perceptual_tolerance_accepts_invisible_changes() {
+    // A small shift in blue is far less visible than the same shift in green
+    assert!(perceptual_delta(0x000000, 0x000008) < perceptual_delta(0x000000, 0x000800));
+    assert!((perceptual_delta(0x000000, 0xFFFFFF) - 1.0).abs() < 1e-3);
+
+    let reference = solid(2, 2, 0x202020);
+    let actual = solid(2, 2, 0x202026);
+    let tolerance = Tolerance {
+        per_pixel: 0,
+        perceptual: Some(0.05),
+        max_mismatched: 0,
+        max_rmse: f64::INFINITY,
+    };
+    assert_eq!(
+        compare(&actual, &reference, &tolerance).unwrap().mismatched,
+        0
+    );
+}
+
+#[test]
+fn rejects_images_of_different_sizes() {
+    assert!(matches!(
+        compare(&solid(2, 2, 0), &solid(2, 3, 0), &Tolerance::default()),
+        Err(Error::SizeMismatch { .. })
+    ));
+}
+
+#[test]
+fn reference_round_trips_through_ppm() {
+    let mut fb = solid(3, 2, 0x102030);
+    fb.plot_pixel(0, 0, 0xFF0000);
+    fb.plot_pixel(2, 1, 0x00FF00);
+
+    let path = std::env::temp_dir().join(format!("softrender-{}.ppm", std::process::id()));
+    fb.write_image(&mut std::fs::File::create(&path).unwrap(), ImageFormat::PPM)
+        .unwrap();
+    let loaded = load_reference(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.get_raw(), fb.get_raw());
+}
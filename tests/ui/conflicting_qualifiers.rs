@@ -0,0 +1,11 @@
+#[macro_use]
+extern crate softrender_derive;
+
+#[derive(Clone, Barycentric)]
+struct Conflicting {
+    #[flat]
+    #[noperspective]
+    value: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate softrender_derive;
+
+#[derive(Clone, Barycentric)]
+enum Shape {
+    Point(f32),
+    Line(f32, f32),
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate softrender_derive;
+
+#[derive(Clone, Barycentric)]
+struct Arguments {
+    #[flat(provoking)]
+    value: f32,
+}
+
+fn main() {}
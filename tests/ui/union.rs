@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate softrender_derive;
+
+#[derive(Clone, Copy, Barycentric)]
+union Bits {
+    float: f32,
+    int: u32,
+}
+
+fn main() {}
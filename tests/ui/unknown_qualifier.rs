@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate softrender_derive;
+
+#[derive(Clone, Barycentric)]
+struct Unknown {
+    #[barycentric(smooth)]
+    value: f32,
+}
+
+fn main() {}
@@ -0,0 +1,143 @@
+//! Tests for drawing into a region of a larger framebuffer, and for borrowed framebuffer views.
+
+use glam::{UVec3, Vec3, Vec4};
+use softrender::{
+    error::Error,
+    fb::{Framebuffer, FramebufferView, FramebufferViewMut},
+    renderer::Renderer,
+    shader::Shader,
+};
+
+const BACKGROUND: u32 = 0x000000;
+const FILL: u32 = 0xFF0000;
+
+struct FillShader;
+
+impl Shader<Vec3, ()> for FillShader {
+    fn vertex(&self, vertex: &Vec3) -> (Vec4, ()) {
+        (vertex.extend(1.0), ())
+    }
+
+    fn fragment(&self, _inputs: ()) -> UVec3 {
+        UVec3::new(255, 0, 0)
+    }
+}
+
+/// Two triangles covering all of normalized device space
+fn fullscreen_quad() -> (Vec<Vec3>, Vec<u32>) {
+    let vertices = vec![
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::new(1.0, -1.0, 0.0),
+        Vec3::new(-1.0, 1.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+    ];
+    (vertices, vec![0, 1, 2, 2, 1, 3])
+}
+
+#[test]
+fn draws_only_inside_viewport() {
+    let (vertices, indices) = fullscreen_quad();
+    let mut renderer = Renderer::new(64, 48);
+    renderer.clear_framebuffer(BACKGROUND);
+    renderer.set_viewport(32, 8, 24, 16).unwrap();
+    let fb = renderer.draw(&mut FillShader, &vertices, &indices);
+
+    for y in 0..fb.get_height() {
+        for x in 0..fb.get_width() {
+            let inside = (32..56).contains(&x) && (8..24).contains(&y);
+            let expected = if inside { FILL } else { BACKGROUND };
+            assert_eq!(fb.get_pixel(x, y), expected, "pixel ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+fn clear_is_limited_to_viewport() {
+    let mut renderer = Renderer::new(16, 16);
+    renderer.clear_framebuffer(BACKGROUND);
+    renderer.set_viewport(0, 8, 16, 8).unwrap();
+    renderer.clear_framebuffer(FILL);
+    let fb = renderer.draw(&mut FillShader, &[], &[]);
+
+    assert_eq!(fb.get_pixel(0, 7), BACKGROUND);
+    assert_eq!(fb.get_pixel(0, 8), FILL);
+    assert_eq!(fb.get_pixel(15, 15), FILL);
+}
+
+#[test]
+fn rejects_viewport_outside_framebuffer() {
+    let mut renderer = Renderer::new(16, 16);
+    assert!(matches!(
+        renderer.set_viewport(8, 0, 9, 16),
+        Err(Error::RegionOutOfBounds { .. })
+    ));
+    assert_eq!(renderer.get_viewport().width, 16);
+}
+
+#[test]
+fn dimensions_beyond_u16() {
+    let mut fb = Framebuffer::<u8>::new(70_000, 2);
+    fb.plot_pixel(69_999, 1, 7);
+    assert_eq!(fb.get_pixel(69_999, 1), 7);
+    assert_eq!(fb.get_raw()[69_999], 7);
+}
+
+#[test]
+fn views_address_region_with_stride() {
+    let mut fb = Framebuffer::<u32>::new(8, 6);
+    {
+        let mut view = fb.view_mut(2, 1, 3, 4).unwrap();
+        assert_eq!(view.get_stride(), 8);
+        view.fill(1);
+        view.plot_pixel(0, 0, 2);
+    }
+    assert_eq!(fb.get_pixel(2, 1), 2);
+    assert_eq!(fb.get_pixel(4, 4), 1);
+    assert_eq!(fb.get_pixel(5, 4), 0);
+    assert_eq!(fb.get_pixel(2, 5), 0);
+
+    let view = fb.view(2, 1, 3, 4).unwrap();
+    assert_eq!(view.row(0), &[2, 1, 1]);
+    assert!(view.try_get_pixel(3, 0).is_err());
+    assert!(fb.view(6, 0, 3, 1).is_err());
+}
+
+#[test]
+fn copies_into_raw_buffer_region() {
+    let mut src = Framebuffer::<u32>::new(2, 2);
+    src.plot_pixel(0, 0, 1);
+    src.plot_pixel(1, 1, 2);
+
+    // A 4x3 window buffer, drawn into at its top right corner
+    let mut window = vec![0u32; 12];
+    let mut dst = FramebufferViewMut::from_raw(&mut window[2..], 2, 2, 4).unwrap();
+    dst.copy_from(&src.as_view()).unwrap();
+    assert_eq!(window, [0, 0, 0, 2, 0, 0, 1, 0, 0, 0, 0, 0]);
+
+    assert!(matches!(
+        FramebufferView::from_raw(&window, 4, 4, 4),
+        Err(Error::BufferTooSmall { .. })
+    ));
+}
+
+#[test]
+fn raw_strides_too_large_to_address_are_rejected() {
+    let window = vec![0u32; 12];
+    // Two rows this far apart would overflow the offset of the second
+    assert!(matches!(
+        FramebufferView::from_raw(&window, 1, 3, usize::MAX / 2 + 1),
+        Err(Error::BufferTooSmall {
+            required: usize::MAX,
+            len: 12
+        })
+    ));
+    // A single row never steps by the stride, so any stride fits
+    assert!(FramebufferView::from_raw(&window, 4, 1, usize::MAX).is_ok());
+    assert!(matches!(
+        FramebufferView::from_raw(&window, 4, 3, 2),
+        Err(Error::InvalidStride {
+            stride: 2,
+            width: 4
+        })
+    ));
+}